@@ -0,0 +1,132 @@
+// Classroom page: students open a teacher's quiz set with its join code and answer every question once.
+// /class.html?code=ABC123 pre-fills the code. Answers are sent together after the last question; the
+// score and the answers are shown once the teacher closes the set.
+document.addEventListener('DOMContentLoaded', () => {
+    const params = new URLSearchParams(window.location.search);
+    const lang = params.get('lang') || ((navigator.language || 'ja').toLowerCase().startsWith('en') ? 'en' : 'ja');
+    const UI = {
+        ja: {
+            title: 'たぬき？クイズ クラス', code: 'クラスコード', nickname: 'ニックネーム', open: 'はじめる',
+            round: (r, n) => `${r} / ${n} 問目`, sending: '送信中…', score: (s, n) => `${n} 問中 ${s} 問正解`,
+            already: 'このクイズセットには回答済みです', correct: '正解', wrong: a => `不正解（正解は「${a}」）`,
+            pending: '回答を送信しました。先生がクイズセットを締め切ると結果が見られます', check: '結果を見る',
+        },
+        en: {
+            title: 'Tanuki? Quiz class', code: 'Class code', nickname: 'Nickname', open: 'Start',
+            round: (r, n) => `Question ${r} of ${n}`, sending: 'Sending…', score: (s, n) => `${s} of ${n} correct`,
+            already: 'You have already answered this quiz set', correct: 'Correct', wrong: a => `Wrong (the answer was "${a}")`,
+            pending: 'Your answers were sent. The results appear once your teacher closes the quiz set', check: 'Show results',
+        },
+    };
+    const ui = UI[lang] || UI.ja;
+    const $ = id => document.getElementById(id);
+
+    $('class-title').textContent = ui.title;
+    $('class-code').placeholder = ui.code;
+    $('class-code').value = params.get('code') || '';
+    $('class-nickname').placeholder = ui.nickname;
+    $('class-open').textContent = ui.open;
+
+    // same anonymous id as the quiz page, so a student cannot answer a set twice from one browser
+    function playerId() {
+        let id = localStorage.getItem('tanuki_player_id');
+        if (!id) {
+            id = (crypto.randomUUID ? crypto.randomUUID() : String(Date.now()) + Math.random().toString(16).slice(2)).replace(/[^A-Za-z0-9_-]/g, '');
+            localStorage.setItem('tanuki_player_id', id);
+        }
+        return id;
+    }
+
+    let set = null;
+    const answers = [];
+
+    async function api(path, options) {
+        const res = await fetch(path + (path.includes('?') ? '&' : '?') + new URLSearchParams({ lang }), options);
+        const data = await res.json();
+        if (!res.ok) throw new Error(data.error ? data.error.message : 'HTTP ' + res.status);
+        return data;
+    }
+
+    $('class-open').onclick = async () => {
+        const code = $('class-code').value.trim().toUpperCase();
+        if (!code || !$('class-nickname').value.trim()) return;
+        // a student who has already answered gets their results instead
+        try {
+            const data = await api(`/api/class/${encodeURIComponent(code)}/result?` + new URLSearchParams({ player_id: playerId() }));
+            $('class-setup').style.display = 'none';
+            showResult(data);
+            return;
+        } catch (e) { /* not answered yet */ }
+        try {
+            set = await api(`/api/class/${encodeURIComponent(code)}?` + new URLSearchParams({ player_id: playerId() }));
+        } catch (e) { $('class-status').textContent = e.message; return; }
+        if (set.submitted) { $('class-status').textContent = ui.already; return; }
+        $('class-setup').style.display = 'none';
+        $('class-title').textContent = set.title;
+        show(0);
+    };
+
+    function show(index) {
+        const q = set.questions[index];
+        $('class-status').textContent = ui.round(index + 1, set.rounds);
+        $('class-question').textContent = q.question;
+        $('options').innerHTML = '';
+        q.choices.forEach(choice => {
+            const wrapper = document.createElement('div');
+            wrapper.className = 'choice-item';
+            const img = document.createElement('img');
+            img.src = choice.image_url;
+            img.className = 'choice-image';
+            wrapper.appendChild(img);
+            const btn = document.createElement('button');
+            btn.textContent = '✔';
+            btn.className = 'option-button';
+            btn.onclick = () => {
+                answers[index] = choice.token;
+                if (index + 1 < set.questions.length) show(index + 1); else submit();
+            };
+            wrapper.appendChild(btn);
+            $('options').appendChild(wrapper);
+        });
+    }
+
+    async function submit() {
+        $('options').innerHTML = '';
+        $('class-question').textContent = '';
+        $('class-status').textContent = ui.sending;
+        let data;
+        try {
+            data = await api(`/api/class/${encodeURIComponent(set.code)}/submit`, {
+                method: 'POST',
+                headers: { 'Content-Type': 'application/json' },
+                body: JSON.stringify({ player_id: playerId(), nickname: $('class-nickname').value.trim(), answers }),
+            });
+        } catch (e) { $('class-status').textContent = e.message; return; }
+        showResult(data);
+    }
+
+    function showResult(data) {
+        $('class-results').innerHTML = '';
+        if (!data.closed) {
+            $('class-status').textContent = ui.pending;
+            const btn = document.createElement('button');
+            btn.textContent = ui.check;
+            btn.className = 'option-button';
+            btn.onclick = async () => {
+                try {
+                    showResult(await api(`/api/class/${encodeURIComponent(data.code)}/result?` + new URLSearchParams({ player_id: playerId() })));
+                } catch (e) { $('class-status').textContent = e.message; }
+            };
+            $('class-results').appendChild(btn);
+            return;
+        }
+        $('class-status').textContent = ui.score(data.score, data.rounds);
+        const list = document.createElement('ol');
+        data.results.forEach(r => {
+            const li = document.createElement('li');
+            li.textContent = r.correct ? ui.correct : ui.wrong(r.correct_label || r.correct_answer);
+            list.appendChild(li);
+        });
+        $('class-results').appendChild(list);
+    }
+});
@@ -0,0 +1,71 @@
+// "Click the feature" page: one annotated photo, the player clicks where the named feature is.
+// Clicks are sent as fractions of the displayed image size; the answer shows the feature's regions.
+document.addEventListener('DOMContentLoaded', () => {
+    const params = new URLSearchParams(window.location.search);
+    const lang = params.get('lang') || ((navigator.language || 'ja').toLowerCase().startsWith('en') ? 'en' : 'ja');
+    const UI = {
+        ja: { title: 'たぬき？クイズ 特徴さがし', next: '次の問題', correct: '正解！', wrong: f => `残念… 「${f}」は緑の部分です` },
+        en: { title: 'Tanuki? Quiz: find the feature', next: 'Next question', correct: 'Correct!', wrong: f => `Not quite… the ${f} is marked in green` },
+    };
+    const ui = UI[lang] || UI.ja;
+    const $ = id => document.getElementById(id);
+    const SVG = 'http://www.w3.org/2000/svg';
+
+    $('hotspot-title').textContent = ui.title;
+    $('hotspot-next').textContent = ui.next;
+
+    let quiz = null;
+    let answered = false;
+
+    async function api(path, options) {
+        const res = await fetch(path + '?' + new URLSearchParams({ lang }), options);
+        const data = await res.json();
+        if (!res.ok) throw new Error(data.error ? data.error.message : 'HTTP ' + res.status);
+        return data;
+    }
+
+    async function load() {
+        $('hotspot-overlay').innerHTML = '';
+        $('hotspot-result').textContent = '';
+        $('hotspot-explanation').textContent = '';
+        $('hotspot-next').style.display = 'none';
+        try {
+            quiz = await api('/api/hotspot_quiz');
+        } catch (e) { $('hotspot-question').textContent = e.message; return; }
+        answered = false;
+        $('hotspot-question').textContent = quiz.question;
+        $('hotspot-image').src = quiz.image_url;
+    }
+
+    function mark(tag, attrs) {
+        const el = document.createElementNS(SVG, tag);
+        Object.entries(attrs).forEach(([k, v]) => el.setAttribute(k, v));
+        $('hotspot-overlay').appendChild(el);
+    }
+
+    $('hotspot-image').addEventListener('click', async e => {
+        if (!quiz || answered) return;
+        answered = true;
+        const box = e.target.getBoundingClientRect();
+        const x = Math.min(1, Math.max(0, (e.clientX - box.left) / box.width));
+        const y = Math.min(1, Math.max(0, (e.clientY - box.top) / box.height));
+        let result;
+        try {
+            result = await api('/api/hotspot_quiz/submit', {
+                method: 'POST',
+                headers: { 'Content-Type': 'application/json' },
+                body: JSON.stringify({ quiz_id: quiz.id, x, y }),
+            });
+        } catch (err) { $('hotspot-result').textContent = err.message; answered = false; return; }
+        (result.regions || []).forEach(r => mark('polygon', {
+            points: r.map(p => p.join(',')).join(' '), fill: 'rgba(0, 200, 0, 0.3)', stroke: 'green', 'stroke-width': 0.005,
+        }));
+        mark('circle', { cx: x, cy: y, r: 0.012, fill: result.correct ? 'green' : 'red' });
+        $('hotspot-result').textContent = result.correct ? ui.correct : ui.wrong(result.correct_label || quiz.feature);
+        $('hotspot-explanation').textContent = result.explanation || '';
+        $('hotspot-next').style.display = '';
+    });
+
+    $('hotspot-next').onclick = load;
+    load();
+});
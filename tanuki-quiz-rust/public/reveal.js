@@ -0,0 +1,85 @@
+// Reveal page: a photo starts blurred and gets clearer on request; answering earlier scores more.
+// /reveal.html?style=pixelate pixelates instead of blurring. Every step returns a new quiz id.
+document.addEventListener('DOMContentLoaded', () => {
+    const params = new URLSearchParams(window.location.search);
+    const lang = params.get('lang') || ((navigator.language || 'ja').toLowerCase().startsWith('en') ? 'en' : 'ja');
+    const UI = {
+        ja: {
+            title: 'たぬき？クイズ じわじわ', clearer: 'もっとはっきり', next: '次の問題',
+            status: (s, n, p) => `${s} / ${n} 段階目・いま答えると ${p} 点`,
+            correct: p => `正解！ ${p} 点`, wrong: a => `残念… 正解は「${a}」`,
+        },
+        en: {
+            title: 'Tanuki? Quiz: slow reveal', clearer: 'Make it clearer', next: 'Next photo',
+            status: (s, n, p) => `Step ${s} of ${n}, worth ${p} points now`,
+            correct: p => `Correct! ${p} points`, wrong: a => `Not quite… it was the ${a}`,
+        },
+    };
+    const ui = UI[lang] || UI.ja;
+    const $ = id => document.getElementById(id);
+
+    $('reveal-title').textContent = ui.title;
+    $('reveal-clearer').textContent = ui.clearer;
+    $('reveal-next').textContent = ui.next;
+
+    let quiz = null;
+
+    async function api(path, options) {
+        const res = await fetch(path + (path.includes('?') ? '&' : '?') + new URLSearchParams({ lang }), options);
+        const data = await res.json();
+        if (!res.ok) throw new Error(data.error ? data.error.message : 'HTTP ' + res.status);
+        return data;
+    }
+
+    function post(path, body) {
+        return api(path, { method: 'POST', headers: { 'Content-Type': 'application/json' }, body: JSON.stringify(body) });
+    }
+
+    function show(q) {
+        quiz = q;
+        $('reveal-question').textContent = q.question;
+        $('reveal-image').src = q.image_url;
+        $('reveal-status').textContent = ui.status(q.step, q.steps, q.points);
+        $('reveal-clearer').disabled = q.step >= q.steps;
+    }
+
+    async function load() {
+        $('reveal-result').textContent = '';
+        $('reveal-explanation').textContent = '';
+        $('reveal-next').style.display = 'none';
+        $('reveal-clearer').style.display = '';
+        $('options').innerHTML = '';
+        const style = params.get('style');
+        try {
+            show(await api('/api/reveal_quiz' + (style ? '?' + new URLSearchParams({ style }) : '')));
+        } catch (e) { $('reveal-status').textContent = e.message; return; }
+        quiz.options.forEach(option => {
+            const btn = document.createElement('button');
+            btn.textContent = option.label;
+            btn.className = 'option-button';
+            btn.onclick = () => answer(option.key);
+            $('options').appendChild(btn);
+        });
+    }
+
+    $('reveal-clearer').onclick = async () => {
+        try {
+            show(await post('/api/reveal_quiz/next', { quiz_id: quiz.id }));
+        } catch (e) { $('reveal-status').textContent = e.message; }
+    };
+
+    async function answer(key) {
+        document.querySelectorAll('#options button').forEach(b => b.disabled = true);
+        $('reveal-clearer').style.display = 'none';
+        let result;
+        try {
+            result = await post('/api/reveal_quiz/submit', { quiz_id: quiz.id, answer: key });
+        } catch (e) { $('reveal-result').textContent = e.message; return; }
+        $('reveal-result').textContent = result.correct ? ui.correct(result.points) : ui.wrong(result.correct_label || result.correct_answer);
+        $('reveal-explanation').textContent = result.explanation || '';
+        $('reveal-next').style.display = '';
+    }
+
+    $('reveal-next').onclick = load;
+    load();
+});
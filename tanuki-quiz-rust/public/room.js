@@ -0,0 +1,154 @@
+// Live room page: the host projects the code and the questions, players answer on their phones.
+// /room.html?code=ABC123 pre-fills the join code; the host creates a room with the button.
+document.addEventListener('DOMContentLoaded', () => {
+    const params = new URLSearchParams(window.location.search);
+    const lang = params.get('lang') || ((navigator.language || 'ja').toLowerCase().startsWith('en') ? 'en' : 'ja');
+    const UI = {
+        ja: {
+            title: 'たぬき？クイズ ルーム', code: 'ルームコード', nickname: 'ニックネーム', join: '参加する', host: 'ルームを作る（先生用）',
+            next: '次へ', waiting: 'ホストの開始を待っています…', players: n => `参加者 ${n} 人`,
+            answered: (a, n) => `${n} 人中 ${a} 人が回答`, sent: '回答しました！結果を待っています…',
+            round: (r, n) => `${r} / ${n} 問目`, answer: a => `正解は「${a}」`, final: '最終結果', points: (s, b) => `${s} 問正解 (+${b})`,
+            correct: '正解！', wrong: '残念…', disconnected: '接続が切れました',
+        },
+        en: {
+            title: 'Tanuki? Quiz room', code: 'Room code', nickname: 'Nickname', join: 'Join', host: 'Create a room (teachers)',
+            next: 'Next', waiting: 'Waiting for the host to start…', players: n => `${n} players`,
+            answered: (a, n) => `${a} of ${n} answered`, sent: 'Answer sent! Waiting for the results…',
+            round: (r, n) => `Question ${r} of ${n}`, answer: a => `The answer was "${a}"`, final: 'Final ranking', points: (s, b) => `${s} correct (+${b})`,
+            correct: 'Correct!', wrong: 'Not quite…', disconnected: 'Disconnected',
+        },
+    };
+    const ui = UI[lang] || UI.ja;
+    const $ = id => document.getElementById(id);
+
+    $('room-title').textContent = ui.title;
+    $('join-code').placeholder = ui.code;
+    $('join-code').value = params.get('code') || '';
+    $('join-nickname').placeholder = ui.nickname;
+    $('join-button').textContent = ui.join;
+    $('host-button').textContent = ui.host;
+    $('next-button').textContent = ui.next;
+
+    // same anonymous id as the quiz page, so a reload rejoins with the same score
+    function playerId() {
+        let id = localStorage.getItem('tanuki_player_id');
+        if (!id) {
+            id = (crypto.randomUUID ? crypto.randomUUID() : String(Date.now()) + Math.random().toString(16).slice(2)).replace(/[^A-Za-z0-9_-]/g, '');
+            localStorage.setItem('tanuki_player_id', id);
+        }
+        return id;
+    }
+
+    let socket = null;
+    let isHost = false;
+
+    function connect(code, query, first) {
+        const proto = window.location.protocol === 'https:' ? 'wss:' : 'ws:';
+        socket = new WebSocket(`${proto}//${window.location.host}/api/rooms/${encodeURIComponent(code)}/ws?${query}`);
+        // the host key goes in the first message, never in the URL
+        if (first) socket.onopen = () => socket.send(JSON.stringify(first));
+        socket.onmessage = e => handle(JSON.parse(e.data));
+        socket.onclose = () => { $('room-status').textContent = ui.disconnected; };
+        $('room-setup').style.display = 'none';
+        $('room-code').textContent = `${ui.code}: ${code}`;
+    }
+
+    $('join-button').onclick = () => {
+        const code = $('join-code').value.trim().toUpperCase();
+        const nickname = $('join-nickname').value.trim();
+        if (!code || !nickname) return;
+        connect(code, new URLSearchParams({ lang, player_id: playerId(), nickname }).toString());
+    };
+
+    $('host-button').onclick = async () => {
+        const res = await fetch('/api/rooms?' + new URLSearchParams({ lang }), {
+            method: 'POST',
+            headers: { 'Content-Type': 'application/json' },
+            body: JSON.stringify({ rounds: Number(params.get('rounds')) || 10, time_limit_secs: Number(params.get('time_limit')) || 20 }),
+        });
+        const data = await res.json();
+        if (!res.ok) { $('room-status').textContent = data.error ? data.error.message : 'HTTP ' + res.status; return; }
+        isHost = true;
+        connect(data.code, new URLSearchParams({ lang, role: 'host' }).toString(), { type: 'host', host_key: data.host_key });
+        $('next-button').style.display = '';
+    };
+
+    $('next-button').onclick = () => socket && socket.send(JSON.stringify({ type: 'next' }));
+
+    function showStandings(standings) {
+        const list = document.createElement('ol');
+        standings.forEach(s => {
+            const li = document.createElement('li');
+            li.value = s.rank;
+            li.textContent = `${s.nickname} — ${ui.points(s.score, s.speed_bonus)}`;
+            list.appendChild(li);
+        });
+        $('room-results').appendChild(list);
+    }
+
+    function handle(ev) {
+        switch (ev.type) {
+            case 'welcome':
+                $('room-status').textContent = ui.waiting;
+                break;
+            case 'lobby':
+                if (!$('room-question').textContent) $('room-status').textContent = `${ui.players(ev.players.length)}: ${ev.players.join(', ')}`;
+                break;
+            case 'question':
+                $('room-results').innerHTML = '';
+                $('room-status').textContent = ui.round(ev.round, ev.rounds);
+                $('room-question').textContent = ev.question;
+                $('options').innerHTML = '';
+                ev.choices.forEach(choice => {
+                    const wrapper = document.createElement('div');
+                    wrapper.className = 'choice-item';
+                    const img = document.createElement('img');
+                    img.src = choice.image_url;
+                    img.className = 'choice-image';
+                    wrapper.appendChild(img);
+                    if (!isHost) {
+                        const btn = document.createElement('button');
+                        btn.textContent = '✔';
+                        btn.className = 'option-button';
+                        btn.onclick = () => {
+                            socket.send(JSON.stringify({ type: 'answer', choice: choice.token }));
+                            document.querySelectorAll('#options button').forEach(b => b.disabled = true);
+                            $('room-status').textContent = ui.sent;
+                        };
+                        wrapper.appendChild(btn);
+                    }
+                    $('options').appendChild(wrapper);
+                });
+                break;
+            case 'progress':
+                if (isHost) $('room-status').textContent = ui.answered(ev.answered, ev.players);
+                break;
+            case 'round_result': {
+                $('options').innerHTML = '';
+                $('room-results').innerHTML = '';
+                const me = ev.results.find(r => r.nickname === $('join-nickname').value.trim());
+                const p = document.createElement('p');
+                p.textContent = (me && !isHost ? (me.correct ? ui.correct : ui.wrong) + ' ' : '') + ui.answer(ev.answer_label);
+                $('room-results').appendChild(p);
+                if (ev.explanation) {
+                    const ex = document.createElement('p');
+                    ex.textContent = ev.explanation;
+                    $('room-results').appendChild(ex);
+                }
+                showStandings(ev.standings);
+                break;
+            }
+            case 'final':
+                $('options').innerHTML = '';
+                $('room-question').textContent = ui.final;
+                $('room-results').innerHTML = '';
+                showStandings(ev.standings);
+                $('next-button').style.display = 'none';
+                break;
+            case 'error':
+                $('room-status').textContent = ev.message;
+                break;
+        }
+    }
+});
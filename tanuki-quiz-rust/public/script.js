@@ -8,16 +8,51 @@ document.addEventListener('DOMContentLoaded', () => {
     const shareButton = document.getElementById('share-button');
 
     let currentQuiz = null;
+    // pass through ?difficulty=easy|normal|hard from the page URL
+    const params = new URLSearchParams(window.location.search);
+    const difficulty = params.get('difficulty');
+    // ?mode=tanuki_or_not shows one image with yes/no buttons instead of picking from several;
+    // ?mode=daily plays today's daily challenge (same questions for everyone, one try per day)
+    const mode = params.get('mode');
+    // ?lang=ja|en overrides the browser language; the server localizes questions and results
+    const lang = params.get('lang') || ((navigator.language || 'ja').toLowerCase().startsWith('en') ? 'en' : 'ja');
+    const UI = {
+        ja: {
+            title: 'たぬき？クイズ', choice: '選択肢', pick: 'これだ！', yes: 'たぬき！', no: 'たぬきじゃない', next: '次の問題へ', more: 'くわしく',
+            correct: '正解！おめでとう🎉', wrong: a => `残念！正解は「${a}」でした。`,
+            fallbackQuestion: '次の画像のうち、どれが該当しますか？',
+            offline: '正解の照合ができませんでした（サーバーに接続できません）。',
+            shareCorrect: 'たぬきクイズで正解しました！', shareTried: 'たぬきクイズに挑戦しました！',
+            dailyTitle: '今日のたぬき', dailyProgress: (i, n) => `${i} / ${n} 問目`, dailyNickname: 'ランキングに載せるニックネーム',
+            dailyScore: (s, n) => `${n} 問中 ${s} 問正解！`, dailyDone: '今日のチャレンジは挑戦済みです。また明日！',
+        },
+        en: {
+            title: 'Tanuki? Quiz', choice: 'choice', pick: 'This one!', yes: 'Tanuki!', no: 'Not a tanuki', next: 'Next question', more: 'Learn more',
+            correct: 'Correct! Well done 🎉', wrong: a => `Not quite! The answer was "${a}".`,
+            fallbackQuestion: 'Which of these images matches?',
+            offline: 'Could not check the answer (the server is unreachable).',
+            shareCorrect: 'I got the tanuki quiz right!', shareTried: 'I tried the tanuki quiz!',
+            dailyTitle: 'Daily tanuki', dailyProgress: (i, n) => `Question ${i} of ${n}`, dailyNickname: 'Nickname for the leaderboard',
+            dailyScore: (s, n) => `${s} of ${n} correct!`, dailyDone: "You've already played today's challenge. Come back tomorrow!",
+        },
+    };
+    const ui = UI[lang] || UI.ja;
+    const apiQuery = () => {
+        // player_id lets the server bring back the animals this player keeps mixing up
+        const q = new URLSearchParams({ lang, player_id: playerId() });
+        if (difficulty) q.set('difficulty', difficulty);
+        return '?' + q.toString();
+    };
 
     async function loadGeneratedQuiz() {
         try {
-            const res = await fetch('/api/generate_quiz');
+            const res = await fetch('/api/generate_quiz' + apiQuery());
             if (!res.ok) throw new Error('HTTP ' + res.status);
             const data = await res.json();
             currentQuiz = data;
 
             // display question
-            if (questionEl) questionEl.textContent = 'たぬき？クイズ';
+            if (questionEl) questionEl.textContent = ui.title;
             // hide the top quiz-image (we show choices instead)
             if (quizImage) { quizImage.style.display = 'none'; }
             questionText.textContent = data.question || '';
@@ -31,20 +66,13 @@ document.addEventListener('DOMContentLoaded', () => {
                 wrapper.className = 'choice-item';
                 const img = document.createElement('img');
                 img.src = choice.image_url;
-                img.alt = '選択肢';
+                img.alt = ui.choice;
                 img.className = 'choice-image';
-                // If external image fails (CDN/Heroku/Unsplash error), fall back to local server-generated image.
-                img.onerror = () => {
-                    try {
-                        img.src = `/images/${choice.category}.png`;
-                    } catch (e) {
-                        img.style.opacity = '0.4';
-                    }
-                };
+                img.onerror = () => img.style.opacity = '0.4';
                 wrapper.appendChild(img);
 
                 const btn = document.createElement('button');
-                btn.textContent = 'これだ！';
+                btn.textContent = ui.pick;
                 btn.className = 'option-button';
                 btn.onclick = () => checkAnswer(choice);
                 wrapper.appendChild(btn);
@@ -72,10 +100,10 @@ document.addEventListener('DOMContentLoaded', () => {
             }));
             // pick random answer
             const answer_category = choices[Math.floor(Math.random() * choices.length)].category;
-            currentQuiz = { question: '次の画像のうち、どれが該当しますか？', choices, answer_category };
+            currentQuiz = { question: ui.fallbackQuestion, choices, answer_category };
 
             // render fallback
-            if (questionEl) questionEl.textContent = 'たぬき？クイズ';
+            if (questionEl) questionEl.textContent = ui.title;
             questionText.textContent = currentQuiz.question || '';
             optionsContainer.innerHTML = '';
             choices.forEach(choice => {
@@ -83,13 +111,13 @@ document.addEventListener('DOMContentLoaded', () => {
                 wrapper.className = 'choice-item';
                 const img = document.createElement('img');
                 img.src = choice.image_url;
-                img.alt = '選択肢';
+                img.alt = ui.choice;
                 img.className = 'choice-image';
                 img.onerror = () => img.style.opacity = '0.4';
                 wrapper.appendChild(img);
 
                 const btn = document.createElement('button');
-                btn.textContent = 'これだ！';
+                btn.textContent = ui.pick;
                 btn.className = 'option-button';
                 btn.onclick = () => checkAnswer(choice);
                 wrapper.appendChild(btn);
@@ -108,20 +136,21 @@ document.addEventListener('DOMContentLoaded', () => {
 
         // try server-side authoritative validation first
         try {
-            const res = await fetch('/api/submit_generated', {
+            const res = await fetch('/api/submit_generated?lang=' + encodeURIComponent(lang), {
                 method: 'POST',
                 headers: { 'Content-Type': 'application/json' },
-                body: JSON.stringify({ quiz_id: currentQuiz.id, selected_category: choice.category })
+                body: JSON.stringify({ quiz_id: currentQuiz.id, choice: choice.token })
             });
 
             if (res.ok) {
                 const data = await res.json();
                 const correct = data.correct;
                 if (correct) {
-                    questionText.textContent = '正解！おめでとう🎉';
+                    questionText.textContent = ui.correct;
                 } else {
-                    questionText.textContent = `残念！正解は「${data.correct_answer}」でした。`;
+                    questionText.textContent = ui.wrong(data.correct_label || data.correct_answer);
                 }
+                showExplanation(data);
                 setupShareButton(correct);
                 shareContainer.style.display = 'block';
             } else {
@@ -137,7 +166,7 @@ document.addEventListener('DOMContentLoaded', () => {
 
         // add next question button
         const next = document.createElement('button');
-        next.textContent = '次の問題へ';
+        next.textContent = ui.next;
         next.className = 'option-button';
         next.style.gridColumn = '1 / -1';
         next.style.marginTop = '1rem';
@@ -145,29 +174,197 @@ document.addEventListener('DOMContentLoaded', () => {
         optionsContainer.appendChild(next);
     }
 
+    async function loadTanukiOrNot() {
+        try {
+            const res = await fetch('/api/tanuki_or_not' + apiQuery());
+            if (!res.ok) throw new Error('HTTP ' + res.status);
+            currentQuiz = await res.json();
+        } catch (err) {
+            console.warn('failed to load /api/tanuki_or_not', err);
+            questionText.textContent = ui.offline;
+            return;
+        }
+        if (questionEl) questionEl.textContent = ui.title;
+        quizImage.src = currentQuiz.image_url;
+        quizImage.alt = ui.choice;
+        quizImage.style.display = '';
+        questionText.textContent = currentQuiz.question;
+        optionsContainer.innerHTML = '';
+        shareContainer.style.display = 'none';
+        [[ui.yes, true], [ui.no, false]].forEach(([label, answer]) => {
+            const btn = document.createElement('button');
+            btn.textContent = label;
+            btn.className = 'option-button';
+            btn.onclick = () => checkTanukiOrNot(answer);
+            optionsContainer.appendChild(btn);
+        });
+    }
+
+    async function checkTanukiOrNot(answer) {
+        for (let b of optionsContainer.getElementsByTagName('button')) b.disabled = true;
+        try {
+            const res = await fetch('/api/tanuki_or_not/submit?lang=' + encodeURIComponent(lang), {
+                method: 'POST',
+                headers: { 'Content-Type': 'application/json' },
+                body: JSON.stringify({ quiz_id: currentQuiz.id, answer })
+            });
+            if (!res.ok) throw new Error('HTTP ' + res.status);
+            const data = await res.json();
+            questionText.textContent = data.correct ? ui.correct : ui.wrong(data.correct_label || data.correct_answer);
+            showExplanation(data);
+            setupShareButton(data.correct);
+        } catch (err) {
+            console.warn('failed to POST /api/tanuki_or_not/submit', err);
+            questionText.textContent = ui.offline;
+            setupShareButton(false);
+        }
+        shareContainer.style.display = 'block';
+        const next = document.createElement('button');
+        next.textContent = ui.next;
+        next.className = 'option-button';
+        next.style.gridColumn = '1 / -1';
+        next.style.marginTop = '1rem';
+        next.onclick = loadTanukiOrNot;
+        optionsContainer.appendChild(next);
+    }
+
     function fallbackLocalCheck(choice) {
             if (currentQuiz && currentQuiz.answer_category) {
                 const correct = choice.category === currentQuiz.answer_category;
                 if (correct) {
-                    questionText.textContent = '正解！おめでとう🎉';
+                    questionText.textContent = ui.correct;
                 } else {
-                    questionText.textContent = `残念！正解は「${currentQuiz.answer_category}」でした。`;
+                    questionText.textContent = ui.wrong(currentQuiz.answer_category);
                 }
                 setupShareButton(correct);
             } else {
                 // no authoritative answer available locally
-                questionText.textContent = '正解の照合ができませんでした（サーバーに接続できません）。';
+                questionText.textContent = ui.offline;
                 setupShareButton(false);
             }
         shareContainer.style.display = 'block';
     }
 
+    // identification tips for the correct species, if the server sent any
+    function showExplanation(data) {
+        if (!data.explanation) return;
+        const p = document.createElement('p');
+        p.className = 'explanation';
+        p.textContent = data.explanation + ' ';
+        if (data.info_url) {
+            const a = document.createElement('a');
+            a.href = data.info_url;
+            a.target = '_blank';
+            a.rel = 'noopener';
+            a.textContent = ui.more;
+            p.appendChild(a);
+        }
+        optionsContainer.appendChild(p);
+    }
+
     function setupShareButton(correct) {
-        const text = correct ? 'たぬきクイズで正解しました！' : 'たぬきクイズに挑戦しました！';
+        const text = correct ? ui.shareCorrect : ui.shareTried;
         const url = window.location.href;
         const shareUrl = `https://twitter.com/intent/tweet?text=${encodeURIComponent(text)}&url=${encodeURIComponent(url)}`;
         shareButton.href = shareUrl;
     }
 
-    loadGeneratedQuiz();
+    // random id kept in the browser: one daily submission per id, and the key for mistake history
+    function playerId() {
+        let id = localStorage.getItem('tanuki_player_id');
+        if (!id) {
+            id = (crypto.randomUUID ? crypto.randomUUID() : String(Date.now()) + Math.floor(Math.random() * 1e9));
+            localStorage.setItem('tanuki_player_id', id);
+        }
+        return id;
+    }
+
+    async function loadDaily() {
+        let daily;
+        try {
+            const res = await fetch(`/api/daily?lang=${encodeURIComponent(lang)}&player_id=${encodeURIComponent(playerId())}`);
+            if (!res.ok) throw new Error('HTTP ' + res.status);
+            daily = await res.json();
+        } catch (err) {
+            console.warn('failed to load /api/daily', err);
+            questionText.textContent = ui.offline;
+            return;
+        }
+        if (questionEl) questionEl.textContent = ui.dailyTitle;
+        if (quizImage) quizImage.style.display = 'none';
+        shareContainer.style.display = 'none';
+        if (daily.submitted) {
+            questionText.textContent = ui.dailyDone;
+            return showDailyLeaderboard(daily.date);
+        }
+        const answers = [];
+        const showQuestion = i => {
+            const q = daily.questions[i];
+            questionText.textContent = `${ui.dailyProgress(i + 1, daily.rounds)}: ${q.question}`;
+            optionsContainer.innerHTML = '';
+            q.choices.forEach(choice => {
+                const wrapper = document.createElement('div');
+                wrapper.className = 'choice-item';
+                const img = document.createElement('img');
+                img.src = choice.image_url;
+                img.alt = ui.choice;
+                img.className = 'choice-image';
+                wrapper.appendChild(img);
+                const btn = document.createElement('button');
+                btn.textContent = ui.pick;
+                btn.className = 'option-button';
+                btn.onclick = () => {
+                    answers.push(choice.token);
+                    if (answers.length < daily.rounds) showQuestion(i + 1); else submitDaily(daily, answers);
+                };
+                wrapper.appendChild(btn);
+                optionsContainer.appendChild(wrapper);
+            });
+        };
+        showQuestion(0);
+    }
+
+    async function submitDaily(daily, answers) {
+        optionsContainer.innerHTML = '';
+        const nickname = (window.prompt(ui.dailyNickname) || '').trim() || 'tanuki';
+        try {
+            const res = await fetch('/api/daily/submit?lang=' + encodeURIComponent(lang), {
+                method: 'POST',
+                headers: { 'Content-Type': 'application/json' },
+                body: JSON.stringify({ date: daily.date, player_id: playerId(), nickname, answers })
+            });
+            const data = await res.json();
+            if (!res.ok) {
+                questionText.textContent = (data.error && data.error.message) || ('HTTP ' + res.status);
+                return;
+            }
+            questionText.textContent = ui.dailyScore(data.score, data.rounds);
+            setupShareButton(data.score === data.rounds);
+            shareContainer.style.display = 'block';
+            showDailyLeaderboard(daily.date);
+        } catch (err) {
+            console.warn('failed to POST /api/daily/submit', err);
+            questionText.textContent = ui.offline;
+        }
+    }
+
+    async function showDailyLeaderboard(date) {
+        try {
+            const res = await fetch(`/api/daily/leaderboard?date=${encodeURIComponent(date)}`);
+            if (!res.ok) return;
+            const ol = document.createElement('ol');
+            for (const e of await res.json()) {
+                const li = document.createElement('li');
+                li.textContent = `${e.nickname} — ${e.score}/${e.rounds}`;
+                ol.appendChild(li);
+            }
+            optionsContainer.appendChild(ol);
+        } catch (err) {
+            console.warn('failed to load /api/daily/leaderboard', err);
+        }
+    }
+
+    if (mode === 'tanuki_or_not') loadTanukiOrNot();
+    else if (mode === 'daily') loadDaily();
+    else loadGeneratedQuiz();
 });
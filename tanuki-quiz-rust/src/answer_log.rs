@@ -0,0 +1,231 @@
+// Log of scored answers, for exporting results.
+//
+// Every answer the server scores is appended to data/answer_log.jsonl (override with
+// ANSWER_LOG_PATH), one JSON record per line: single quizzes and session rounds, yes/no,
+// hotspot and reveal quizzes, daily challenges, live rooms and classroom quiz sets. Admins
+// export it as CSV or JSON through GET /api/admin/export/answers, filtered by time, source,
+// session and player.
+// The file only grows; rotate or truncate it by hand when it gets too large.
+
+use chrono::{DateTime, Utc};
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+use crate::GeneratedChoice;
+
+/// Values of `AnswerRecord::source`.
+pub const SOURCES: &[&str] = &["quiz", "tanuki_or_not", "hotspot", "reveal", "daily", "room", "class"];
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct AnswerRecord {
+    pub answered_at: DateTime<Utc>,
+    // one of SOURCES
+    pub source: String,
+    // session id, daily date, room code or class code; None for a single quiz
+    #[serde(default)]
+    pub session: Option<String>,
+    #[serde(default)]
+    pub player_id: Option<String>,
+    #[serde(default)]
+    pub nickname: Option<String>,
+    // quiz id, or "<session>#<question number>" for fixed sets and rooms
+    pub question_id: String,
+    // species the player had to find, or the feature to click for hotspot
+    pub answer: String,
+    // photos shown, in order; generated images as "generated:<species>"
+    pub shown: Vec<String>,
+    // species picked, "yes"/"no" for tanuki_or_not, "x,y" of the click for hotspot;
+    // None when unanswered or an unknown choice
+    #[serde(default)]
+    pub picked: Option<String>,
+    #[serde(default)]
+    pub picked_image: Option<String>,
+    pub correct: bool,
+    #[serde(default)]
+    pub response_ms: Option<u64>,
+}
+
+impl AnswerRecord {
+    /// A pick-one answer; `picked` is None for an unknown or missing choice.
+    pub fn choice(source: &str, question_id: String, answer: &str, choices: &[GeneratedChoice], picked: Option<&GeneratedChoice>) -> AnswerRecord {
+        AnswerRecord {
+            answered_at: Utc::now(),
+            source: source.to_string(),
+            session: None,
+            player_id: None,
+            nickname: None,
+            question_id,
+            answer: answer.to_string(),
+            shown: choices.iter().map(shown_image).collect(),
+            picked: picked.map(|c| c.category.clone()),
+            picked_image: picked.map(shown_image),
+            correct: picked.is_some_and(|c| c.category == answer),
+            response_ms: None,
+        }
+    }
+}
+
+fn shown_image(choice: &GeneratedChoice) -> String {
+    choice.file.clone().unwrap_or_else(|| format!("generated:{}", choice.category))
+}
+
+/// Which records an export includes. Empty fields match everything.
+#[derive(Default, Clone)]
+pub struct Filter {
+    pub from: Option<DateTime<Utc>>,
+    // exclusive
+    pub to: Option<DateTime<Utc>>,
+    pub source: Option<String>,
+    pub session: Option<String>,
+    pub player_id: Option<String>,
+}
+
+impl Filter {
+    pub fn matches(&self, r: &AnswerRecord) -> bool {
+        self.from.is_none_or(|from| r.answered_at >= from)
+            && self.to.is_none_or(|to| r.answered_at < to)
+            && self.source.as_ref().is_none_or(|s| &r.source == s)
+            && self.session.as_ref().is_none_or(|s| r.session.as_ref() == Some(s))
+            && self.player_id.as_ref().is_none_or(|p| r.player_id.as_ref() == Some(p))
+    }
+}
+
+// serializes appends so records never interleave
+static LOG_LOCK: Lazy<Mutex<()>> = Lazy::new(|| Mutex::new(()));
+
+pub fn log_path() -> PathBuf {
+    env::var("ANSWER_LOG_PATH").map(PathBuf::from).unwrap_or_else(|_| PathBuf::from("data").join("answer_log.jsonl"))
+}
+
+fn append_to(p: &Path, records: &[AnswerRecord]) -> Result<(), String> {
+    if records.is_empty() { return Ok(()); }
+    let mut lines = String::new();
+    for r in records {
+        lines.push_str(&serde_json::to_string(r).map_err(|e| e.to_string())?);
+        lines.push('\n');
+    }
+    let _guard = LOG_LOCK.lock();
+    if let Some(dir) = p.parent() { std::fs::create_dir_all(dir).map_err(|e| format!("mkdir error: {}", e))?; }
+    let mut f = std::fs::OpenOptions::new().create(true).append(true).open(p).map_err(|e| format!("open error: {}", e))?;
+    f.write_all(lines.as_bytes()).map_err(|e| format!("write error: {}", e))
+}
+
+// a failed log write must not cost players their answers
+pub fn log(records: Vec<AnswerRecord>) {
+    log_to(&log_path(), records)
+}
+
+pub fn log_to(p: &Path, records: Vec<AnswerRecord>) {
+    if let Err(e) = append_to(p, &records) { eprintln!("answer log write failed: {}", e); }
+}
+
+/// Calls `f` with every matching record, oldest first, until it returns false.
+/// Reads without the append lock, so a long export never holds up answers; a line still being
+/// written is skipped like any other unreadable line. A failed read ends the walk with an error.
+pub fn for_each(filter: &Filter, f: impl FnMut(&AnswerRecord) -> bool) -> Result<(), String> {
+    let file = match std::fs::File::open(log_path()) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(format!("open error: {}", e)),
+    };
+    for_each_in(BufReader::new(file), filter, f)
+}
+
+fn for_each_in(reader: impl BufRead, filter: &Filter, mut f: impl FnMut(&AnswerRecord) -> bool) -> Result<(), String> {
+    for line in reader.lines() {
+        let line = match line {
+            Ok(line) => line,
+            // not UTF-8: an unreadable line like any other
+            Err(e) if e.kind() == std::io::ErrorKind::InvalidData => continue,
+            Err(e) => return Err(format!("read error: {}", e)),
+        };
+        let Ok(r) = serde_json::from_str::<AnswerRecord>(&line) else { continue };
+        if filter.matches(&r) && !f(&r) { break; }
+    }
+    Ok(())
+}
+
+pub const CSV_HEADER: &str = "answered_at,source,session,player_id,nickname,question_id,answer,shown,picked,picked_image,correct,response_ms\r\n";
+
+/// One CSV line (RFC 4180), ending in CRLF. Photos shown are joined with ';'.
+pub fn csv_row(r: &AnswerRecord) -> String {
+    let fields = [
+        r.answered_at.to_rfc3339(),
+        r.source.clone(),
+        r.session.clone().unwrap_or_default(),
+        r.player_id.clone().unwrap_or_default(),
+        r.nickname.clone().unwrap_or_default(),
+        r.question_id.clone(),
+        r.answer.clone(),
+        r.shown.join(";"),
+        r.picked.clone().unwrap_or_default(),
+        r.picked_image.clone().unwrap_or_default(),
+        r.correct.to_string(),
+        r.response_ms.map(|ms| ms.to_string()).unwrap_or_default(),
+    ];
+    let mut line = fields.iter().map(|f| csv_field(f)).collect::<Vec<_>>().join(",");
+    line.push_str("\r\n");
+    line
+}
+
+fn csv_field(value: &str) -> String {
+    // nicknames are player input: keep spreadsheets from running them as formulas
+    let value = if value.starts_with(['=', '+', '-', '@', '\t', '\r']) { format!("'{}", value) } else { value.to_string() };
+    if value.contains([',', '"', '\n', '\r']) { format!("\"{}\"", value.replace('"', "\"\"")) } else { value }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(source: &str, session: Option<&str>, minute: u32) -> AnswerRecord {
+        AnswerRecord {
+            answered_at: format!("2026-05-01T10:{:02}:00Z", minute).parse().unwrap(),
+            source: source.to_string(),
+            session: session.map(|s| s.to_string()),
+            player_id: Some("p1".to_string()),
+            nickname: Some("=cmd, \"x\"".to_string()),
+            question_id: "q".to_string(),
+            answer: "tanuki".to_string(),
+            shown: vec!["tanuki1.jpg".to_string(), "anaguma1.jpg".to_string()],
+            picked: Some("anaguma".to_string()),
+            picked_image: Some("anaguma1.jpg".to_string()),
+            correct: false,
+            response_ms: Some(1200),
+        }
+    }
+
+    #[test]
+    fn test_filter_and_skip_bad_lines() {
+        let lines = [
+            serde_json::to_string(&record("room", Some("ABCDEF"), 1)).unwrap(),
+            "not json".to_string(),
+            serde_json::to_string(&record("class", Some("ABCDEF"), 2)).unwrap(),
+            serde_json::to_string(&record("room", Some("GHJKLM"), 3)).unwrap(),
+            serde_json::to_string(&record("room", Some("ABCDEF"), 4)).unwrap(),
+        ].join("\n");
+        let filter = Filter {
+            from: Some("2026-05-01T10:01:00Z".parse().unwrap()),
+            to: Some("2026-05-01T10:04:00Z".parse().unwrap()),
+            source: Some("room".to_string()),
+            session: Some("ABCDEF".to_string()),
+            player_id: None,
+        };
+        let mut seen = Vec::new();
+        for_each_in(lines.as_bytes(), &filter, |r| { seen.push(r.answered_at.format("%M").to_string()); true }).unwrap();
+        assert_eq!(seen, vec!["01"]);
+        let mut count = 0;
+        for_each_in(lines.as_bytes(), &Filter::default(), |_| { count += 1; count < 2 }).unwrap();
+        assert_eq!(count, 2);
+    }
+
+    #[test]
+    fn test_csv_row_quotes_and_defuses_formulas() {
+        let row = csv_row(&record("quiz", None, 5));
+        assert_eq!(row, "2026-05-01T10:05:00+00:00,quiz,,p1,\"'=cmd, \"\"x\"\"\",q,tanuki,tanuki1.jpg;anaguma1.jpg,anaguma,anaguma1.jpg,false,1200\r\n");
+    }
+}
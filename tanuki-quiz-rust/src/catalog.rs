@@ -0,0 +1,136 @@
+// Static quiz catalog used by /api/quiz and /api/submit.
+//
+// The catalog lives in a JSON file (data/questions.json by default, override with
+// QUIZ_CATALOG_PATH) so content editors can add questions without recompiling.
+// It is loaded once at startup and can be reloaded via POST /api/admin/catalog/reload.
+
+use once_cell::sync::Lazy;
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::env;
+use std::path::{Path, PathBuf};
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct QuizQuestion {
+    pub id: usize,
+    pub image_url: String,
+    pub answer: String,
+}
+
+// currently active catalog; an invalid file on reload leaves this untouched
+static CATALOG: Lazy<RwLock<Vec<QuizQuestion>>> = Lazy::new(|| RwLock::new(Vec::new()));
+
+pub fn catalog_path() -> PathBuf {
+    env::var("QUIZ_CATALOG_PATH").map(PathBuf::from).unwrap_or_else(|_| PathBuf::from("data").join("questions.json"))
+}
+
+fn assets_dir() -> PathBuf { PathBuf::from("public").join("assets") }
+
+pub fn all_questions() -> Vec<QuizQuestion> {
+    CATALOG.read().clone()
+}
+
+pub fn find_question(id: usize) -> Option<QuizQuestion> {
+    CATALOG.read().iter().find(|q| q.id == id).cloned()
+}
+
+/// Parse and validate a catalog file. All problems are collected so editors can fix them in one pass.
+pub fn load_catalog(path: &Path, assets_dir: &Path) -> Result<Vec<QuizQuestion>, Vec<String>> {
+    let s = std::fs::read_to_string(path).map_err(|e| vec![format!("cannot read {}: {}", path.display(), e)])?;
+    let questions: Vec<QuizQuestion> = serde_json::from_str(&s).map_err(|e| vec![format!("invalid catalog json in {}: {}", path.display(), e)])?;
+    validate_catalog(&questions, assets_dir)?;
+    Ok(questions)
+}
+
+pub fn validate_catalog(questions: &[QuizQuestion], assets_dir: &Path) -> Result<(), Vec<String>> {
+    let mut errors = Vec::new();
+    if questions.is_empty() { errors.push("catalog has no questions".to_string()); }
+    let mut seen = HashSet::new();
+    for q in questions {
+        if q.id == 0 { errors.push("question id must be greater than 0".to_string()); }
+        if !seen.insert(q.id) { errors.push(format!("question {}: duplicate id", q.id)); }
+        if q.answer.trim().is_empty() { errors.push(format!("question {}: answer is empty", q.id)); }
+        if let Err(e) = validate_image_url(&q.image_url, assets_dir) { errors.push(format!("question {}: {}", q.id, e)); }
+    }
+    if errors.is_empty() { Ok(()) } else { Err(errors) }
+}
+
+fn validate_image_url(url: &str, assets_dir: &Path) -> Result<(), String> {
+    if let Some(name) = url.strip_prefix("/assets/") {
+        // only plain relative paths inside public/assets are allowed
+        if name.is_empty() || name.contains("..") || name.contains('\\') {
+            return Err(format!("invalid asset path '{}'", url));
+        }
+        if !assets_dir.join(name).is_file() {
+            return Err(format!("asset '{}' not found in {}", name, assets_dir.display()));
+        }
+        Ok(())
+    } else if let Some(name) = url.strip_prefix("/images/") {
+        // procedurally generated by serve_image, always available
+        if name.is_empty() { Err("empty /images/ key".to_string()) } else { Ok(()) }
+    } else if url.starts_with("https://") || url.starts_with("http://") {
+        Ok(())
+    } else {
+        Err(format!("image_url '{}' must start with /assets/, /images/ or http(s)://", url))
+    }
+}
+
+/// Whether a catalog question shows public/assets/<name>; only those photos are served under /assets.
+pub fn references_asset(name: &str) -> bool {
+    let url = format!("/assets/{}", name);
+    CATALOG.read().iter().any(|q| q.image_url == url)
+}
+
+/// Load the configured catalog and swap it in. Returns the number of questions loaded.
+pub fn reload() -> Result<usize, Vec<String>> {
+    let questions = load_catalog(&catalog_path(), &assets_dir())?;
+    let n = questions.len();
+    *CATALOG.write() = questions;
+    Ok(n)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn q(id: usize, image_url: &str, answer: &str) -> QuizQuestion {
+        QuizQuestion { id, image_url: image_url.to_string(), answer: answer.to_string() }
+    }
+
+    fn temp_assets() -> PathBuf {
+        let dir = env::temp_dir().join(format!("tanuki-catalog-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("tanuki1.jpg"), b"x").unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_validate_accepts_existing_assets_and_procedural_images() {
+        let dir = temp_assets();
+        let qs = vec![q(1, "/assets/tanuki1.jpg", "たぬき"), q(2, "/images/anaguma1.png", "アナグマ")];
+        assert!(validate_catalog(&qs, &dir).is_ok());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_validate_collects_all_errors() {
+        let dir = temp_assets();
+        let qs = vec![
+            q(1, "/assets/missing.jpg", "たぬき"),
+            q(1, "/images/tanuki1.png", ""),
+            q(3, "/assets/../secret.txt", "たぬき"),
+            q(4, "tanuki.png", "たぬき"),
+        ];
+        let errs = validate_catalog(&qs, &dir).unwrap_err();
+        assert_eq!(errs.len(), 5, "unexpected errors: {:?}", errs);
+        assert!(validate_catalog(&[], &dir).is_err());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_bundled_catalog_is_valid() {
+        let qs = load_catalog(&PathBuf::from("data").join("questions.json"), &assets_dir());
+        assert!(qs.is_ok(), "bundled catalog invalid: {:?}", qs.err());
+    }
+}
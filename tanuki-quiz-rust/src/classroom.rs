@@ -0,0 +1,395 @@
+// Classroom quiz sets.
+//
+// A teacher (admin) builds a fixed set of questions from photos in public/assets and shares its join
+// code. Every student who enters the code gets exactly the same questions, with the choices in the
+// same order, so results can be compared across the class. Each student (a client-generated
+// player_id) submits once per set; the teacher sees per-student and per-question results.
+// Sets and their submissions are kept in data/quiz_sets.json (override with QUIZ_SETS_PATH). The file is
+// read once and then served from memory, so edit it by hand only while the server is stopped.
+
+use chrono::{DateTime, Utc};
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use rand::seq::SliceRandom;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::env;
+use std::path::PathBuf;
+use uuid::Uuid;
+
+use crate::room;
+
+pub const MAX_QUESTIONS: usize = 50;
+pub const MAX_CHOICES: usize = 6;
+pub const MAX_TITLE_CHARS: usize = 100;
+
+/// One question as the teacher writes it: the species to find and the photos to choose from.
+#[derive(Deserialize, Clone)]
+pub struct QuestionSpec {
+    pub answer: String,
+    pub images: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SetChoice {
+    // random per-set token; students only ever see this
+    pub token: String,
+    pub file: String,
+    pub category: String,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SetQuestion {
+    pub answer: String,
+    pub choices: Vec<SetChoice>,
+}
+
+impl SetQuestion {
+    pub fn is_correct(&self, token: &str) -> bool {
+        self.choices.iter().any(|c| c.token == token && c.category == self.answer)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Submission {
+    pub player_id: String,
+    pub nickname: String,
+    // one choice token per question, in question order
+    pub answers: Vec<String>,
+    pub score: usize,
+    pub submitted_at: DateTime<Utc>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct QuizSet {
+    pub id: String,
+    pub code: String,
+    pub title: String,
+    // closed sets keep their results but take no more submissions
+    pub open: bool,
+    pub questions: Vec<SetQuestion>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    #[serde(default)]
+    pub submissions: Vec<Submission>,
+}
+
+// what the admin list shows of a set
+#[derive(Serialize)]
+pub struct QuizSetSummary {
+    pub id: String,
+    pub code: String,
+    pub title: String,
+    pub open: bool,
+    pub questions: usize,
+    pub submissions: usize,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl QuizSet {
+    pub fn summary(&self) -> QuizSetSummary {
+        QuizSetSummary {
+            id: self.id.clone(),
+            code: self.code.clone(),
+            title: self.title.clone(),
+            open: self.open,
+            questions: self.questions.len(),
+            submissions: self.submissions.len(),
+            created_at: self.created_at,
+            updated_at: self.updated_at,
+        }
+    }
+
+    pub fn has_submitted(&self, player_id: &str) -> bool {
+        self.submission(player_id).is_some()
+    }
+
+    pub fn submission(&self, player_id: &str) -> Option<&Submission> {
+        self.submissions.iter().find(|s| s.player_id == player_id)
+    }
+}
+
+#[derive(Serialize)]
+pub struct StudentResult {
+    pub nickname: String,
+    pub score: usize,
+    pub questions: usize,
+    // per question, in question order
+    pub correct: Vec<bool>,
+    pub submitted_at: DateTime<Utc>,
+}
+
+#[derive(Serialize)]
+pub struct QuestionResult {
+    pub index: usize,
+    pub answer: String,
+    pub answered: usize,
+    pub correct: usize,
+    pub accuracy: Option<f64>,
+    // photo -> how many students picked it
+    pub picks: BTreeMap<String, usize>,
+}
+
+#[derive(Serialize)]
+pub struct SetResults {
+    pub id: String,
+    pub code: String,
+    pub title: String,
+    // best score first, then the earlier submission
+    pub students: Vec<StudentResult>,
+    pub questions: Vec<QuestionResult>,
+}
+
+pub enum UpdateError {
+    NotFound,
+    // the questions of a set cannot change once students have answered it
+    HasSubmissions,
+    Io(String),
+}
+
+pub enum SubmitError {
+    NotFound,
+    Closed,
+    AnswerCount(usize),
+    AlreadySubmitted,
+    NicknameTaken,
+    Io(String),
+}
+
+// the sets as last read from or written to the file; None until first used, or after a failed write
+static SETS: Lazy<Mutex<Option<Vec<QuizSet>>>> = Lazy::new(|| Mutex::new(None));
+
+fn sets_path() -> PathBuf {
+    env::var("QUIZ_SETS_PATH").map(PathBuf::from).unwrap_or_else(|_| PathBuf::from("data").join("quiz_sets.json"))
+}
+
+fn load_sets() -> Vec<QuizSet> {
+    std::fs::read_to_string(sets_path()).ok()
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default()
+}
+
+// Write the cached sets out. If that fails the cache is dropped, so the next call reads the file
+// again rather than serving a change that was never saved.
+fn save_sets(cache: &mut Option<Vec<QuizSet>>) -> Result<(), String> {
+    let sets = cache.as_deref().unwrap_or_default();
+    write_sets(sets).inspect_err(|_| *cache = None)
+}
+
+fn write_sets(sets: &[QuizSet]) -> Result<(), String> {
+    let p = sets_path();
+    if let Some(dir) = p.parent() { std::fs::create_dir_all(dir).map_err(|e| format!("mkdir error: {}", e))?; }
+    let s = serde_json::to_string_pretty(sets).map_err(|e| e.to_string())?;
+    let tmp = p.with_extension("json.tmp");
+    std::fs::write(&tmp, s).map_err(|e| format!("write error: {}", e))?;
+    std::fs::rename(&tmp, &p).map_err(|e| format!("rename error: {}", e))
+}
+
+/// Check a title, returning it trimmed.
+pub fn clean_title(raw: &str) -> Result<String, String> {
+    let t = raw.trim();
+    if t.is_empty() || t.chars().count() > MAX_TITLE_CHARS || t.chars().any(|c| c.is_control()) {
+        return Err(format!("title must be 1-{} characters without control characters", MAX_TITLE_CHARS));
+    }
+    Ok(t.to_string())
+}
+
+/// Turn the teacher's questions into a set's questions. `category_of` gives the species of a photo in
+/// public/assets, or None if there is no such photo. Choices are shuffled once here, so every student
+/// sees the same order. All problems are reported, one per line.
+pub fn build_questions<R: Rng + ?Sized>(specs: &[QuestionSpec], category_of: impl Fn(&str) -> Option<String>, rng: &mut R) -> Result<Vec<SetQuestion>, Vec<String>> {
+    let mut errors = Vec::new();
+    if specs.is_empty() || specs.len() > MAX_QUESTIONS { errors.push(format!("a set needs 1-{} questions", MAX_QUESTIONS)); }
+    let mut questions = Vec::new();
+    for (i, spec) in specs.iter().enumerate() {
+        let n = i + 1;
+        if spec.images.len() < 2 || spec.images.len() > MAX_CHOICES {
+            errors.push(format!("question {}: needs 2-{} images", n, MAX_CHOICES));
+            continue;
+        }
+        let mut choices: Vec<SetChoice> = Vec::new();
+        for file in &spec.images {
+            if choices.iter().any(|c| &c.file == file) {
+                errors.push(format!("question {}: {} is listed twice", n, file));
+                continue;
+            }
+            match category_of(file) {
+                Some(category) => choices.push(SetChoice { token: format!("{:016x}", rng.gen::<u64>()), file: file.clone(), category }),
+                None => errors.push(format!("question {}: {} is not a photo of a known species in public/assets", n, file)),
+            }
+        }
+        if !choices.iter().any(|c| c.category == spec.answer) { errors.push(format!("question {}: no image shows {}", n, spec.answer)); }
+        if !choices.is_empty() && choices.iter().all(|c| c.category == spec.answer) { errors.push(format!("question {}: every image shows {}", n, spec.answer)); }
+        choices.shuffle(rng);
+        questions.push(SetQuestion { answer: spec.answer.clone(), choices });
+    }
+    if errors.is_empty() { Ok(questions) } else { Err(errors) }
+}
+
+pub fn create(title: String, questions: Vec<SetQuestion>) -> Result<QuizSet, String> {
+    let mut cache = SETS.lock();
+    let sets = cache.get_or_insert_with(load_sets);
+    let mut rng = rand::thread_rng();
+    let code = loop {
+        let code = room::new_code(&mut rng);
+        if !sets.iter().any(|s| s.code == code) { break code; }
+    };
+    let now = Utc::now();
+    let set = QuizSet { id: Uuid::new_v4().to_string(), code, title, open: true, questions, created_at: now, updated_at: now, submissions: Vec::new() };
+    sets.push(set.clone());
+    save_sets(&mut cache)?;
+    Ok(set)
+}
+
+// look at the sets without copying all of them
+fn with_sets<T>(f: impl FnOnce(&[QuizSet]) -> T) -> T {
+    let mut cache = SETS.lock();
+    f(cache.get_or_insert_with(load_sets))
+}
+
+pub fn list() -> Vec<QuizSet> {
+    with_sets(|sets| sets.to_vec())
+}
+
+pub fn get(id: &str) -> Option<QuizSet> {
+    with_sets(|sets| sets.iter().find(|s| s.id == id).cloned())
+}
+
+/// An open or closed set by its join code (case-insensitive).
+pub fn by_code(code: &str) -> Option<QuizSet> {
+    let code = room::normalize_code(code);
+    with_sets(|sets| sets.iter().find(|s| s.code == code).cloned())
+}
+
+/// Change a set; None leaves that part as it is.
+pub fn update(id: &str, title: Option<String>, questions: Option<Vec<SetQuestion>>, open: Option<bool>) -> Result<QuizSet, UpdateError> {
+    let mut cache = SETS.lock();
+    let set = cache.get_or_insert_with(load_sets).iter_mut().find(|s| s.id == id).ok_or(UpdateError::NotFound)?;
+    if questions.is_some() && !set.submissions.is_empty() { return Err(UpdateError::HasSubmissions); }
+    if let Some(t) = title { set.title = t; }
+    if let Some(q) = questions { set.questions = q; }
+    if let Some(o) = open { set.open = o; }
+    set.updated_at = Utc::now();
+    let updated = set.clone();
+    save_sets(&mut cache).map_err(UpdateError::Io)?;
+    Ok(updated)
+}
+
+/// Delete a set and its results. Returns false if there was no such set.
+pub fn delete(id: &str) -> Result<bool, String> {
+    let mut cache = SETS.lock();
+    let sets = cache.get_or_insert_with(load_sets);
+    let before = sets.len();
+    sets.retain(|s| s.id != id);
+    if sets.len() == before { return Ok(false); }
+    save_sets(&mut cache)?;
+    Ok(true)
+}
+
+/// Score and record a student's answers. An unknown token counts as a wrong answer.
+/// Returns the set as answered along with the submission.
+pub fn submit(code: &str, player_id: &str, nickname: String, answers: Vec<String>) -> Result<(QuizSet, Submission), SubmitError> {
+    let code = room::normalize_code(code);
+    let mut cache = SETS.lock();
+    let set = cache.get_or_insert_with(load_sets).iter_mut().find(|s| s.code == code).ok_or(SubmitError::NotFound)?;
+    if !set.open { return Err(SubmitError::Closed); }
+    if answers.len() != set.questions.len() { return Err(SubmitError::AnswerCount(set.questions.len())); }
+    if set.has_submitted(player_id) { return Err(SubmitError::AlreadySubmitted); }
+    if set.submissions.iter().any(|s| s.nickname == nickname) { return Err(SubmitError::NicknameTaken); }
+    let score = set.questions.iter().zip(&answers).filter(|(q, a)| q.is_correct(a)).count();
+    let submission = Submission { player_id: player_id.to_string(), nickname, answers, score, submitted_at: Utc::now() };
+    set.submissions.push(submission.clone());
+    let answered = set.clone();
+    save_sets(&mut cache).map_err(SubmitError::Io)?;
+    Ok((answered, submission))
+}
+
+pub fn results(set: &QuizSet) -> SetResults {
+    let mut students: Vec<StudentResult> = set.submissions.iter().map(|s| StudentResult {
+        nickname: s.nickname.clone(),
+        score: s.score,
+        questions: set.questions.len(),
+        correct: set.questions.iter().zip(&s.answers).map(|(q, a)| q.is_correct(a)).collect(),
+        submitted_at: s.submitted_at,
+    }).collect();
+    students.sort_by(|a, b| b.score.cmp(&a.score).then(a.submitted_at.cmp(&b.submitted_at)));
+    let questions = set.questions.iter().enumerate().map(|(index, q)| {
+        let mut picks = BTreeMap::new();
+        let mut correct = 0;
+        for s in &set.submissions {
+            let Some(token) = s.answers.get(index) else { continue };
+            let Some(choice) = q.choices.iter().find(|c| &c.token == token) else { continue };
+            *picks.entry(choice.file.clone()).or_insert(0) += 1;
+            if choice.category == q.answer { correct += 1; }
+        }
+        let answered: usize = picks.values().sum();
+        QuestionResult {
+            index,
+            answer: q.answer.clone(),
+            answered,
+            correct,
+            accuracy: if answered > 0 { Some(correct as f64 / answered as f64) } else { None },
+            picks,
+        }
+    }).collect();
+    SetResults { id: set.id.clone(), code: set.code.clone(), title: set.title.clone(), students, questions }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn spec(answer: &str, images: &[&str]) -> QuestionSpec {
+        QuestionSpec { answer: answer.to_string(), images: images.iter().map(|s| s.to_string()).collect() }
+    }
+
+    // photos are named after their species in these tests
+    fn category_of(file: &str) -> Option<String> {
+        file.split('_').next().filter(|s| ["tanuki", "anaguma", "hakubishin"].contains(s)).map(|s| s.to_string())
+    }
+
+    #[test]
+    fn test_build_questions_reports_every_problem() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let ok = build_questions(&[spec("tanuki", &["tanuki_1.jpg", "anaguma_1.jpg", "hakubishin_1.jpg"])], category_of, &mut rng).unwrap();
+        assert_eq!(ok[0].choices.len(), 3);
+        assert_eq!(ok[0].choices.iter().filter(|c| c.category == "tanuki").count(), 1);
+        let Err(errors) = build_questions(&[
+            spec("tanuki", &["anaguma_1.jpg", "hakubishin_1.jpg"]),
+            spec("tanuki", &["tanuki_1.jpg", "tanuki_1.jpg", "cat_1.jpg"]),
+            spec("tanuki", &["tanuki_1.jpg"]),
+        ], category_of, &mut rng) else { panic!("invalid questions were accepted") };
+        assert_eq!(errors.len(), 5, "{:?}", errors);
+        assert!(build_questions(&[], category_of, &mut rng).is_err());
+    }
+
+    #[test]
+    fn test_results_per_student_and_question() {
+        let mut rng = StdRng::seed_from_u64(2);
+        let questions = build_questions(&[
+            spec("tanuki", &["tanuki_1.jpg", "anaguma_1.jpg"]),
+            spec("anaguma", &["tanuki_2.jpg", "anaguma_2.jpg"]),
+        ], category_of, &mut rng).unwrap();
+        let token = |q: usize, file: &str| questions[q].choices.iter().find(|c| c.file == file).unwrap().token.clone();
+        let submission = |nickname: &str, answers: Vec<String>, score: usize| Submission { player_id: nickname.to_string(), nickname: nickname.to_string(), answers, score, submitted_at: Utc::now() };
+        let set = QuizSet {
+            id: "id".to_string(), code: "ABCDEF".to_string(), title: "t".to_string(), open: true, created_at: Utc::now(), updated_at: Utc::now(),
+            submissions: vec![
+                submission("one", vec![token(0, "anaguma_1.jpg"), token(1, "anaguma_2.jpg")], 1),
+                submission("two", vec![token(0, "tanuki_1.jpg"), token(1, "anaguma_2.jpg")], 2),
+                submission("odd", vec!["bogus".to_string(), token(1, "tanuki_2.jpg")], 0),
+            ],
+            questions,
+        };
+        let r = results(&set);
+        assert_eq!(r.students.iter().map(|s| s.nickname.as_str()).collect::<Vec<_>>(), vec!["two", "one", "odd"]);
+        assert_eq!(r.students[1].correct, vec![false, true]);
+        assert_eq!((r.questions[0].answered, r.questions[0].correct), (2, 1));
+        assert_eq!(r.questions[1].picks.get("anaguma_2.jpg"), Some(&2));
+        assert_eq!(r.questions[1].accuracy, Some(2.0 / 3.0));
+    }
+}
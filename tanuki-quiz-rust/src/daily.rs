@@ -0,0 +1,331 @@
+// Daily challenge.
+//
+// Every player gets the same questions on a calendar day (server local time): the quiz
+// generator is fed an RNG seeded from the date and a secret, so nobody can work out future
+// days' answers from the source. The secret is DAILY_SEED_SECRET, or a random one generated
+// on first start and kept in data/daily_seed_secret (override with DAILY_SECRET_PATH).
+// Each player (a client-generated player_id) submits once per day, timed by the server from
+// their first fetch of the challenge. Only the score is returned until the day is over, so a
+// throwaway player_id cannot collect the answers. Results go to a separate daily leaderboard
+// in data/daily_leaderboard.json (override with DAILY_LEADERBOARD_PATH).
+// The answer key of a day is saved the first time its questions are generated, in
+// data/daily_answers.json (override with DAILY_ANSWERS_PATH), and the published answers come
+// from it: regenerating a past day from its seed would follow later photo or round changes.
+
+use chrono::{DateTime, Local, NaiveDate, Utc};
+use once_cell::sync::{Lazy, OnceCell};
+use parking_lot::Mutex;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::env;
+use std::path::{Path, PathBuf};
+
+pub const DEFAULT_ROUNDS: usize = 5;
+pub const MAX_ROUNDS: usize = 20;
+pub const MAX_PLAYER_ID_CHARS: usize = 64;
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct DailyEntry {
+    pub date: NaiveDate,
+    // kept private to the server: it is what stops a second submission
+    pub player_id: String,
+    pub nickname: String,
+    pub score: usize,
+    pub rounds: usize,
+    // from the player's first GET /api/daily of the day; None only in entries from older versions
+    pub time_taken_ms: Option<u64>,
+    pub submitted_at: DateTime<Utc>,
+}
+
+// what one question of a day was looking for
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct KeyQuestion {
+    // `<date>#<n>`, numbered from 1
+    pub quiz_id: String,
+    pub answer: String,
+    // the correct choices' photos: files in public/assets or procedural /images keys
+    pub photos: Vec<String>,
+}
+
+// what the public daily leaderboard shows of an entry
+#[derive(Serialize)]
+pub struct DailyRank {
+    pub rank: usize,
+    pub nickname: String,
+    pub score: usize,
+    pub rounds: usize,
+    pub time_taken_ms: Option<u64>,
+    pub submitted_at: DateTime<Utc>,
+}
+
+pub enum SubmitError {
+    // the player never fetched the challenge, so there is no server-side start time
+    NotStarted,
+    AlreadySubmitted,
+    NicknameTaken,
+    Io(String),
+}
+
+// serializes read-modify-write cycles on the daily leaderboard file
+static DAILY_LOCK: Lazy<Mutex<()>> = Lazy::new(|| Mutex::new(()));
+
+// first time each player fetched the challenge, for the current day only
+type StartTimes = (Option<NaiveDate>, HashMap<String, DateTime<Utc>>);
+static STARTED: Lazy<Mutex<StartTimes>> = Lazy::new(|| Mutex::new((None, HashMap::new())));
+
+pub fn today() -> NaiveDate {
+    Local::now().date_naive()
+}
+
+/// Number of questions in a daily challenge, DAILY_ROUNDS (default 5, at most MAX_ROUNDS).
+pub fn rounds() -> usize {
+    env::var("DAILY_ROUNDS").ok().and_then(|s| s.parse().ok()).unwrap_or(DEFAULT_ROUNDS).clamp(1, MAX_ROUNDS)
+}
+
+/// Whether a day's challenge has ended, so its answers may be shown.
+pub fn is_over(date: NaiveDate) -> bool {
+    date < today()
+}
+
+static SECRET: OnceCell<String> = OnceCell::new();
+
+fn secret_path() -> PathBuf {
+    env::var("DAILY_SECRET_PATH").map(PathBuf::from).unwrap_or_else(|_| PathBuf::from("data").join("daily_seed_secret"))
+}
+
+/// The seed secret: DAILY_SEED_SECRET, or the one kept in the secret file, created on first use.
+/// Called at startup, so a secret that cannot be kept stops the server instead of falling back to none.
+pub fn secret() -> Result<&'static str, String> {
+    SECRET.get_or_try_init(|| match env::var("DAILY_SEED_SECRET") {
+        Ok(s) if !s.is_empty() => Ok(s),
+        _ => load_or_create_secret(&secret_path()),
+    }).map(|s| s.as_str())
+}
+
+fn load_or_create_secret(p: &Path) -> Result<String, String> {
+    if let Ok(s) = std::fs::read_to_string(p) {
+        if !s.trim().is_empty() { return Ok(s.trim().to_string()); }
+    }
+    let secret = format!("{:032x}", rand::thread_rng().gen::<u128>());
+    if let Some(dir) = p.parent() { std::fs::create_dir_all(dir).map_err(|e| format!("mkdir error: {}", e))?; }
+    std::fs::write(p, &secret).map_err(|e| format!("cannot write {}: {}", p.display(), e))?;
+    Ok(secret)
+}
+
+/// Seed for a day's challenge.
+pub fn seed_for(date: NaiveDate) -> u64 {
+    seed_with(date, secret().expect("the daily seed secret is loaded at startup"))
+}
+
+// FNV-1a over the date and the secret, so it is stable across builds
+fn seed_with(date: NaiveDate, secret: &str) -> u64 {
+    fnv1a(format!("{}|{}", date.format("%Y-%m-%d"), secret).as_bytes())
+}
+
+fn fnv1a(bytes: &[u8]) -> u64 {
+    let mut h: u64 = 0xcbf29ce484222325;
+    for b in bytes {
+        h ^= *b as u64;
+        h = h.wrapping_mul(0x100000001b3);
+    }
+    h
+}
+
+pub fn valid_player_id(id: &str) -> bool {
+    !id.is_empty() && id.chars().count() <= MAX_PLAYER_ID_CHARS && id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// Remember when a player first opened today's challenge.
+pub fn mark_started(date: NaiveDate, player_id: &str) {
+    let mut started = STARTED.lock();
+    if started.0 != Some(date) { *started = (Some(date), HashMap::new()); }
+    started.1.entry(player_id.to_string()).or_insert_with(Utc::now);
+}
+
+fn started_at(date: NaiveDate, player_id: &str) -> Option<DateTime<Utc>> {
+    let started = STARTED.lock();
+    if started.0 != Some(date) { return None; }
+    started.1.get(player_id).copied()
+}
+
+fn leaderboard_path() -> PathBuf {
+    env::var("DAILY_LEADERBOARD_PATH").map(PathBuf::from).unwrap_or_else(|_| PathBuf::from("data").join("daily_leaderboard.json"))
+}
+
+fn answers_path() -> PathBuf {
+    env::var("DAILY_ANSWERS_PATH").map(PathBuf::from).unwrap_or_else(|_| PathBuf::from("data").join("daily_answers.json"))
+}
+
+// a missing file is empty; an unreadable one is an error, never silently reset
+fn read_json<T: serde::de::DeserializeOwned + Default>(p: &Path) -> Result<T, String> {
+    let s = match std::fs::read_to_string(p) {
+        Ok(s) => s,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(T::default()),
+        Err(e) => return Err(format!("read error: {}", e)),
+    };
+    serde_json::from_str(&s).map_err(|e| format!("parse error: {}", e))
+}
+
+// write then rename, so a crash never leaves half a file behind
+fn write_json<T: Serialize + ?Sized>(p: &Path, value: &T) -> Result<(), String> {
+    if let Some(dir) = p.parent() { std::fs::create_dir_all(dir).map_err(|e| format!("mkdir error: {}", e))?; }
+    let s = serde_json::to_string_pretty(value).map_err(|e| e.to_string())?;
+    let tmp = p.with_extension("json.tmp");
+    std::fs::write(&tmp, s).map_err(|e| format!("write error: {}", e))?;
+    std::fs::rename(&tmp, p).map_err(|e| format!("rename error: {}", e))
+}
+
+fn load_entries() -> Result<Vec<DailyEntry>, String> {
+    read_json(&leaderboard_path())
+}
+
+fn save_entries(entries: &[DailyEntry]) -> Result<(), String> {
+    write_json(&leaderboard_path(), entries)
+}
+
+/// Save the answer key of a day unless one is already saved: the first generation wins.
+pub fn save_answer_key(date: NaiveDate, key: Vec<KeyQuestion>) -> Result<(), String> {
+    let _guard = DAILY_LOCK.lock();
+    save_key_to(&answers_path(), date, key)
+}
+
+fn save_key_to(p: &Path, date: NaiveDate, key: Vec<KeyQuestion>) -> Result<(), String> {
+    let mut keys: BTreeMap<NaiveDate, Vec<KeyQuestion>> = read_json(p)?;
+    if keys.contains_key(&date) { return Ok(()); }
+    keys.insert(date, key);
+    write_json(p, &keys)
+}
+
+/// The saved answer key of a day; None if its challenge was never generated.
+pub fn answer_key(date: NaiveDate) -> Result<Option<Vec<KeyQuestion>>, String> {
+    let _guard = DAILY_LOCK.lock();
+    key_from(&answers_path(), date)
+}
+
+fn key_from(p: &Path, date: NaiveDate) -> Result<Option<Vec<KeyQuestion>>, String> {
+    let mut keys: BTreeMap<NaiveDate, Vec<KeyQuestion>> = read_json(p)?;
+    Ok(keys.remove(&date))
+}
+
+pub fn has_submitted(date: NaiveDate, player_id: &str) -> Result<bool, String> {
+    let _guard = DAILY_LOCK.lock();
+    Ok(load_entries()?.iter().any(|e| e.date == date && e.player_id == player_id))
+}
+
+fn check_unique(entries: &[DailyEntry], entry: &DailyEntry) -> Result<(), SubmitError> {
+    let same_day = entries.iter().filter(|e| e.date == entry.date);
+    for e in same_day {
+        if e.player_id == entry.player_id { return Err(SubmitError::AlreadySubmitted); }
+        if e.nickname == entry.nickname { return Err(SubmitError::NicknameTaken); }
+    }
+    Ok(())
+}
+
+/// Record a player's result for the day. Returns the entry with its time filled in.
+pub fn submit(date: NaiveDate, player_id: &str, nickname: String, score: usize, rounds: usize) -> Result<DailyEntry, SubmitError> {
+    let now = Utc::now();
+    let started = started_at(date, player_id).ok_or(SubmitError::NotStarted)?;
+    let time_taken_ms = Some((now - started).num_milliseconds().max(0) as u64);
+    let entry = DailyEntry { date, player_id: player_id.to_string(), nickname, score, rounds, time_taken_ms, submitted_at: now };
+    let _guard = DAILY_LOCK.lock();
+    let mut entries = load_entries().map_err(SubmitError::Io)?;
+    check_unique(&entries, &entry)?;
+    entries.push(entry.clone());
+    save_entries(&entries).map_err(SubmitError::Io)?;
+    Ok(entry)
+}
+
+pub fn top(date: NaiveDate, limit: usize) -> Result<Vec<DailyRank>, String> {
+    let entries = {
+        let _guard = DAILY_LOCK.lock();
+        load_entries()?
+    };
+    Ok(rank(entries, date, limit))
+}
+
+// best score first; ties go to the faster (unknown times last), then the earlier submission
+fn rank(mut entries: Vec<DailyEntry>, date: NaiveDate, limit: usize) -> Vec<DailyRank> {
+    entries.retain(|e| e.date == date);
+    entries.sort_by(|a, b| b.score.cmp(&a.score)
+        .then(a.time_taken_ms.unwrap_or(u64::MAX).cmp(&b.time_taken_ms.unwrap_or(u64::MAX)))
+        .then(a.submitted_at.cmp(&b.submitted_at)));
+    entries.into_iter().take(limit).enumerate().map(|(i, e)| DailyRank {
+        rank: i + 1,
+        nickname: e.nickname,
+        score: e.score,
+        rounds: e.rounds,
+        time_taken_ms: e.time_taken_ms,
+        submitted_at: e.submitted_at,
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(date: NaiveDate, player_id: &str, nickname: &str, score: usize, time_taken_ms: Option<u64>) -> DailyEntry {
+        DailyEntry { date, player_id: player_id.to_string(), nickname: nickname.to_string(), score, rounds: 5, time_taken_ms, submitted_at: Utc::now() }
+    }
+
+    #[test]
+    fn test_seed_is_stable_per_day() {
+        let d = NaiveDate::from_ymd_opt(2026, 1, 2).unwrap();
+        assert_eq!(seed_with(d, "s"), seed_with(d, "s"));
+        assert_ne!(seed_with(d, "s"), seed_with(d.succ_opt().unwrap(), "s"));
+        assert_ne!(seed_with(d, "s"), seed_with(d, "t"));
+        // known FNV-1a vector, so seeds do not change between builds
+        assert_eq!(fnv1a(b"a"), 0xaf63dc4c8601ec8c);
+    }
+
+    #[test]
+    fn test_secret_is_generated_once_and_kept() {
+        let p = std::env::temp_dir().join(format!("tanuki-daily-secret-{}", uuid::Uuid::new_v4()));
+        let first = load_or_create_secret(&p).unwrap();
+        assert_eq!(first.len(), 32);
+        assert_eq!(load_or_create_secret(&p).unwrap(), first);
+        let _ = std::fs::remove_file(&p);
+        assert!(!is_over(today()));
+        assert!(is_over(today().pred_opt().unwrap()));
+    }
+
+    #[test]
+    fn test_first_answer_key_of_a_day_is_kept() {
+        let p = std::env::temp_dir().join(format!("tanuki-daily-answers-{}.json", uuid::Uuid::new_v4()));
+        let d = NaiveDate::from_ymd_opt(2026, 1, 2).unwrap();
+        let key = |answer: &str| vec![KeyQuestion { quiz_id: format!("{}#1", d), answer: answer.to_string(), photos: vec![format!("{}.jpg", answer)] }];
+        assert_eq!(key_from(&p, d).unwrap(), None);
+        save_key_to(&p, d, key("tanuki")).unwrap();
+        save_key_to(&p, d, key("badger")).unwrap();
+        assert_eq!(key_from(&p, d).unwrap(), Some(key("tanuki")));
+        std::fs::write(&p, "{").unwrap();
+        assert!(key_from(&p, d).is_err());
+        let _ = std::fs::remove_file(&p);
+    }
+
+    #[test]
+    fn test_one_submission_per_player_and_nickname_per_day() {
+        let d = NaiveDate::from_ymd_opt(2026, 1, 2).unwrap();
+        let entries = vec![entry(d, "p1", "たぬき", 3, None)];
+        assert!(matches!(check_unique(&entries, &entry(d, "p1", "other", 5, None)), Err(SubmitError::AlreadySubmitted)));
+        assert!(matches!(check_unique(&entries, &entry(d, "p2", "たぬき", 5, None)), Err(SubmitError::NicknameTaken)));
+        assert!(check_unique(&entries, &entry(d.succ_opt().unwrap(), "p1", "たぬき", 5, None)).is_ok());
+    }
+
+    #[test]
+    fn test_rank_filters_day_and_orders_by_score_then_time() {
+        let d = NaiveDate::from_ymd_opt(2026, 1, 2).unwrap();
+        let other = d.pred_opt().unwrap();
+        let ranked = rank(vec![
+            entry(d, "a", "untimed", 4, None),
+            entry(d, "b", "slow", 4, Some(9000)),
+            entry(d, "c", "best", 5, Some(20000)),
+            entry(other, "d", "yesterday", 5, Some(1)),
+        ], d, 10);
+        let names: Vec<&str> = ranked.iter().map(|r| r.nickname.as_str()).collect();
+        assert_eq!(names, vec!["best", "slow", "untimed"]);
+        assert_eq!(ranked[2].rank, 3);
+        assert!(valid_player_id("3f2a-xy_9"));
+        assert!(!valid_player_id("has space"));
+    }
+}
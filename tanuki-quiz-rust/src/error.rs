@@ -0,0 +1,179 @@
+// Shared API error type.
+//
+// Every handler failure is turned into a real HTTP status plus a JSON body of the form
+// { "error": { "code": "not_found", "message": "...", "details": [...] } }
+// so clients and monitoring can tell e.g. a bad admin token (401) from an empty result.
+
+use axum::async_trait;
+use axum::extract::rejection::{JsonRejection, PathRejection, QueryRejection};
+use axum::extract::{FromRequest, FromRequestParts, Path, Query, Request};
+use axum::http::request::Parts;
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+#[derive(Debug)]
+pub struct ApiError {
+    pub status: StatusCode,
+    pub message: String,
+    pub details: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct ErrorBody<'a> {
+    error: ErrorDetail<'a>,
+}
+
+#[derive(Serialize)]
+struct ErrorDetail<'a> {
+    code: &'static str,
+    message: &'a str,
+    #[serde(skip_serializing_if = "<[String]>::is_empty")]
+    details: &'a [String],
+}
+
+impl ApiError {
+    pub fn new(status: StatusCode, message: impl Into<String>) -> ApiError {
+        ApiError { status, message: message.into(), details: Vec::new() }
+    }
+
+    pub fn bad_request(message: impl Into<String>) -> ApiError { ApiError::new(StatusCode::BAD_REQUEST, message) }
+    pub fn unauthorized(message: impl Into<String>) -> ApiError { ApiError::new(StatusCode::UNAUTHORIZED, message) }
+    pub fn forbidden(message: impl Into<String>) -> ApiError { ApiError::new(StatusCode::FORBIDDEN, message) }
+    pub fn not_found(message: impl Into<String>) -> ApiError { ApiError::new(StatusCode::NOT_FOUND, message) }
+    pub fn conflict(message: impl Into<String>) -> ApiError { ApiError::new(StatusCode::CONFLICT, message) }
+    pub fn payload_too_large(message: impl Into<String>) -> ApiError { ApiError::new(StatusCode::PAYLOAD_TOO_LARGE, message) }
+    pub fn too_many_requests(message: impl Into<String>) -> ApiError { ApiError::new(StatusCode::TOO_MANY_REQUESTS, message) }
+    pub fn unavailable(message: impl Into<String>) -> ApiError { ApiError::new(StatusCode::SERVICE_UNAVAILABLE, message) }
+    pub fn internal(message: impl Into<String>) -> ApiError { ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, message) }
+
+    pub fn with_details(mut self, details: Vec<String>) -> ApiError {
+        self.details = details;
+        self
+    }
+
+    /// Stable machine-readable code for the status.
+    pub fn code(&self) -> &'static str {
+        match self.status {
+            StatusCode::BAD_REQUEST => "bad_request",
+            StatusCode::UNAUTHORIZED => "unauthorized",
+            StatusCode::FORBIDDEN => "forbidden",
+            StatusCode::NOT_FOUND => "not_found",
+            StatusCode::CONFLICT => "conflict",
+            StatusCode::PAYLOAD_TOO_LARGE => "payload_too_large",
+            StatusCode::UNSUPPORTED_MEDIA_TYPE => "unsupported_media_type",
+            StatusCode::UNPROCESSABLE_ENTITY => "unprocessable_entity",
+            StatusCode::TOO_MANY_REQUESTS => "too_many_requests",
+            StatusCode::SERVICE_UNAVAILABLE => "unavailable",
+            _ => "internal",
+        }
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        if self.status.is_server_error() { eprintln!("api error {}: {}", self.status.as_u16(), self.message); }
+        let body = ErrorBody { error: ErrorDetail { code: self.code(), message: &self.message, details: &self.details } };
+        (self.status, Json(body)).into_response()
+    }
+}
+
+impl From<JsonRejection> for ApiError {
+    fn from(r: JsonRejection) -> ApiError {
+        ApiError::new(r.status(), r.body_text())
+    }
+}
+
+impl From<QueryRejection> for ApiError {
+    fn from(r: QueryRejection) -> ApiError {
+        ApiError::new(r.status(), r.body_text())
+    }
+}
+
+impl From<PathRejection> for ApiError {
+    fn from(r: PathRejection) -> ApiError {
+        ApiError::new(r.status(), r.body_text())
+    }
+}
+
+impl From<axum::extract::multipart::MultipartError> for ApiError {
+    fn from(e: axum::extract::multipart::MultipartError) -> ApiError {
+        ApiError::new(e.status(), e.body_text())
+    }
+}
+
+/// `Json` extractor whose rejections (bad JSON, body too large, ...) use the ApiError body.
+pub struct ApiJson<T>(pub T);
+
+#[async_trait]
+impl<T, S> FromRequest<S> for ApiJson<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let Json(value) = Json::<T>::from_request(req, state).await?;
+        Ok(ApiJson(value))
+    }
+}
+
+/// `Query` extractor whose rejections (e.g. a number that does not parse) use the ApiError body.
+pub struct ApiQuery<T>(pub T);
+
+#[async_trait]
+impl<T, S> FromRequestParts<S> for ApiQuery<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let Query(value) = Query::<T>::from_request_parts(parts, state).await?;
+        Ok(ApiQuery(value))
+    }
+}
+
+/// `Path` extractor whose rejections (e.g. a bad index in the URL) use the ApiError body.
+pub struct ApiPath<T>(pub T);
+
+#[async_trait]
+impl<T, S> FromRequestParts<S> for ApiPath<T>
+where
+    T: DeserializeOwned + Send,
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let Path(value) = Path::<T>::from_request_parts(parts, state).await?;
+        Ok(ApiPath(value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_codes_follow_status() {
+        assert_eq!(ApiError::unauthorized("x").code(), "unauthorized");
+        assert_eq!(ApiError::payload_too_large("x").code(), "payload_too_large");
+        assert_eq!(ApiError::new(StatusCode::IM_A_TEAPOT, "x").code(), "internal");
+    }
+
+    #[tokio::test]
+    async fn test_error_body_shape() {
+        let res = ApiError::bad_request("catalog invalid").with_details(vec!["question 1: answer is empty".to_string()]).into_response();
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+        let bytes = axum::body::to_bytes(res.into_body(), 4096).await.unwrap();
+        let v: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(v["error"]["code"], "bad_request");
+        assert_eq!(v["error"]["message"], "catalog invalid");
+        assert_eq!(v["error"]["details"][0], "question 1: answer is empty");
+    }
+}
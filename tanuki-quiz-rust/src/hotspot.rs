@@ -0,0 +1,129 @@
+// Hotspot annotations: named regions on asset photos, and scoring clicks against them.
+//
+// An admin outlines features such as the facial mask or the tail rings as polygons on a photo
+// (POST /api/admin/annotations). Coordinates are fractions of the image width and height, with
+// (0, 0) at the top left, so they hold for the photo, its thumbnail and any display size.
+// A "click the feature" quiz (/api/hotspot_quiz) shows one annotated photo and asks for one
+// feature; the click is correct when it falls inside any region with that feature name, so a
+// feature can have several regions (two ears).
+
+use serde::{Deserialize, Serialize};
+
+pub const MAX_ANNOTATIONS: usize = 20;
+pub const MAX_POINTS: usize = 64;
+const MAX_FEATURE_CHARS: usize = 32;
+
+/// A point as [x, y], each from 0.0 to 1.0.
+pub type Point = [f64; 2];
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Annotation {
+    // feature name, e.g. "face_mask"; translated through the "feature.<name>" locale keys
+    pub feature: String,
+    // polygon corners in order; the last corner connects back to the first
+    pub points: Vec<Point>,
+}
+
+/// What a hotspot quiz asks for: a feature and every region of it on the photo.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Target {
+    pub feature: String,
+    pub regions: Vec<Vec<Point>>,
+}
+
+impl Target {
+    pub fn hit(&self, click: Point) -> bool {
+        self.regions.iter().any(|r| contains(r, click))
+    }
+}
+
+/// Every problem with a set of annotations for one photo, as readable messages.
+pub fn validate(annotations: &[Annotation]) -> Vec<String> {
+    let mut errors = Vec::new();
+    if annotations.len() > MAX_ANNOTATIONS {
+        errors.push(format!("at most {} regions per photo", MAX_ANNOTATIONS));
+    }
+    for (i, a) in annotations.iter().enumerate() {
+        let n = i + 1;
+        if !valid_feature(&a.feature) {
+            errors.push(format!("region {}: feature must be 1-{} characters of a-z, 0-9 and _", n, MAX_FEATURE_CHARS));
+        }
+        if a.points.len() < 3 || a.points.len() > MAX_POINTS {
+            errors.push(format!("region {}: needs 3 to {} points", n, MAX_POINTS));
+        }
+        if !a.points.iter().all(|&p| valid_point(p)) {
+            errors.push(format!("region {}: coordinates must be between 0 and 1", n));
+        }
+    }
+    errors
+}
+
+fn valid_feature(feature: &str) -> bool {
+    !feature.is_empty() && feature.len() <= MAX_FEATURE_CHARS && feature.bytes().all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'_')
+}
+
+pub fn valid_point([x, y]: Point) -> bool {
+    (0.0..=1.0).contains(&x) && (0.0..=1.0).contains(&y)
+}
+
+/// Features on a photo with their regions, in the order they were first annotated.
+pub fn targets(annotations: &[Annotation]) -> Vec<Target> {
+    let mut out: Vec<Target> = Vec::new();
+    for a in annotations {
+        match out.iter_mut().find(|t| t.feature == a.feature) {
+            Some(t) => t.regions.push(a.points.clone()),
+            None => out.push(Target { feature: a.feature.clone(), regions: vec![a.points.clone()] }),
+        }
+    }
+    out
+}
+
+// even-odd ray casting; a click exactly on an edge may land either way
+fn contains(polygon: &[Point], [x, y]: Point) -> bool {
+    let mut inside = false;
+    let mut j = polygon.len().wrapping_sub(1);
+    for (i, &[xi, yi]) in polygon.iter().enumerate() {
+        let [xj, yj] = polygon[j];
+        if (yi > y) != (yj > y) && x < (xj - xi) * (y - yi) / (yj - yi) + xi {
+            inside = !inside;
+        }
+        j = i;
+    }
+    inside
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn region(feature: &str, points: &[Point]) -> Annotation {
+        Annotation { feature: feature.to_string(), points: points.to_vec() }
+    }
+
+    #[test]
+    fn test_clicks_inside_concave_regions_and_any_region_of_a_feature() {
+        // an L shape: the notch at the top right is outside
+        let l = [[0.1, 0.1], [0.3, 0.1], [0.3, 0.4], [0.6, 0.4], [0.6, 0.6], [0.1, 0.6]];
+        let targets = targets(&[region("ears", &l), region("face_mask", &[[0.7, 0.7], [0.9, 0.7], [0.8, 0.9]]), region("ears", &[[0.7, 0.1], [0.9, 0.1], [0.9, 0.3], [0.7, 0.3]])]);
+        assert_eq!(targets.iter().map(|t| t.feature.as_str()).collect::<Vec<_>>(), vec!["ears", "face_mask"]);
+        let ears = &targets[0];
+        assert!(ears.hit([0.2, 0.2]));
+        assert!(ears.hit([0.5, 0.5]));
+        assert!(!ears.hit([0.5, 0.2]));
+        assert!(ears.hit([0.8, 0.2]));
+        assert!(!ears.hit([0.8, 0.8]));
+        assert!(targets[1].hit([0.8, 0.75]));
+    }
+
+    #[test]
+    fn test_validate_reports_each_problem() {
+        assert!(validate(&[region("tail_rings", &[[0.0, 0.0], [1.0, 0.0], [1.0, 1.0]])]).is_empty());
+        let errors = validate(&[
+            region("Tail Rings", &[[0.0, 0.0], [1.0, 0.0], [1.0, 1.0]]),
+            region("ears", &[[0.0, 0.0], [1.0, 0.0]]),
+            region("ears", &[[0.0, 0.0], [1.5, 0.0], [f64::NAN, 1.0]]),
+        ]);
+        assert_eq!(errors.len(), 3, "{:?}", errors);
+        assert_eq!(validate(&vec![region("ears", &[[0.0, 0.0], [1.0, 0.0], [1.0, 1.0]]); MAX_ANNOTATIONS + 1]).len(), 1);
+    }
+}
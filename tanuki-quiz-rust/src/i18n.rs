@@ -0,0 +1,148 @@
+// Localization of player-facing text.
+//
+// Question templates and error messages live in data/locales/<lang>.json (override the
+// directory with LOCALES_DIR) as flat "key": "text" maps with {name} placeholders.
+// Species names and explanations are localized in the species registry instead.
+// The locale of a request comes from ?lang=, then Accept-Language, then DEFAULT_LOCALE.
+
+use axum::async_trait;
+use axum::extract::{FromRequestParts, Query};
+use axum::http::header::ACCEPT_LANGUAGE;
+use axum::http::request::Parts;
+use once_cell::sync::Lazy;
+use parking_lot::RwLock;
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::env;
+use std::path::PathBuf;
+
+pub const DEFAULT_LOCALE: &str = "ja";
+
+// shipped locales with their bundled catalogs; the first one is the default
+const BUNDLED: &[(&str, &str)] = &[
+    ("ja", include_str!("../data/locales/ja.json")),
+    ("en", include_str!("../data/locales/en.json")),
+];
+
+static CATALOGS: Lazy<RwLock<HashMap<&'static str, HashMap<String, String>>>> = Lazy::new(|| {
+    let catalogs = BUNDLED.iter().map(|(lang, s)| (*lang, serde_json::from_str(s).expect("bundled locale catalog must be valid"))).collect();
+    RwLock::new(catalogs)
+});
+
+pub fn locales_dir() -> PathBuf {
+    env::var("LOCALES_DIR").map(PathBuf::from).unwrap_or_else(|_| PathBuf::from("data").join("locales"))
+}
+
+/// Load every supported locale from LOCALES_DIR. Locales whose file is missing or invalid keep their bundled catalog.
+pub fn reload() -> Result<usize, Vec<String>> {
+    let dir = locales_dir();
+    let mut errors = Vec::new();
+    let mut loaded = 0;
+    for (lang, _) in BUNDLED {
+        let p = dir.join(format!("{}.json", lang));
+        match std::fs::read_to_string(&p).map_err(|e| e.to_string()).and_then(|s| serde_json::from_str::<HashMap<String, String>>(&s).map_err(|e| e.to_string())) {
+            Ok(catalog) => {
+                CATALOGS.write().insert(lang, catalog);
+                loaded += 1;
+            }
+            Err(e) => errors.push(format!("{}: {}", p.display(), e)),
+        }
+    }
+    if errors.is_empty() { Ok(loaded) } else { Err(errors) }
+}
+
+/// Supported locale for a language tag such as "en-US" or "JA".
+pub fn supported(tag: &str) -> Option<&'static str> {
+    let primary = tag.split(['-', '_']).next()?.trim().to_ascii_lowercase();
+    BUNDLED.iter().map(|(lang, _)| *lang).find(|lang| *lang == primary)
+}
+
+/// Best supported locale from an Accept-Language header, honouring q-values.
+pub fn from_accept_language(header: &str) -> Option<&'static str> {
+    let mut ranges: Vec<(&str, f32)> = header.split(',').filter_map(|part| {
+        let mut it = part.split(';');
+        let tag = it.next()?.trim();
+        let q = it.find_map(|p| p.trim().strip_prefix("q=").and_then(|q| q.parse::<f32>().ok())).unwrap_or(1.0);
+        if tag.is_empty() || q <= 0.0 { None } else { Some((tag, q)) }
+    }).collect();
+    // stable sort keeps header order for equal weights
+    ranges.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+    ranges.into_iter().find_map(|(tag, _)| supported(tag))
+}
+
+/// Text for `key` in `lang`, falling back to the default locale and then to the key itself.
+pub fn t(lang: &str, key: &str) -> String {
+    let catalogs = CATALOGS.read();
+    catalogs.get(lang).and_then(|c| c.get(key))
+        .or_else(|| catalogs.get(DEFAULT_LOCALE).and_then(|c| c.get(key)))
+        .cloned()
+        .unwrap_or_else(|| key.to_string())
+}
+
+/// Like `t`, replacing `{name}` placeholders with the given values.
+pub fn tf(lang: &str, key: &str, args: &[(&str, &str)]) -> String {
+    let mut s = t(lang, key);
+    for (name, value) in args {
+        s = s.replace(&format!("{{{}}}", name), value);
+    }
+    s
+}
+
+/// Request locale extractor: `?lang=` wins over Accept-Language; unsupported values fall through.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Locale(pub &'static str);
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for Locale {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let from_query = Query::<HashMap<String, String>>::try_from_uri(&parts.uri).ok()
+            .and_then(|Query(q)| q.get("lang").and_then(|l| supported(l)));
+        let from_header = || parts.headers.get(ACCEPT_LANGUAGE).and_then(|v| v.to_str().ok()).and_then(from_accept_language);
+        Ok(Locale(from_query.or_else(from_header).unwrap_or(DEFAULT_LOCALE)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_accept_language_prefers_weights_and_supported_tags() {
+        assert_eq!(from_accept_language("en-US,en;q=0.9,ja;q=0.8"), Some("en"));
+        assert_eq!(from_accept_language("fr-FR, ja;q=0.5, en;q=0.7"), Some("en"));
+        assert_eq!(from_accept_language("de, fr;q=0.9"), None);
+        assert_eq!(from_accept_language("en;q=0, ja"), Some("ja"));
+        assert_eq!(supported("JA_jp"), Some("ja"));
+    }
+
+    #[test]
+    fn test_lookup_and_placeholders() {
+        assert_eq!(tf("en", "question.which", &[("label", "raccoon dog")]), "Which of these images shows the raccoon dog?");
+        assert_eq!(tf("ja", "error.question_not_found", &[("id", "7")]), "問題 7 が見つかりません");
+        // unknown locales use the default, unknown keys come back unchanged
+        assert_eq!(t("fr", "error.unknown_choice"), t(DEFAULT_LOCALE, "error.unknown_choice"));
+        assert_eq!(t("en", "no.such.key"), "no.such.key");
+    }
+
+    #[test]
+    fn test_bundled_catalogs_have_the_same_keys() {
+        let catalogs: Vec<HashMap<String, String>> = BUNDLED.iter().map(|(_, s)| serde_json::from_str(s).unwrap()).collect();
+        let keys: Vec<HashSet<&String>> = catalogs.iter().map(|c| c.keys().collect()).collect();
+        for (i, k) in keys.iter().enumerate() {
+            assert_eq!(k, &keys[0], "locale {} keys differ from {}", BUNDLED[i].0, BUNDLED[0].0);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_extractor_query_overrides_header() {
+        let req = axum::http::Request::builder().uri("/api/generate_quiz?lang=en").header(ACCEPT_LANGUAGE, "ja").body(()).unwrap();
+        let (mut parts, _) = req.into_parts();
+        assert_eq!(Locale::from_request_parts(&mut parts, &()).await.unwrap(), Locale("en"));
+        let req = axum::http::Request::builder().uri("/api/generate_quiz?lang=xx").header(ACCEPT_LANGUAGE, "en-GB").body(()).unwrap();
+        let (mut parts, _) = req.into_parts();
+        assert_eq!(Locale::from_request_parts(&mut parts, &()).await.unwrap(), Locale("en"));
+    }
+}
@@ -0,0 +1,146 @@
+// Per-photo answer statistics.
+//
+// Every scored answer is counted against each photo in public/assets that the player was shown.
+// The counts live in memory and are written to data/image_stats.json (override with
+// IMAGE_STATS_PATH) by the periodic cleanup task, so an answer never waits on the disk and
+// never rewrites the asset index. Stats that older versions kept in index.json are taken over
+// the first time the server starts without a stats file.
+
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::env;
+use std::path::{Path, PathBuf};
+
+#[derive(Serialize, Deserialize, Clone, Default, Debug, PartialEq)]
+pub struct ImageStats {
+    // scored answers to quizzes that showed the photo
+    pub shown: u64,
+    pub correct: u64,
+    // when the photo was the one to find: species picked instead, with counts
+    #[serde(default)]
+    pub wrong_picks: BTreeMap<String, u64>,
+    // when the photo itself was picked wrongly: species it was taken for, with counts
+    #[serde(default)]
+    pub mistaken_for: BTreeMap<String, u64>,
+}
+
+// one scored answer as seen from one of the photos shown
+#[derive(Debug, PartialEq)]
+pub struct ImageOutcome {
+    pub correct: bool,
+    pub wrong_pick: Option<String>,
+    pub mistaken_for: Option<String>,
+}
+
+impl ImageStats {
+    pub fn record(&mut self, o: &ImageOutcome) {
+        self.shown += 1;
+        if o.correct { self.correct += 1; }
+        if let Some(sp) = &o.wrong_pick { *self.wrong_picks.entry(sp.clone()).or_insert(0) += 1; }
+        if let Some(sp) = &o.mistaken_for { *self.mistaken_for.entry(sp.clone()).or_insert(0) += 1; }
+    }
+}
+
+#[derive(Default)]
+struct Store {
+    stats: BTreeMap<String, ImageStats>,
+    // changed since the last flush
+    dirty: bool,
+}
+
+static STATS: Lazy<Mutex<Store>> = Lazy::new(|| Mutex::new(Store::default()));
+
+// the part of an index.json entry that older versions kept stats in
+#[derive(Deserialize)]
+struct LegacyEntry {
+    filename: String,
+    #[serde(default)]
+    stats: ImageStats,
+}
+
+fn stats_path() -> PathBuf {
+    env::var("IMAGE_STATS_PATH").map(PathBuf::from).unwrap_or_else(|_| PathBuf::from("data").join("image_stats.json"))
+}
+
+/// Load the stats file, or take over the stats in `index` (public/assets/index.json) when there is none yet.
+/// Returns the number of photos with stats.
+pub fn load(index: &Path) -> Result<usize, String> {
+    let p = stats_path();
+    let (stats, migrated) = match std::fs::read_to_string(&p) {
+        Ok(s) => (serde_json::from_str(&s).map_err(|e| format!("cannot parse {}: {}", p.display(), e))?, false),
+        Err(_) => (legacy_stats(index), true),
+    };
+    let n = {
+        let mut store = STATS.lock();
+        store.stats = stats;
+        store.dirty = migrated && !store.stats.is_empty();
+        store.stats.len()
+    };
+    flush()?;
+    Ok(n)
+}
+
+fn legacy_stats(index: &Path) -> BTreeMap<String, ImageStats> {
+    let entries: Vec<LegacyEntry> = std::fs::read_to_string(index).ok().and_then(|s| serde_json::from_str(&s).ok()).unwrap_or_default();
+    entries.into_iter().filter(|e| e.stats.shown > 0).map(|e| (e.filename, e.stats)).collect()
+}
+
+pub fn record(outcomes: Vec<(String, ImageOutcome)>) {
+    if outcomes.is_empty() { return; }
+    let mut store = STATS.lock();
+    for (file, outcome) in outcomes {
+        store.stats.entry(file).or_default().record(&outcome);
+    }
+    store.dirty = true;
+}
+
+pub fn get(file: &str) -> Option<ImageStats> {
+    STATS.lock().stats.get(file).cloned()
+}
+
+pub fn all() -> BTreeMap<String, ImageStats> {
+    STATS.lock().stats.clone()
+}
+
+/// Forget a deleted photo.
+pub fn remove(file: &str) {
+    let mut store = STATS.lock();
+    if store.stats.remove(file).is_some() { store.dirty = true; }
+}
+
+/// Write the stats file if anything changed. Write then rename, so a crash never leaves half a file.
+pub fn flush() -> Result<(), String> {
+    let snapshot = {
+        let mut store = STATS.lock();
+        if !store.dirty { return Ok(()); }
+        store.dirty = false;
+        store.stats.clone()
+    };
+    write(&stats_path(), &snapshot).inspect_err(|_| STATS.lock().dirty = true)
+}
+
+fn write(p: &Path, stats: &BTreeMap<String, ImageStats>) -> Result<(), String> {
+    if let Some(dir) = p.parent() { std::fs::create_dir_all(dir).map_err(|e| format!("mkdir error: {}", e))?; }
+    let s = serde_json::to_string(stats).map_err(|e| e.to_string())?;
+    let tmp = p.with_extension("json.tmp");
+    std::fs::write(&tmp, s).map_err(|e| format!("write error: {}", e))?;
+    std::fs::rename(&tmp, p).map_err(|e| format!("rename error: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_legacy_index_stats_are_taken_over() {
+        let p = std::env::temp_dir().join(format!("tanuki-index-{}.json", uuid::Uuid::new_v4()));
+        std::fs::write(&p, r#"[{"filename":"tanuki1.jpg","size":1,"stats":{"shown":3,"correct":2}},{"filename":"tanuki2.jpg","size":1}]"#).unwrap();
+        let stats = legacy_stats(&p);
+        // photos that were never shown have nothing to take over
+        assert_eq!(stats.len(), 1);
+        assert_eq!((stats["tanuki1.jpg"].shown, stats["tanuki1.jpg"].correct), (3, 2));
+        let _ = std::fs::remove_file(&p);
+    }
+}
@@ -0,0 +1,147 @@
+// Server-side image variants for quiz choices.
+//
+// Hard quizzes never send the original photo: the choice is cropped, turned to
+// grayscale and pixelated here before it leaves the server. Reveal quizzes use the
+// same path for their blurred or pixelated frames, and /media (media.rs) its crop and
+// encoders for resized variants.
+
+use image::imageops::FilterType;
+use image::{DynamicImage, ImageOutputFormat};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::io::Cursor;
+
+// output size of degraded choices (matches upload thumbnails)
+const OUT_W: u32 = 320;
+const OUT_H: u32 = 240;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Degrade {
+    // crop rectangle as fractions of the source image
+    pub crop_x: f32,
+    pub crop_y: f32,
+    pub crop_w: f32,
+    pub crop_h: f32,
+    pub grayscale: bool,
+    // width the crop is shrunk to before being scaled back up (pixelation); 0 keeps full detail
+    pub low_res_width: u32,
+    // gaussian blur sigma in output pixels; 0 for none
+    #[serde(default)]
+    pub blur: f32,
+}
+
+impl Degrade {
+    /// Random degradation for hard quizzes: a 40-65% crop, grayscale, 32-64px effective width and a light blur.
+    pub fn random<R: Rng + ?Sized>(rng: &mut R) -> Degrade {
+        let crop_w: f32 = rng.gen_range(0.4..=0.65);
+        let crop_h: f32 = rng.gen_range(0.4..=0.65);
+        Degrade {
+            crop_x: rng.gen_range(0.0..=(1.0 - crop_w)),
+            crop_y: rng.gen_range(0.0..=(1.0 - crop_h)),
+            crop_w,
+            crop_h,
+            grayscale: true,
+            low_res_width: rng.gen_range(32..=64),
+            blur: rng.gen_range(0.5..=1.5),
+        }
+    }
+}
+
+/// Crops to a rectangle given as fractions of the image; always keeps at least one pixel.
+pub fn crop_fraction(img: &DynamicImage, x: f32, y: f32, cw: f32, ch: f32) -> DynamicImage {
+    let (w, h) = (img.width(), img.height());
+    let x = ((x.clamp(0.0, 1.0) * w as f32) as u32).min(w.saturating_sub(1));
+    let y = ((y.clamp(0.0, 1.0) * h as f32) as u32).min(h.saturating_sub(1));
+    let cw = ((cw.clamp(0.0, 1.0) * w as f32) as u32).clamp(1, w - x);
+    let ch = ((ch.clamp(0.0, 1.0) * h as f32) as u32).clamp(1, h - y);
+    img.crop_imm(x, y, cw, ch)
+}
+
+pub fn degrade_image(img: &DynamicImage, d: &Degrade) -> DynamicImage {
+    let mut out = crop_fraction(img, d.crop_x, d.crop_y, d.crop_w, d.crop_h);
+    let (cw, ch) = (out.width(), out.height());
+    if d.grayscale { out = DynamicImage::ImageLuma8(out.to_luma8()); }
+    if d.low_res_width > 0 {
+        // shrink then blow back up with nearest-neighbour so detail is really gone
+        let small_w = d.low_res_width.clamp(1, cw);
+        let small_h = ((small_w as f32 * ch as f32 / cw as f32).round() as u32).max(1);
+        let small = out.resize_exact(small_w, small_h, FilterType::Triangle);
+        let (ow, oh) = fit_within(small_w, small_h, OUT_W, OUT_H);
+        out = small.resize_exact(ow, oh, FilterType::Nearest);
+    } else {
+        let (ow, oh) = fit_within(cw, ch, OUT_W, OUT_H);
+        out = out.resize_exact(ow, oh, FilterType::Triangle);
+    }
+    // blurring at output size keeps large photos cheap and the sigma independent of the source size
+    if d.blur > 0.0 { out = out.blur(d.blur); }
+    out
+}
+
+/// Shrinks an image to fit the quiz image size; smaller images are left as they are.
+pub fn fit_output(img: &DynamicImage) -> DynamicImage {
+    if img.width() <= OUT_W && img.height() <= OUT_H { return img.clone(); }
+    let (ow, oh) = fit_within(img.width(), img.height(), OUT_W, OUT_H);
+    img.resize_exact(ow, oh, FilterType::Triangle)
+}
+
+fn fit_within(w: u32, h: u32, max_w: u32, max_h: u32) -> (u32, u32) {
+    let scale = (max_w as f32 / w as f32).min(max_h as f32 / h as f32);
+    (((w as f32 * scale).round() as u32).max(1), ((h as f32 * scale).round() as u32).max(1))
+}
+
+pub fn encode_jpeg(img: &DynamicImage) -> Result<Vec<u8>, String> {
+    let mut buf: Vec<u8> = Vec::new();
+    // jpeg has no alpha channel
+    DynamicImage::ImageRgb8(img.to_rgb8()).write_to(&mut Cursor::new(&mut buf), ImageOutputFormat::Jpeg(85)).map_err(|e| e.to_string())?;
+    Ok(buf)
+}
+
+pub fn encode_png(img: &DynamicImage) -> Result<Vec<u8>, String> {
+    let mut buf: Vec<u8> = Vec::new();
+    img.write_to(&mut Cursor::new(&mut buf), ImageOutputFormat::Png).map_err(|e| e.to_string())?;
+    Ok(buf)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::RgbaImage;
+
+    #[test]
+    fn test_degrade_crops_grays_and_fits_output() {
+        let mut im = RgbaImage::new(800, 600);
+        for (x, _y, p) in im.enumerate_pixels_mut() { *p = image::Rgba([(x % 256) as u8, 40, 200, 255]); }
+        let d = Degrade { crop_x: 0.25, crop_y: 0.25, crop_w: 0.5, crop_h: 0.5, grayscale: true, low_res_width: 40, blur: 0.0 };
+        let out = degrade_image(&DynamicImage::ImageRgba8(im), &d);
+        assert!(out.width() <= OUT_W && out.height() <= OUT_H);
+        assert_eq!(out.width(), OUT_W);
+        let rgb = out.to_rgb8();
+        let p = rgb.get_pixel(10, 10);
+        assert!(p[0] == p[1] && p[1] == p[2], "expected grayscale pixel, got {:?}", p);
+    }
+
+    #[test]
+    fn test_blur_without_pixelation_smooths_edges() {
+        // left half black, right half white
+        let im = RgbaImage::from_fn(640, 480, |x, _| if x < 320 { image::Rgba([0, 0, 0, 255]) } else { image::Rgba([255, 255, 255, 255]) });
+        let d = Degrade { crop_x: 0.0, crop_y: 0.0, crop_w: 1.0, crop_h: 1.0, grayscale: false, low_res_width: 0, blur: 8.0 };
+        let out = degrade_image(&DynamicImage::ImageRgba8(im), &d).to_rgb8();
+        assert_eq!((out.width(), out.height()), (OUT_W, OUT_H));
+        let edge = out.get_pixel(OUT_W / 2, OUT_H / 2)[0];
+        assert!(edge > 40 && edge < 215, "expected a blurred edge, got {}", edge);
+        assert!(out.get_pixel(5, OUT_H / 2)[0] < 10);
+    }
+
+    #[test]
+    fn test_random_degrade_stays_inside_image() {
+        let mut rng = rand::thread_rng();
+        for _ in 0..100 {
+            let d = Degrade::random(&mut rng);
+            assert!(d.crop_x + d.crop_w <= 1.0 + f32::EPSILON);
+            assert!(d.crop_y + d.crop_h <= 1.0 + f32::EPSILON);
+        }
+        let tiny = DynamicImage::ImageRgba8(RgbaImage::new(3, 2));
+        let out = degrade_image(&tiny, &Degrade::random(&mut rng));
+        assert!(out.width() >= 1 && out.height() >= 1);
+    }
+}
@@ -0,0 +1,161 @@
+// Persistent leaderboard for finished quiz sessions.
+//
+// Scores are never taken from the client: POST /api/leaderboard only names a finished
+// session, and the score/time recorded by the server for that session are stored.
+// Entries are kept in data/leaderboard.json (override with LEADERBOARD_PATH).
+
+use chrono::{DateTime, Datelike, Duration, Local, TimeZone, Utc};
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::path::PathBuf;
+
+pub const MAX_NICKNAME_CHARS: usize = 20;
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct LeaderboardEntry {
+    pub nickname: String,
+    pub score: usize,
+    // from timed sessions; entries written before timed sessions have none
+    #[serde(default)]
+    pub speed_bonus: u32,
+    pub rounds: usize,
+    pub time_taken_ms: u64,
+    pub submitted_at: DateTime<Utc>,
+    pub session_id: String,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Period {
+    Day,
+    Week,
+    All,
+}
+
+impl Period {
+    pub fn parse(s: &str) -> Option<Period> {
+        match s {
+            "day" => Some(Period::Day),
+            "week" => Some(Period::Week),
+            "all" => Some(Period::All),
+            _ => None,
+        }
+    }
+
+    /// Start of the current calendar day / ISO week in server local time.
+    fn since(self, now: DateTime<Local>) -> Option<DateTime<Utc>> {
+        let today = now.date_naive();
+        let start = match self {
+            Period::Day => today,
+            Period::Week => today - Duration::days(today.weekday().num_days_from_monday() as i64),
+            Period::All => return None,
+        };
+        Local.from_local_datetime(&start.and_hms_opt(0, 0, 0)?).earliest().map(|d| d.with_timezone(&Utc))
+    }
+}
+
+// serializes read-modify-write cycles on the leaderboard file
+static LEADERBOARD_LOCK: Lazy<Mutex<()>> = Lazy::new(|| Mutex::new(()));
+
+fn leaderboard_path() -> PathBuf {
+    env::var("LEADERBOARD_PATH").map(PathBuf::from).unwrap_or_else(|_| PathBuf::from("data").join("leaderboard.json"))
+}
+
+// a missing file is an empty leaderboard; an unreadable one is an error, never silently reset
+fn load_entries() -> Result<Vec<LeaderboardEntry>, String> {
+    let s = match std::fs::read_to_string(leaderboard_path()) {
+        Ok(s) => s,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(format!("read error: {}", e)),
+    };
+    serde_json::from_str(&s).map_err(|e| format!("parse error: {}", e))
+}
+
+fn save_entries(entries: &[LeaderboardEntry]) -> Result<(), String> {
+    let p = leaderboard_path();
+    if let Some(dir) = p.parent() { std::fs::create_dir_all(dir).map_err(|e| format!("mkdir error: {}", e))?; }
+    let s = serde_json::to_string_pretty(entries).map_err(|e| e.to_string())?;
+    // write then rename, so a crash never leaves half a leaderboard behind
+    let tmp = p.with_extension("json.tmp");
+    std::fs::write(&tmp, s).map_err(|e| format!("write error: {}", e))?;
+    std::fs::rename(&tmp, &p).map_err(|e| format!("rename error: {}", e))
+}
+
+/// Trim and validate a player-supplied nickname.
+pub fn clean_nickname(raw: &str) -> Option<String> {
+    let n = raw.trim();
+    if n.is_empty() || n.chars().count() > MAX_NICKNAME_CHARS || n.chars().any(|c| c.is_control()) { return None; }
+    Some(n.to_string())
+}
+
+pub fn add_entry(entry: LeaderboardEntry) -> Result<(), String> {
+    let _guard = LEADERBOARD_LOCK.lock();
+    let mut entries = load_entries()?;
+    if entries.iter().any(|e| e.session_id == entry.session_id) { return Err("session already submitted".to_string()); }
+    entries.push(entry);
+    save_entries(&entries)
+}
+
+pub fn top(period: Period, limit: usize) -> Result<Vec<LeaderboardEntry>, String> {
+    let entries = {
+        let _guard = LEADERBOARD_LOCK.lock();
+        load_entries()?
+    };
+    Ok(rank(entries, period.since(Local::now()), limit))
+}
+
+// best score first; ties go to the larger speed bonus, then the faster, then the earlier submission
+fn rank(mut entries: Vec<LeaderboardEntry>, since: Option<DateTime<Utc>>, limit: usize) -> Vec<LeaderboardEntry> {
+    if let Some(since) = since { entries.retain(|e| e.submitted_at >= since); }
+    entries.sort_by(|a, b| b.score.cmp(&a.score).then(b.speed_bonus.cmp(&a.speed_bonus)).then(a.time_taken_ms.cmp(&b.time_taken_ms)).then(a.submitted_at.cmp(&b.submitted_at)));
+    entries.truncate(limit);
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(nickname: &str, score: usize, time_taken_ms: u64, submitted_at: DateTime<Utc>) -> LeaderboardEntry {
+        LeaderboardEntry { nickname: nickname.to_string(), score, speed_bonus: 0, rounds: 10, time_taken_ms, submitted_at, session_id: nickname.to_string() }
+    }
+
+    #[test]
+    fn test_rank_orders_by_score_then_time() {
+        let now = Utc::now();
+        let ranked = rank(vec![entry("slow", 8, 9000, now), entry("best", 9, 20000, now), entry("fast", 8, 3000, now)], None, 10);
+        let names: Vec<&str> = ranked.iter().map(|e| e.nickname.as_str()).collect();
+        assert_eq!(names, vec!["best", "fast", "slow"]);
+        let bonus = LeaderboardEntry { speed_bonus: 5, ..entry("bonus", 8, 9000, now) };
+        assert_eq!(rank(vec![entry("fast", 8, 3000, now), bonus], None, 10)[0].nickname, "bonus");
+    }
+
+    #[test]
+    fn test_rank_filters_by_period_and_limit() {
+        let now = Utc::now();
+        let old = now - Duration::days(10);
+        let ranked = rank(vec![entry("old", 10, 1000, old), entry("a", 5, 1000, now), entry("b", 4, 1000, now)], Some(now - Duration::days(1)), 1);
+        assert_eq!(ranked.len(), 1);
+        assert_eq!(ranked[0].nickname, "a");
+    }
+
+    #[test]
+    fn test_period_bounds() {
+        let now = Local::now();
+        let day = Period::Day.since(now).unwrap();
+        let week = Period::Week.since(now).unwrap();
+        assert!(week <= day && day <= now.with_timezone(&Utc));
+        assert!(Period::All.since(now).is_none());
+        assert_eq!(Period::parse("week"), Some(Period::Week));
+        assert_eq!(Period::parse("month"), None);
+    }
+
+    #[test]
+    fn test_clean_nickname() {
+        assert_eq!(clean_nickname("  たぬき名人 ").as_deref(), Some("たぬき名人"));
+        assert!(clean_nickname("   ").is_none());
+        assert!(clean_nickname("bad\nname").is_none());
+        assert!(clean_nickname(&"x".repeat(MAX_NICKNAME_CHARS + 1)).is_none());
+    }
+}
@@ -1,4 +1,4 @@
-use axum::{routing::get, routing::post, Json, Router, extract::Path, response::IntoResponse};
+use axum::{routing::get, routing::post, Json, Router, response::IntoResponse};
 use axum::http::HeaderMap;
 use rand::seq::SliceRandom;
 use serde::{Deserialize, Serialize};
@@ -12,15 +12,18 @@ use std::io::Cursor;
 use bytes::Bytes;
 use axum::http::header;
 use rand::Rng;
+use rand::SeedableRng;
+use rand::rngs::StdRng;
 use once_cell::sync::Lazy;
 use parking_lot::Mutex;
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 use std::time::{Duration, Instant};
 use uuid::Uuid;
 use base64::Engine;
 use base64::engine::general_purpose::STANDARD as BASE64;
-use axum::extract::Multipart;
-use axum::extract::Query;
+use axum::extract::{DefaultBodyLimit, Multipart};
+use axum::extract::{ConnectInfo, State};
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
 use std::collections::HashMap as StdHashMap;
 use reqwest::Client;
 use serde_json::Value;
@@ -28,13 +31,34 @@ use serde_json::Value;
 use std::fs::File;
 use std::io::Write;
 use std::io::Read;
-
-#[derive(Serialize, Clone)]
-struct QuizQuestion {
-    id: usize,
-    image_url: String,
-    answer: String,
-}
+use image_stats::{ImageOutcome, ImageStats};
+
+mod answer_log;
+mod catalog;
+mod classroom;
+mod daily;
+mod error;
+mod hotspot;
+mod i18n;
+mod image_stats;
+mod imaging;
+mod leaderboard;
+mod media;
+mod metrics;
+mod quiz_log;
+mod quiz_store;
+mod quiz_token;
+mod reveal;
+mod review;
+mod room;
+mod session;
+mod species;
+mod tanuki_or_not;
+use answer_log::AnswerRecord;
+use catalog::QuizQuestion;
+use error::{ApiError, ApiJson, ApiPath, ApiQuery};
+use i18n::Locale;
+use quiz_store::{Receipt, StoredQuiz};
 
 #[derive(Deserialize)]
 struct QuizAnswer {
@@ -42,55 +66,228 @@ struct QuizAnswer {
     answer: String,
 }
 
-#[derive(Serialize)]
+// Deserialize: kept as JSON in quiz receipts (quiz_store::Receipt)
+#[derive(Serialize, Deserialize)]
 struct QuizResult {
     correct: bool,
     correct_answer: String,
+    // correct_answer as a display name in the request locale, for generated quizzes
+    #[serde(skip_serializing_if = "Option::is_none")]
+    correct_label: Option<String>,
+    // identification tips for the correct species, from the species registry
+    #[serde(skip_serializing_if = "Option::is_none")]
+    explanation: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    info_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    session: Option<session::SessionProgress>,
+    // server-measured time from issuing a generated quiz to receiving the answer
+    #[serde(skip_serializing_if = "Option::is_none")]
+    response_ms: Option<u64>,
+    // points earned by this answer in a timed session
+    #[serde(skip_serializing_if = "Option::is_none")]
+    speed_bonus: Option<u32>,
+    // where the feature was in a hotspot quiz, in the same coordinates as the click
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    regions: Option<Vec<Vec<hotspot::Point>>>,
+    // points earned in a reveal quiz; fewer for each step revealed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    points: Option<u32>,
+}
+
+impl QuizResult {
+    fn new(correct: bool, correct_answer: String, sp: Option<species::Species>, lang: &str) -> QuizResult {
+        QuizResult {
+            correct,
+            correct_answer,
+            correct_label: None,
+            explanation: sp.as_ref().and_then(|sp| sp.explanation(lang)).map(|s| s.to_string()),
+            info_url: sp.and_then(|sp| sp.info_url),
+            session: None,
+            response_ms: None,
+            speed_bonus: None,
+            regions: None,
+            points: None,
+        }
+    }
+
+    // missing or expired generated quiz — treated as incorrect with a generic answer
+    fn unknown(lang: &str) -> QuizResult {
+        let mut result = QuizResult::new(false, "unknown".to_string(), None, lang);
+        result.correct_label = Some(i18n::t(lang, "result.unknown"));
+        result
+    }
 }
 
 // For generated-quiz submissions (client -> server)
 #[derive(Deserialize)]
 struct GeneratedSubmit {
     quiz_id: String,
-    selected_category: String,
-}
-
-// In-memory store for active generated quizzes
-static QUIZ_STORE: Lazy<Mutex<HashMap<String, (GeneratedQuiz, Instant)>>> = Lazy::new(|| Mutex::new(HashMap::new()));
-
-fn get_all_questions() -> Vec<QuizQuestion> {
-    vec![
-        // たぬき (Tanuki)
-    QuizQuestion { id: 1, image_url: "/images/tanuki1.png".to_string(), answer: "たぬき".to_string() },
-    QuizQuestion { id: 2, image_url: "/images/tanuki2.png".to_string(), answer: "たぬき".to_string() },
-    QuizQuestion { id: 3, image_url: "/images/tanuki3.png".to_string(), answer: "たぬき".to_string() },
-        // アナグマ (Anaguma)
-    QuizQuestion { id: 4, image_url: "/images/anaguma1.png".to_string(), answer: "アナグマ".to_string() },
-    QuizQuestion { id: 5, image_url: "/images/anaguma2.png".to_string(), answer: "アナグマ".to_string() },
-    QuizQuestion { id: 6, image_url: "/images/anaguma3.png".to_string(), answer: "アナグマ".to_string() },
-        // ハクビシン (Hakubishin)
-    QuizQuestion { id: 7, image_url: "/images/hakubishin1.png".to_string(), answer: "ハクビシン".to_string() },
-    QuizQuestion { id: 8, image_url: "/images/hakubishin2.png".to_string(), answer: "ハクビシン".to_string() },
-    QuizQuestion { id: 9, image_url: "/images/hakubishin3.png".to_string(), answer: "ハクビシン".to_string() },
-        // 追加問題 (Mixed)
-        QuizQuestion { id: 10, image_url: "/images/tanuki4.png".to_string(), answer: "たぬき".to_string() },
-        QuizQuestion { id: 11, image_url: "/images/anaguma4.png".to_string(), answer: "アナグマ".to_string() },
-        QuizQuestion { id: 12, image_url: "/images/hakubishin4.png".to_string(), answer: "ハクビシン".to_string() },
-    ]
-}
-
-#[derive(Serialize, Clone)]
+    // opaque choice token from GeneratedQuizResponse; resolved to a category on the server
+    choice: String,
+}
+
+// Shared state handed to handlers through axum State
+#[derive(Clone)]
+struct AppState {
+    // generated quizzes waiting for an answer (see quiz_store)
+    quizzes: quiz_store::Quizzes,
+}
+
+// Who a generated quiz is issued to, for QUIZ_MAX_PER_CLIENT: the peer address, or the first
+// X-Forwarded-For entry when TRUST_FORWARDED_FOR=true (only behind a proxy that sets it).
+fn client_key(headers: &HeaderMap, peer: SocketAddr) -> String {
+    if env::var("TRUST_FORWARDED_FOR").map(|v| v.to_lowercase() == "true").unwrap_or(false) {
+        let forwarded = headers.get("x-forwarded-for").and_then(|v| v.to_str().ok()).and_then(|v| v.split(',').next()).map(|v| v.trim());
+        if let Some(ip) = forwarded.filter(|v| !v.is_empty()) { return ip.to_string(); }
+    }
+    peer.ip().to_string()
+}
+
+// refuse a new quiz when the client already has QUIZ_MAX_PER_CLIENT open
+async fn check_open_quizzes(state: &AppState, client: &str, lang: &str) -> Result<(), ApiError> {
+    state.quizzes.check_client(client).await
+        .map_err(|limit| ApiError::too_many_requests(i18n::tf(lang, "error.too_many_open_quizzes", &[("max", &limit.max.to_string())])))
+}
+
+// keep the result of a scored quiz so a retried submit gets the same answer
+async fn keep_receipt(state: &AppState, id: &str, kind: QuizKind, answer: &str, result: &QuizResult) {
+    match serde_json::to_value(result) {
+        Ok(value) => state.quizzes.put_receipt(id, Receipt::new(kind, answer, value)).await,
+        Err(e) => eprintln!("cannot keep receipt for {}: {}", id, e),
+    }
+}
+
+// a repeated submit gets the original result; a different answer is refused
+fn replay_receipt(receipt: Receipt, answer: &str, lang: &str) -> Result<Json<QuizResult>, ApiError> {
+    if receipt.answer != answer { return Err(ApiError::conflict(i18n::t(lang, "error.answer_already_submitted"))); }
+    serde_json::from_value(receipt.result).map(Json).map_err(|e| ApiError::internal(format!("unreadable quiz receipt: {}", e)))
+}
+
+// quizzes dropped unanswered (expired or evicted): session rounds, timed or not, count as timed out so the session can still finish
+fn drop_unanswered(removed: Vec<StoredQuiz>) {
+    for stored in removed {
+        if let Some(sid) = &stored.quiz.session_id {
+            session::record_answer(sid, &stored.id, &stored.quiz.answer_category, session::Outcome::TimedOut);
+        }
+    }
+}
+
+// today's daily challenge, generated from the date seed on first use and kept for the rest of the day
+// so uploads or index changes during the day do not change the questions
+type DailyQuizzes = (chrono::NaiveDate, Vec<GeneratedQuiz>);
+static DAILY_CHALLENGE: Lazy<Mutex<Option<DailyQuizzes>>> = Lazy::new(|| Mutex::new(None));
+
+// Server-side view of a choice. Never sent to the client: the category is the answer.
+#[derive(Serialize, Deserialize, Clone)]
 struct GeneratedChoice {
-    id: usize,
-    image_url: String,
+    // random per-quiz token, the only handle the client gets for this choice
+    token: String,
     category: String,
+    // image source for /api/quiz_image: a file in public/assets or a procedural /images key
+    file: Option<String>,
+    procedural_key: Option<String>,
+    degrade: Option<imaging::Degrade>,
+}
+
+// What the client sees of a choice: an opaque token and an opaque per-quiz image URL
+#[derive(Serialize)]
+struct PublicChoice {
+    token: String,
+    image_url: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+enum Difficulty {
+    Easy,
+    Normal,
+    Hard,
+}
+
+impl Difficulty {
+    fn parse(s: &str) -> Option<Difficulty> {
+        match s {
+            "easy" => Some(Difficulty::Easy),
+            "normal" => Some(Difficulty::Normal),
+            "hard" => Some(Difficulty::Hard),
+            _ => None,
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            Difficulty::Easy => "easy",
+            Difficulty::Normal => "normal",
+            Difficulty::Hard => "hard",
+        }
+    }
+
+    // number of distinct species shown (capped by the registry size)
+    fn species_per_quiz(self) -> usize {
+        match self {
+            Difficulty::Easy | Difficulty::Normal => 3,
+            Difficulty::Hard => 4,
+        }
+    }
 }
 
-#[derive(Serialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+enum QuizKind {
+    // pick the named species out of several images (/api/generate_quiz)
+    Choice,
+    // one image, answer yes/no to "is this a tanuki?" (/api/tanuki_or_not)
+    TanukiOrNot,
+    // one annotated photo, click where the named feature is (/api/hotspot_quiz)
+    Hotspot,
+    // one photo revealed step by step, name the species (/api/reveal_quiz)
+    Reveal,
+}
+
+impl QuizKind {
+    fn parse(s: &str) -> Option<QuizKind> {
+        match s {
+            "choice" => Some(QuizKind::Choice),
+            "tanuki_or_not" => Some(QuizKind::TanukiOrNot),
+            "hotspot" => Some(QuizKind::Hotspot),
+            "reveal" => Some(QuizKind::Reveal),
+            _ => None,
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            QuizKind::Choice => "choice",
+            QuizKind::TanukiOrNot => "tanuki_or_not",
+            QuizKind::Hotspot => "hotspot",
+            QuizKind::Reveal => "reveal",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
 struct GeneratedQuiz {
+    kind: QuizKind,
+    // seed of the StdRng the quiz was drawn from (see quiz_log)
+    seed: u64,
+    difficulty: Difficulty,
     question: String,
     choices: Vec<GeneratedChoice>,
     answer_category: String,
+    // set when the quiz is a round of a multi-round session
+    session_id: Option<String>,
+    // anonymous player whose mistake history shaped the quiz and is updated by the answer
+    player_id: Option<String>,
+    weights: Option<review::Weights>,
+    // answers arriving later than this after the quiz was issued are rejected
+    time_limit_ms: Option<u64>,
+    // the feature to click in a hotspot quiz
+    #[serde(default)]
+    hotspot: Option<hotspot::Target>,
+    // the frame shown and the options of a reveal quiz
+    #[serde(default)]
+    reveal: Option<reveal::Progress>,
 }
 
 // Response returned to client when creating a quiz (no answer included)
@@ -98,7 +295,185 @@ struct GeneratedQuiz {
 struct GeneratedQuizResponse {
     id: String,
     question: String,
-    choices: Vec<GeneratedChoice>,
+    choices: Vec<PublicChoice>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    session_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    round: Option<usize>,
+    // the clock starts when this response is generated; the server does the timing
+    #[serde(skip_serializing_if = "Option::is_none")]
+    time_limit_ms: Option<u64>,
+}
+
+// Response for the yes/no mode: a single opaque image URL, nothing that names the species
+#[derive(Serialize)]
+struct TanukiOrNotResponse {
+    id: String,
+    question: String,
+    image_url: String,
+}
+
+#[derive(Deserialize)]
+struct TanukiOrNotSubmit {
+    quiz_id: String,
+    // true = "yes, this is a tanuki"
+    answer: bool,
+}
+
+// Response for the hotspot mode: the photo and the feature to click
+#[derive(Serialize)]
+struct HotspotQuizResponse {
+    id: String,
+    question: String,
+    feature: String,
+    image_url: String,
+}
+
+#[derive(Deserialize)]
+struct HotspotSubmit {
+    quiz_id: String,
+    // the click as fractions of the displayed image width and height, from the top left
+    x: f64,
+    y: f64,
+}
+
+#[derive(Serialize)]
+struct RevealOption {
+    key: String,
+    label: String,
+}
+
+// Response for the reveal mode, after generation and after every step; the id changes with each step
+#[derive(Serialize)]
+struct RevealQuizResponse {
+    id: String,
+    question: String,
+    options: Vec<RevealOption>,
+    // 1-based frame number out of `steps`
+    step: usize,
+    steps: usize,
+    // what a correct answer is worth now
+    points: u32,
+    image_url: String,
+}
+
+#[derive(Deserialize)]
+struct RevealNext {
+    quiz_id: String,
+}
+
+#[derive(Deserialize)]
+struct RevealSubmit {
+    quiz_id: String,
+    // species key from `options`
+    answer: String,
+}
+
+// a question of a fixed set: the daily challenge or a classroom quiz set
+#[derive(Serialize)]
+struct FixedQuestion {
+    index: usize,
+    question: String,
+    choices: Vec<PublicChoice>,
+}
+
+#[derive(Serialize)]
+struct DailyChallengeResponse {
+    date: chrono::NaiveDate,
+    rounds: usize,
+    questions: Vec<FixedQuestion>,
+    // whether the player_id passed in the query has already submitted today
+    submitted: bool,
+}
+
+#[derive(Deserialize)]
+struct DailySubmit {
+    // the day the answers are for; submissions after midnight are rejected
+    date: chrono::NaiveDate,
+    player_id: String,
+    nickname: String,
+    // one choice token per question, in question order
+    answers: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct DailySubmitResult {
+    date: chrono::NaiveDate,
+    score: usize,
+    rounds: usize,
+    time_taken_ms: Option<u64>,
+}
+
+// one question of a past daily challenge, from GET /api/daily/answers
+#[derive(Serialize)]
+struct DailyAnswer {
+    index: usize,
+    // `<date>#<n>`, numbered from 1
+    quiz_id: String,
+    answer: String,
+    answer_label: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    explanation: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    info_url: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct QuizSetCreate {
+    title: String,
+    questions: Vec<classroom::QuestionSpec>,
+}
+
+// fields left out stay as they are
+#[derive(Deserialize)]
+struct QuizSetUpdate {
+    title: Option<String>,
+    questions: Option<Vec<classroom::QuestionSpec>>,
+    open: Option<bool>,
+}
+
+#[derive(Serialize)]
+struct ClassSetResponse {
+    code: String,
+    title: String,
+    rounds: usize,
+    questions: Vec<FixedQuestion>,
+    // whether the player_id passed in the query has already submitted this set
+    submitted: bool,
+}
+
+#[derive(Deserialize)]
+struct ClassSubmit {
+    player_id: String,
+    nickname: String,
+    // one choice token per question, in question order
+    answers: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct ClassSubmitResult {
+    code: String,
+    rounds: usize,
+    // the score and the answers stay hidden until the teacher closes the set, so students cannot
+    // pass the answers around while others are still answering
+    closed: bool,
+    score: Option<usize>,
+    results: Option<Vec<QuizResult>>,
+}
+
+#[derive(Deserialize, Default)]
+struct SessionCreate {
+    rounds: Option<usize>,
+    // makes every round timed and enables speed bonuses
+    time_limit_secs: Option<u64>,
+}
+
+#[derive(Serialize)]
+struct SessionCreated {
+    session_id: String,
+    rounds: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    time_limit_secs: Option<u64>,
 }
 
 #[derive(Deserialize)]
@@ -114,7 +489,6 @@ struct AdminUploadResult {
     ok: bool,
     saved_filename: Option<String>,
     thumb_filename: Option<String>,
-    message: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -128,6 +502,12 @@ struct AssetIndexEntry {
     source: Option<String>,
     license: Option<String>,
     uploader: Option<String>,
+    // marked by an admin as a textbook example; only these are used for easy quizzes
+    #[serde(default)]
+    clear_example: bool,
+    // feature regions for hotspot quizzes (see hotspot)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    annotations: Vec<hotspot::Annotation>,
 }
 
 fn index_path() -> PathBuf { PathBuf::from("public").join("assets").join("index.json") }
@@ -144,212 +524,1256 @@ fn load_index() -> Vec<AssetIndexEntry> {
     vec![]
 }
 
+// write then rename, so readers and a crash never see half an index
 fn save_index(entries: &Vec<AssetIndexEntry>) {
     let p = index_path();
-    if let Ok(mut f) = File::create(&p) {
-        let _ = f.write_all(serde_json::to_string_pretty(entries).unwrap_or_else(|_| "[]".to_string()).as_bytes());
+    let tmp = p.with_extension("json.tmp");
+    if std::fs::write(&tmp, serde_json::to_string_pretty(entries).unwrap_or_else(|_| "[]".to_string())).is_ok() {
+        let _ = std::fs::rename(&tmp, &p);
+    }
+}
+
+// index.json as last parsed, with the modification time it had then
+struct CachedIndex {
+    modified: std::time::SystemTime,
+    entries: std::sync::Arc<Vec<AssetIndexEntry>>,
+}
+
+static INDEX_CACHE: Lazy<Mutex<Option<CachedIndex>>> = Lazy::new(|| Mutex::new(None));
+
+// The index for readers that look at it on every quiz or request. It is parsed again only when
+// index.json has changed, whether through update_index or by hand.
+fn cached_index() -> std::sync::Arc<Vec<AssetIndexEntry>> {
+    let modified = std::fs::metadata(index_path()).and_then(|m| m.modified()).ok();
+    let mut cache = INDEX_CACHE.lock();
+    if let (Some(cached), Some(modified)) = (cache.as_ref(), modified) {
+        if cached.modified == modified { return cached.entries.clone(); }
+    }
+    let entries = std::sync::Arc::new(load_index());
+    *cache = modified.map(|modified| CachedIndex { modified, entries: entries.clone() });
+    entries
+}
+
+// serializes read-modify-write cycles on index.json (uploads and admin edits)
+static INDEX_LOCK: Lazy<Mutex<()>> = Lazy::new(|| Mutex::new(()));
+
+fn update_index<T>(f: impl FnOnce(&mut Vec<AssetIndexEntry>) -> T) -> T {
+    let _guard = INDEX_LOCK.lock();
+    let mut idx = load_index();
+    let out = f(&mut idx);
+    save_index(&idx);
+    out
+}
+
+// index entry for a photo that is already in public/assets but not yet indexed
+fn new_index_entry(filename: &str) -> AssetIndexEntry {
+    let target = PathBuf::from("public").join("assets").join(filename);
+    AssetIndexEntry {
+        filename: filename.to_string(),
+        size: target.metadata().map(|m| m.len()).unwrap_or(0),
+        thumb: PathBuf::from("public").join("assets").join("thumbs").join(filename).exists(),
+        phash: image::open(&target).ok().map(|img| compute_ahash(&img)),
+        uploaded_at: chrono::Utc::now().to_rfc3339(),
+        source: None,
+        license: None,
+        uploader: None,
+        clear_example: false,
+        annotations: Vec::new(),
+    }
+}
+
+// What a pick-one answer says about each photo shown; `picked` is None for an unknown token.
+// Procedural images are skipped.
+fn choice_outcomes(choices: &[GeneratedChoice], answer_category: &str, picked: Option<&GeneratedChoice>) -> Vec<(String, ImageOutcome)> {
+    let correct = picked.is_some_and(|p| p.category == answer_category);
+    let wrong = picked.filter(|_| !correct);
+    choices.iter().filter_map(|c| {
+        let file = c.file.clone()?;
+        let wrong_pick = wrong.filter(|_| c.category == answer_category).map(|p| p.category.clone());
+        let mistaken_for = wrong.filter(|p| p.token == c.token).map(|_| answer_category.to_string());
+        Some((file, ImageOutcome { correct, wrong_pick, mistaken_for }))
+    }).collect()
+}
+
+// counted in memory; the cleanup task writes them out (see image_stats)
+fn record_image_outcomes(outcomes: Vec<(String, ImageOutcome)>) {
+    image_stats::record(outcomes);
+}
+
+fn compute_ahash(img: &DynamicImage) -> String {
+    // average hash (8x8 -> 64 bits)
+    let small = img.resize_exact(8, 8, image::imageops::FilterType::Nearest).to_luma8();
+    let mut sum: u32 = 0;
+    for p in small.pixels() { sum += p[0] as u32; }
+    let avg = (sum / 64) as u8;
+    let mut bits: u64 = 0;
+    for (i, p) in small.pixels().enumerate() {
+        if p[0] >= avg { bits |= 1u64 << i; }
+    }
+    format!("{:016x}", bits)
+}
+
+fn hamming_hex(a_hex: &str, b_hex: &str) -> Option<u32> {
+    if a_hex.len() != 16 || b_hex.len() != 16 { return None; }
+    let a = u64::from_str_radix(a_hex, 16).ok()?;
+    let b = u64::from_str_radix(b_hex, 16).ok()?;
+    Some((a ^ b).count_ones())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::DynamicImage;
+    use image::RgbaImage;
+
+    #[test]
+    fn test_ahash_and_hamming_same_image() {
+        // create a small solid image
+        let mut im = RgbaImage::new(16, 16);
+        for p in im.pixels_mut() { *p = image::Rgba([200, 180, 160, 255]); }
+        let di = DynamicImage::ImageRgba8(im.clone());
+        let h1 = compute_ahash(&di);
+        let h2 = compute_ahash(&di);
+        assert_eq!(h1, h2);
+        let dist = hamming_hex(&h1, &h2).unwrap();
+        assert_eq!(dist, 0);
+    }
+
+    #[test]
+    fn test_difficulty_filters_clear_examples() {
+        let files = || vec!["tanuki1.jpg".to_string(), "tanuki2.jpg".to_string()];
+        let clear = vec!["tanuki1.jpg".to_string()];
+        let mut easy = files();
+        assert!(filter_by_difficulty(&mut easy, Difficulty::Easy, &clear));
+        assert_eq!(easy, clear);
+        let mut hard = files();
+        assert!(filter_by_difficulty(&mut hard, Difficulty::Hard, &clear));
+        assert_eq!(hard, vec!["tanuki2.jpg".to_string()]);
+        // a species with only clear examples still has a photo in hard quizzes
+        let mut only_clear = clear.clone();
+        assert!(filter_by_difficulty(&mut only_clear, Difficulty::Hard, &clear));
+        assert_eq!(only_clear, clear);
+        assert!(!filter_by_difficulty(&mut vec!["tanuki2.jpg".to_string()], Difficulty::Easy, &clear));
+    }
+
+    #[test]
+    fn test_ahash_and_hamming_different_images() {
+        let mut a = RgbaImage::new(16, 16);
+        for p in a.pixels_mut() { *p = image::Rgba([10, 10, 10, 255]); }
+        // make b with left half dark and right half bright so phash should differ
+        let mut b = RgbaImage::new(16, 16);
+        for y in 0..16 {
+            for x in 0..16 {
+                let v = if x < 8 { 20 } else { 230 };
+                b.put_pixel(x, y, image::Rgba([v, v, v, 255]));
+            }
+        }
+        let ha = compute_ahash(&DynamicImage::ImageRgba8(a));
+        let hb = compute_ahash(&DynamicImage::ImageRgba8(b));
+        let dist = hamming_hex(&ha, &hb).unwrap();
+        assert!(dist > 0, "expected different images to have non-zero Hamming distance, got 0 (ha={} hb={})", ha, hb);
+    }
+
+    #[test]
+    fn test_seeded_pick_choices_is_reproducible() {
+        let pick = |seed: u64| {
+            let (choices, target) = pick_choices(Difficulty::Hard, &mut StdRng::seed_from_u64(seed), None).unwrap();
+            (choices.iter().map(|c| (c.token.clone(), c.category.clone(), c.file.clone(), c.degrade.clone())).collect::<Vec<_>>(), target)
+        };
+        assert_eq!(pick(42), pick(42));
+        assert_ne!(pick(42).0, pick(43).0);
+    }
+
+    #[test]
+    fn test_weighted_pick_focuses_on_weak_species() {
+        let mut w = review::Weights::default();
+        w.species.insert("anaguma".to_string(), 1e9);
+        w.partners.insert("anaguma".to_string(), [("hakubishin".to_string(), 1e9)].into_iter().collect());
+        for seed in 0..20 {
+            let (choices, target) = pick_choices(Difficulty::Normal, &mut StdRng::seed_from_u64(seed), Some(&w)).unwrap();
+            assert_eq!(target, "anaguma");
+            assert!(choices.iter().any(|c| c.category == "hakubishin"));
+        }
+    }
+
+    #[test]
+    fn test_replay_quiz_matches_logged_tokens() {
+        let choice = pick_tanuki_or_not(Difficulty::Hard, 0.3, &mut StdRng::seed_from_u64(7)).unwrap();
+        let record = quiz_log::QuizRecord {
+            id: "q".to_string(),
+            kind: "tanuki_or_not".to_string(),
+            seed: 7,
+            difficulty: "hard".to_string(),
+            tanuki_ratio: Some(0.3),
+            answer_category: choice.category.clone(),
+            tokens: vec![choice.token.clone()],
+            session_id: None,
+            weights: None,
+            feature: None,
+            style: None,
+            step: None,
+            created_at: chrono::Utc::now(),
+        };
+        let (choices, answer, hotspot) = replay_quiz(&record).unwrap();
+        assert_eq!(hotspot, None);
+        assert_eq!(answer, record.answer_category);
+        assert_eq!(choices[0].token, choice.token);
+        assert_eq!(choices[0].degrade, choice.degrade);
+        assert!(replay_quiz(&quiz_log::QuizRecord { kind: "unknown".to_string(), ..record }).is_none());
+    }
+
+    #[test]
+    fn test_replay_reveal_rebuilds_later_frames() {
+        let mut rng = StdRng::seed_from_u64(11);
+        let (choice, _) = pick_reveal(&mut rng).unwrap();
+        let crop = reveal::random_crop(&mut rng);
+        let record = quiz_log::QuizRecord {
+            id: "r".to_string(),
+            kind: "reveal".to_string(),
+            seed: 11,
+            difficulty: "normal".to_string(),
+            tanuki_ratio: None,
+            answer_category: choice.category.clone(),
+            tokens: vec![reveal::step_token(11, 2)],
+            session_id: None,
+            weights: None,
+            feature: None,
+            style: Some(reveal::Style::Pixelate),
+            step: Some(2),
+            created_at: chrono::Utc::now(),
+        };
+        let (choices, _, _) = replay_quiz(&record).unwrap();
+        assert_eq!(choices[0].token, record.tokens[0]);
+        assert_eq!(choices[0].file, choice.file);
+        assert_eq!(choices[0].degrade, Some(reveal::frame(reveal::Style::Pixelate, 2, crop)));
+        // the first frame keeps the token the quiz was generated with
+        let (first, _, _) = replay_quiz(&quiz_log::QuizRecord { step: Some(0), ..record }).unwrap();
+        assert_eq!(first[0].token, choice.token);
+    }
+
+    #[tokio::test]
+    async fn test_quiz_photo_at_requested_size_without_admin() {
+        let state = AppState { quizzes: quiz_store::Quizzes::new(std::sync::Arc::new(quiz_store::MemoryStore::default())) };
+        let (choices, answer) = pick_choices(Difficulty::Normal, &mut StdRng::seed_from_u64(3), None).unwrap();
+        let choice = choices.iter().find(|c| c.file.is_some() && c.degrade.is_none()).unwrap().clone();
+        let quiz = GeneratedQuiz { kind: QuizKind::Choice, seed: 3, difficulty: Difficulty::Normal, question: "q".to_string(), choices: choices.clone(), answer_category: answer, session_id: None, player_id: None, weights: None, time_limit_ms: None, hotspot: None, reveal: None };
+        let id = state.quizzes.insert(StoredQuiz::new("sized", quiz, None)).await;
+        let fetch = |q: &[(&str, &str)]| {
+            let q = q.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+            quiz_image(State(state.clone()), Locale("en"), ApiPath((id.clone(), choice.token.clone())), ApiQuery(q))
+        };
+        let res = fetch(&[("w", "96")]).await.unwrap().into_response();
+        assert_eq!(res.status(), axum::http::StatusCode::OK);
+        let bytes = axum::body::to_bytes(res.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(image::load_from_memory(&bytes).unwrap().width(), 96);
+        let res = fetch(&[("w", "0")]).await.err().unwrap().into_response();
+        assert_eq!(res.status(), axum::http::StatusCode::BAD_REQUEST);
+    }
+
+    #[test]
+    fn test_choice_outcomes_blame_target_and_picked_photos() {
+        let choice = |token: &str, category: &str, file: Option<&str>| GeneratedChoice { token: token.to_string(), category: category.to_string(), file: file.map(|f| f.to_string()), procedural_key: None, degrade: None };
+        let choices = vec![choice("a", "tanuki", Some("tanuki1.jpg")), choice("b", "hakubishin", Some("hakubishin1.jpg")), choice("c", "anaguma", None)];
+        let wrong = choice_outcomes(&choices, "tanuki", Some(&choices[1]));
+        // the procedural choice has no photo to blame
+        assert_eq!(wrong.len(), 2);
+        assert_eq!(wrong[0], ("tanuki1.jpg".to_string(), ImageOutcome { correct: false, wrong_pick: Some("hakubishin".to_string()), mistaken_for: None }));
+        assert_eq!(wrong[1], ("hakubishin1.jpg".to_string(), ImageOutcome { correct: false, wrong_pick: None, mistaken_for: Some("tanuki".to_string()) }));
+        assert!(choice_outcomes(&choices, "tanuki", Some(&choices[0])).iter().all(|(_, o)| o.correct && o.wrong_pick.is_none() && o.mistaken_for.is_none()));
+        assert!(choice_outcomes(&choices, "tanuki", None).iter().all(|(_, o)| !o.correct && o.wrong_pick.is_none()));
+
+        let mut stats = ImageStats::default();
+        for (_, o) in wrong.iter().chain(choice_outcomes(&choices, "tanuki", Some(&choices[0])).iter()).filter(|(f, _)| f == "tanuki1.jpg") { stats.record(o); }
+        assert_eq!((stats.shown, stats.correct), (2, 1));
+        assert_eq!(stats.wrong_picks["hakubishin"], 1);
+    }
+}
+
+// similar search endpoint: ?filename=<name>&token=<token>&max_hamming=10
+async fn admin_similar(headers: HeaderMap, ApiQuery(q): ApiQuery<StdHashMap<String, String>>) -> Result<Json<Vec<AdminListEntry>>, ApiError> {
+    // prefer Authorization: Bearer <token> header, fallback to ?token=
+    require_admin(&headers, Some(&q))?;
+    let filename = q.get("filename").cloned().ok_or_else(|| ApiError::bad_request("missing filename"))?;
+    let max_hamming: u32 = q.get("max_hamming").and_then(|s| s.parse().ok()).unwrap_or(10);
+    let idx = load_index();
+    let base = match idx.iter().find(|e| e.filename == filename) { Some(e) => e.phash.clone().unwrap_or_default(), None => return Err(ApiError::not_found(format!("{} is not in the asset index", filename))) };
+    let mut out = Vec::new();
+    for e in idx.iter() {
+        if e.filename == filename { continue; }
+        if let (Some(a), Some(b)) = (Some(base.as_str()), e.phash.as_deref()) {
+            if let Some(dist) = hamming_hex(a, b) {
+                if dist <= max_hamming { out.push(AdminListEntry { filename: e.filename.clone(), size: e.size, thumb: e.thumb, uploaded_at: Some(e.uploaded_at.clone()), uploader: e.uploader.clone(), clear_example: e.clear_example, stats: image_stats::get(&e.filename) }); }
+            }
+        }
+    }
+    Ok(Json(out))
+}
+
+// all local photos in public/assets whose filename starts with the category key, sorted by name
+fn asset_candidates(cat_key: &str) -> Vec<String> {
+    let static_dir = PathBuf::from("public").join("assets");
+    let mut candidates: Vec<String> = Vec::new();
+    if let Ok(entries) = std::fs::read_dir(&static_dir) {
+        for entry in entries.flatten() {
+            if let Some(name_os) = entry.file_name().to_str() {
+                let name = name_os.to_string();
+                let lower = name.to_lowercase();
+                if lower.starts_with(cat_key) && (lower.ends_with(".jpg") || lower.ends_with(".jpeg") || lower.ends_with(".png")) {
+                    candidates.push(name);
+                }
+            }
+        }
+    }
+    candidates.sort();
+    candidates
+}
+
+fn clear_example_files() -> Vec<String> {
+    cached_index().iter().filter(|e| e.clear_example).map(|e| e.filename.clone()).collect()
+}
+
+async fn generate_quiz(State(state): State<AppState>, ConnectInfo(peer): ConnectInfo<SocketAddr>, Locale(lang): Locale, headers: HeaderMap, ApiQuery(q): ApiQuery<StdHashMap<String, String>>) -> Result<Json<GeneratedQuizResponse>, ApiError> {
+    let client = client_key(&headers, peer);
+    check_open_quizzes(&state, &client, lang).await?;
+    let difficulty = match q.get("difficulty") {
+        Some(d) => Difficulty::parse(d).ok_or_else(|| ApiError::bad_request(i18n::t(lang, "error.difficulty_invalid")))?,
+        None => Difficulty::Normal,
+    };
+    let seed = quiz_seed(&headers, &q, lang)?;
+    // ?player_id=<id> weights the quiz toward that player's past mistakes
+    let player_id = q.get("player_id").cloned();
+    if let Some(pid) = &player_id {
+        if !daily::valid_player_id(pid) { return Err(ApiError::bad_request(i18n::t(lang, "error.player_id_invalid"))); }
+    }
+    let weights = match player_id.clone() {
+        // the history is a file read; keep it off the async workers
+        Some(pid) => tokio::task::spawn_blocking(move || review::weights_for(&pid)).await.ok().flatten(),
+        None => None,
+    };
+    // generate id up front: sessions and the quiz log know the quiz by it
+    let id = Uuid::new_v4().to_string();
+    let (choices, target_cat) = pick_choices(difficulty, &mut StdRng::seed_from_u64(seed), weights.as_ref())
+        // only possible for easy quizzes before admins have marked clear examples
+        .ok_or_else(|| ApiError::unavailable(i18n::t(lang, "error.not_enough_clear_examples")))?;
+    let question = choice_question(&target_cat, lang);
+
+    // ?time_limit=<secs> times a single quiz; session rounds always use the session's limit
+    let time_limit = match q.get("time_limit") {
+        Some(s) => Some(s.parse().ok().and_then(valid_time_limit).ok_or_else(|| ApiError::bad_request(i18n::tf(lang, "error.time_limit_invalid", &[("max", &session::MAX_TIME_LIMIT_SECS.to_string())])))?),
+        None => None,
+    };
+
+    // session rounds are reserved before the quiz is stored
+    let session_id = q.get("session_id").cloned();
+    let (round, time_limit) = match &session_id {
+        Some(sid) => match session::start_round(sid, &id, &target_cat) {
+            Ok((n, limit)) => (Some(n), limit),
+            Err(session::RoundError::NotFound) => return Err(ApiError::not_found(i18n::t(lang, "error.session_not_found"))),
+            Err(session::RoundError::Complete) => return Err(ApiError::conflict(i18n::t(lang, "error.session_rounds_issued"))),
+        },
+        None => (None, time_limit),
+    };
+    let time_limit_ms = time_limit.map(|l| l.as_millis() as u64);
+    let tokens: Vec<String> = choices.iter().map(|c| c.token.clone()).collect();
+    let quiz = GeneratedQuiz { kind: QuizKind::Choice, seed, difficulty, question: question.clone(), choices, answer_category: target_cat.clone(), session_id: session_id.clone(), player_id, weights, time_limit_ms, hotspot: None, reveal: None };
+    log_quiz(&id, &quiz, None);
+    // the id clients hold; choice images are served under /api/quiz_image/<id>/<token>
+    let id = state.quizzes.insert(StoredQuiz::new(&id, quiz, Some(client))).await;
+    let public_choices = tokens.into_iter().map(|token| PublicChoice { image_url: format!("/api/quiz_image/{}/{}", id, token), token }).collect();
+
+    Ok(Json(GeneratedQuizResponse { id, question, choices: public_choices, session_id, round, time_limit_ms }))
+}
+
+// whole seconds, 1 to MAX_TIME_LIMIT_SECS
+fn valid_time_limit(secs: u64) -> Option<Duration> {
+    Some(Duration::from_secs(secs)).filter(|_| (1..=session::MAX_TIME_LIMIT_SECS).contains(&secs))
+}
+
+// optional time_limit_secs from a JSON body (sessions, rooms)
+fn time_limit_from(secs: Option<u64>, lang: &str) -> Result<Option<Duration>, ApiError> {
+    match secs {
+        Some(secs) => valid_time_limit(secs).map(Some).ok_or_else(|| ApiError::bad_request(i18n::tf(lang, "error.time_limit_invalid", &[("max", &session::MAX_TIME_LIMIT_SECS.to_string())]))),
+        None => Ok(None),
+    }
+}
+
+/// Extra time allowed past a quiz's limit for the answer to reach the server, QUIZ_DEADLINE_GRACE_MS (default 500).
+fn deadline_grace() -> Duration {
+    Duration::from_millis(env::var("QUIZ_DEADLINE_GRACE_MS").ok().and_then(|s| s.parse().ok()).unwrap_or(500))
+}
+
+// a timed quiz is late once its limit plus the grace period has passed since it was issued
+fn past_deadline(quiz: &GeneratedQuiz, elapsed: Duration) -> bool {
+    quiz.time_limit_ms.is_some_and(|ms| elapsed > Duration::from_millis(ms) + deadline_grace())
+}
+
+// ?seed=<u64> reproduces a quiz for bug reports and tests. Admin only: whoever knows the seed
+// can work out the answer. Without it every quiz gets a fresh random seed.
+fn quiz_seed(headers: &HeaderMap, q: &StdHashMap<String, String>, lang: &str) -> Result<u64, ApiError> {
+    match q.get("seed") {
+        Some(s) => {
+            require_admin(headers, Some(q))?;
+            s.parse().map_err(|_| ApiError::bad_request(i18n::t(lang, "error.seed_invalid")))
+        }
+        None => Ok(quiz_log::random_seed()),
+    }
+}
+
+// a failed log write must not stop the quiz; the quiz just cannot be regenerated later.
+// The append runs on the blocking pool so a slow disk never holds up the request.
+fn log_quiz(id: &str, quiz: &GeneratedQuiz, tanuki_ratio: Option<f64>) {
+    let record = quiz_log::QuizRecord {
+        id: id.to_string(),
+        kind: quiz.kind.as_str().to_string(),
+        seed: quiz.seed,
+        difficulty: quiz.difficulty.as_str().to_string(),
+        tanuki_ratio,
+        answer_category: quiz.answer_category.clone(),
+        tokens: quiz.choices.iter().map(|c| c.token.clone()).collect(),
+        session_id: quiz.session_id.clone(),
+        weights: quiz.weights.clone(),
+        feature: quiz.hotspot.as_ref().map(|t| t.feature.clone()),
+        style: quiz.reveal.as_ref().map(|p| p.style),
+        step: quiz.reveal.as_ref().map(|p| p.step),
+        created_at: chrono::Utc::now(),
+    };
+    tokio::task::spawn_blocking(move || {
+        if let Err(e) = quiz_log::append(&record) { eprintln!("quiz log write failed: {}", e); }
+    });
+}
+
+// Species, images and target for one pick-one quiz. All randomness comes from `rng`, so a
+// seeded RNG gives the same quiz as long as the registry and public/assets are unchanged.
+// None when fewer than two images are usable (easy quizzes before clear examples are marked).
+// With `weights` from a player's mistake history the target, its companions and the photos
+// are drawn by weight instead of uniformly.
+fn pick_choices(difficulty: Difficulty, rng: &mut impl Rng, weights: Option<&review::Weights>) -> Option<(Vec<GeneratedChoice>, String)> {
+    let mut choices: Vec<GeneratedChoice> = Vec::new();
+    let clear = if difficulty == Difficulty::Normal { None } else { Some(clear_example_files()) };
+    // categories come from the species registry; local photos are public/assets/<key>*.jpg
+    let all_species = species::keys();
+    let (categories, focus): (Vec<&String>, Option<&String>) = match weights {
+        Some(w) => {
+            // the species to review becomes the target, shown next to the species it gets confused with
+            let target = all_species.choose_weighted(rng, |k| w.species_weight(k)).ok()?;
+            let others: Vec<&String> = all_species.iter().filter(|k| *k != target).collect();
+            let mut cats: Vec<&String> = others.choose_multiple_weighted(rng, difficulty.species_per_quiz().saturating_sub(1), |k| w.partner_weight(target, k)).ok()?.copied().collect();
+            cats.push(target);
+            (cats, Some(target))
+        }
+        None => (all_species.choose_multiple(rng, difficulty.species_per_quiz()).collect(), None),
+    };
+    // hard quizzes show two different photos of one randomly chosen species
+    let doubled = if difficulty == Difficulty::Hard { categories.choose(rng).copied() } else { None };
+    for cat_key in categories.iter().copied() {
+        // look for any matching local files in public/assets (support any number)
+        let mut candidates = asset_candidates(cat_key);
+        if let Some(clear) = &clear {
+            // easy quizzes never fall back to unvetted images
+            if !filter_by_difficulty(&mut candidates, difficulty, clear) { continue; }
+        }
+        let picks = if doubled == Some(cat_key) { 2 } else { 1 };
+        let mut picked: Vec<String> = match weights {
+            Some(w) => candidates.choose_multiple_weighted(rng, picks, |f| w.image_weight(f)).ok()?.cloned().collect(),
+            None => candidates.choose_multiple(rng, picks).cloned().collect(),
+        };
+        if picked.is_empty() { picked.push(String::new()); }
+        for file in picked {
+            choices.push(build_choice(cat_key, file, difficulty, rng));
+        }
+    }
+    if choices.len() < 2 { return None; }
+
+    // Shuffle so order isn't predictable
+    choices.shuffle(rng);
+
+    // select target category (an easy quiz may have had to drop the focus species)
+    let target_cat = match focus {
+        Some(t) if choices.iter().any(|c| &c.category == t) => t.clone(),
+        _ => choices.choose(rng)?.category.clone(),
+    };
+    Some((choices, target_cat))
+}
+
+fn choice_question(target_cat: &str, lang: &str) -> String {
+    let label = species::get(target_cat).map(|sp| sp.name(lang).to_string()).unwrap_or_else(|| i18n::t(lang, "question.fallback_label"));
+    i18n::tf(lang, "question.which", &[("label", &label)])
+}
+
+// Easy quizzes keep only clear examples; false when that leaves nothing. Hard quizzes drop
+// clear examples as long as the species has other photos.
+fn filter_by_difficulty(candidates: &mut Vec<String>, difficulty: Difficulty, clear: &[String]) -> bool {
+    match difficulty {
+        Difficulty::Easy => {
+            candidates.retain(|c| clear.contains(c));
+            !candidates.is_empty()
+        }
+        Difficulty::Hard => {
+            if candidates.iter().any(|c| !clear.contains(c)) { candidates.retain(|c| !clear.contains(c)); }
+            true
+        }
+        Difficulty::Normal => true,
     }
 }
 
-fn compute_ahash(img: &DynamicImage) -> String {
-    // average hash (8x8 -> 64 bits)
-    let small = img.resize_exact(8, 8, image::imageops::FilterType::Nearest).to_luma8();
-    let mut sum: u32 = 0;
-    for p in small.pixels() { sum += p[0] as u32; }
-    let avg = (sum / 64) as u8;
-    let mut bits: u64 = 0;
-    for (i, p) in small.pixels().enumerate() {
-        if p[0] >= avg { bits |= 1u64 << i; }
-    }
-    format!("{:016x}", bits)
+// `file` is empty when the category has no local photo; the procedural image is used instead
+fn build_choice(cat_key: &str, file: String, difficulty: Difficulty, rng: &mut impl Rng) -> GeneratedChoice {
+    let token = format!("{:016x}", rng.gen::<u64>());
+    let (file, procedural_key) = if file.is_empty() { (None, Some(cat_key.to_string())) } else { (Some(file), None) };
+    // hard quizzes get a cropped, grayscale, low-resolution variant rendered server-side
+    let degrade = if difficulty == Difficulty::Hard { Some(imaging::Degrade::random(rng)) } else { None };
+    GeneratedChoice { token, category: cat_key.to_string(), file, procedural_key, degrade }
+}
+
+// yes/no mode: one image, a tanuki with probability TANUKI_RATIO, otherwise a look-alike
+async fn generate_tanuki_or_not(State(state): State<AppState>, ConnectInfo(peer): ConnectInfo<SocketAddr>, Locale(lang): Locale, headers: HeaderMap, ApiQuery(q): ApiQuery<StdHashMap<String, String>>) -> Result<Json<TanukiOrNotResponse>, ApiError> {
+    let client = client_key(&headers, peer);
+    check_open_quizzes(&state, &client, lang).await?;
+    let difficulty = match q.get("difficulty") {
+        Some(d) => Difficulty::parse(d).ok_or_else(|| ApiError::bad_request(i18n::t(lang, "error.difficulty_invalid")))?,
+        None => Difficulty::Normal,
+    };
+    let seed = quiz_seed(&headers, &q, lang)?;
+    let id = Uuid::new_v4().to_string();
+    let ratio = tanuki_or_not::tanuki_ratio();
+    let choice = pick_tanuki_or_not(difficulty, ratio, &mut StdRng::seed_from_u64(seed))
+        .ok_or_else(|| ApiError::unavailable(i18n::t(lang, "error.not_enough_clear_examples")))?;
+    let shown = choice.category.clone();
+    let label = species::get(tanuki_or_not::TARGET).map(|sp| sp.name(lang).to_string()).unwrap_or_else(|| tanuki_or_not::TARGET.to_string());
+    let question = i18n::tf(lang, "question.is_target", &[("label", &label)]);
+    let token = choice.token.clone();
+    let quiz = GeneratedQuiz { kind: QuizKind::TanukiOrNot, seed, difficulty, question: question.clone(), choices: vec![choice], answer_category: shown, session_id: None, player_id: None, weights: None, time_limit_ms: None, hotspot: None, reveal: None };
+    log_quiz(&id, &quiz, Some(ratio));
+    let id = state.quizzes.insert(StoredQuiz::new(&id, quiz, Some(client))).await;
+    let image_url = format!("/api/quiz_image/{}/{}", id, token);
+    Ok(Json(TanukiOrNotResponse { id, question, image_url }))
+}
+
+// The single image of a yes/no quiz; its category is the species shown.
+// None for easy quizzes when the picked species has no clear example yet.
+fn pick_tanuki_or_not(difficulty: Difficulty, ratio: f64, rng: &mut impl Rng) -> Option<GeneratedChoice> {
+    let shown = tanuki_or_not::pick_species(&species::keys(), ratio, rng)?;
+    let mut candidates = asset_candidates(&shown);
+    if difficulty != Difficulty::Normal && !filter_by_difficulty(&mut candidates, difficulty, &clear_example_files()) { return None; }
+    let file = candidates.choose(rng).cloned().unwrap_or_default();
+    Some(build_choice(&shown, file, difficulty, rng))
+}
+
+async fn submit_tanuki_or_not(State(state): State<AppState>, Locale(lang): Locale, ApiJson(payload): ApiJson<TanukiOrNotSubmit>) -> Result<Json<QuizResult>, ApiError> {
+    let answer = payload.answer.to_string();
+    if let Some(receipt) = state.quizzes.receipt(&payload.quiz_id, QuizKind::TanukiOrNot).await { return replay_receipt(receipt, &answer, lang); }
+    let found = matches!(state.quizzes.get(&payload.quiz_id).await, Some(stored) if stored.quiz.kind == QuizKind::TanukiOrNot);
+    let removed = if found { state.quizzes.take(&payload.quiz_id, Some(Receipt::pending(QuizKind::TanukiOrNot, &answer))).await } else { None };
+    let Some(StoredQuiz { id: quiz_id, quiz, .. }) = removed else {
+        // a concurrent duplicate took it first; wait for its result
+        if found { if let Some(receipt) = state.quizzes.raced_receipt(&payload.quiz_id, QuizKind::TanukiOrNot).await { return replay_receipt(receipt, &answer, lang); } }
+        return Ok(Json(QuizResult::unknown(lang)));
+    };
+    let shown = quiz.answer_category;
+    let correct = tanuki_or_not::is_correct(&shown, payload.answer);
+    tanuki_or_not::record(&shown, payload.answer);
+    // a look-alike called a tanuki was mistaken for one
+    let mistaken_for = if payload.answer && !correct { Some(tanuki_or_not::TARGET.to_string()) } else { None };
+    record_image_outcomes(quiz.choices.iter().filter_map(|c| c.file.clone()).map(|f| (f, ImageOutcome { correct, wrong_pick: None, mistaken_for: mistaken_for.clone() })).collect());
+    answer_log::log(vec![AnswerRecord {
+        session: quiz.session_id.clone(),
+        player_id: quiz.player_id.clone(),
+        picked: Some(if payload.answer { "yes" } else { "no" }.to_string()),
+        picked_image: None,
+        correct,
+        ..AnswerRecord::choice("tanuki_or_not", quiz_id, &shown, &quiz.choices, None)
+    }]);
+    let sp = species::get(&shown);
+    let mut result = QuizResult::new(correct, shown.clone(), sp.clone(), lang);
+    result.correct_label = sp.map(|sp| sp.name(lang).to_string());
+    keep_receipt(&state, &payload.quiz_id, QuizKind::TanukiOrNot, &answer, &result).await;
+    Ok(Json(result))
+}
+
+// A random annotated photo and one of its features. The choice carries the photo's species; the
+// photo is never degraded, since cropping would move the regions.
+fn pick_hotspot(rng: &mut impl Rng) -> Option<(GeneratedChoice, hotspot::Target)> {
+    let index = cached_index();
+    let photos: Vec<(&String, String, Vec<hotspot::Target>)> = index.iter()
+        .filter(|e| !e.annotations.is_empty() && PathBuf::from("public").join("assets").join(&e.filename).is_file())
+        .filter_map(|e| {
+            let sp = species::for_image_key(&e.filename)?;
+            Some((&e.filename, sp.key, hotspot::targets(&e.annotations)))
+        })
+        .collect();
+    let (file, category, targets) = photos.choose(rng)?;
+    let target = targets.choose(rng)?.clone();
+    let token = format!("{:016x}", rng.gen::<u64>());
+    Some((GeneratedChoice { token, category: category.clone(), file: Some(file.to_string()), procedural_key: None, degrade: None }, target))
+}
+
+// "feature.<name>" from the locale catalog, or the name itself with spaces
+fn feature_label(feature: &str, lang: &str) -> String {
+    let key = format!("feature.{}", feature);
+    let label = i18n::t(lang, &key);
+    if label == key { feature.replace('_', " ") } else { label }
+}
+
+async fn generate_hotspot_quiz(State(state): State<AppState>, ConnectInfo(peer): ConnectInfo<SocketAddr>, Locale(lang): Locale, headers: HeaderMap, ApiQuery(q): ApiQuery<StdHashMap<String, String>>) -> Result<Json<HotspotQuizResponse>, ApiError> {
+    let client = client_key(&headers, peer);
+    check_open_quizzes(&state, &client, lang).await?;
+    let seed = quiz_seed(&headers, &q, lang)?;
+    let id = Uuid::new_v4().to_string();
+    let (choice, target) = pick_hotspot(&mut StdRng::seed_from_u64(seed))
+        .ok_or_else(|| ApiError::unavailable(i18n::t(lang, "error.no_hotspots")))?;
+    let label = species::get(&choice.category).map(|sp| sp.name(lang).to_string()).unwrap_or_else(|| i18n::t(lang, "question.fallback_label"));
+    let feature = feature_label(&target.feature, lang);
+    let question = i18n::tf(lang, "question.hotspot", &[("feature", &feature), ("label", &label)]);
+    let token = choice.token.clone();
+    let quiz = GeneratedQuiz { kind: QuizKind::Hotspot, seed, difficulty: Difficulty::Normal, question: question.clone(), answer_category: choice.category.clone(), choices: vec![choice], session_id: None, player_id: None, weights: None, time_limit_ms: None, hotspot: Some(target), reveal: None };
+    log_quiz(&id, &quiz, None);
+    let id = state.quizzes.insert(StoredQuiz::new(&id, quiz, Some(client))).await;
+    let image_url = format!("/api/quiz_image/{}/{}", id, token);
+    Ok(Json(HotspotQuizResponse { id, question, feature, image_url }))
+}
+
+async fn submit_hotspot_quiz(State(state): State<AppState>, Locale(lang): Locale, ApiJson(payload): ApiJson<HotspotSubmit>) -> Result<Json<QuizResult>, ApiError> {
+    let click = [payload.x, payload.y];
+    if !hotspot::valid_point(click) { return Err(ApiError::bad_request(i18n::t(lang, "error.click_invalid"))); }
+    let answer = format!("{},{}", payload.x, payload.y);
+    if let Some(receipt) = state.quizzes.receipt(&payload.quiz_id, QuizKind::Hotspot).await { return replay_receipt(receipt, &answer, lang); }
+    let found = matches!(state.quizzes.get(&payload.quiz_id).await, Some(stored) if stored.quiz.kind == QuizKind::Hotspot);
+    let taken = if found { state.quizzes.take(&payload.quiz_id, Some(Receipt::pending(QuizKind::Hotspot, &answer))).await } else { None };
+    let removed = taken.map(|stored| {
+        let elapsed = stored.elapsed(chrono::Utc::now());
+        (stored, elapsed)
+    });
+    let Some((StoredQuiz { id: quiz_id, quiz, .. }, elapsed)) = removed else {
+        // a concurrent duplicate took it first; wait for its result
+        if found { if let Some(receipt) = state.quizzes.raced_receipt(&payload.quiz_id, QuizKind::Hotspot).await { return replay_receipt(receipt, &answer, lang); } }
+        return Ok(Json(QuizResult::unknown(lang)));
+    };
+    let Some(target) = quiz.hotspot else {
+        state.quizzes.drop_receipt(&payload.quiz_id).await;
+        return Ok(Json(QuizResult::unknown(lang)));
+    };
+    let correct = target.hit(click);
+    answer_log::log(vec![AnswerRecord {
+        picked: Some(answer.clone()),
+        picked_image: None,
+        correct,
+        response_ms: Some(elapsed.as_millis() as u64),
+        ..AnswerRecord::choice("hotspot", quiz_id, &target.feature, &quiz.choices, None)
+    }]);
+    let mut result = QuizResult::new(correct, target.feature.clone(), species::get(&quiz.answer_category), lang);
+    result.correct_label = Some(feature_label(&target.feature, lang));
+    result.response_ms = Some(elapsed.as_millis() as u64);
+    result.regions = Some(target.regions);
+    keep_receipt(&state, &payload.quiz_id, QuizKind::Hotspot, &answer, &result).await;
+    Ok(Json(result))
+}
+
+// A random photo of a species that has photos, and the species to choose from: the answer and
+// up to three others, shuffled. The choice is the sharp photo; callers set the frame.
+fn pick_reveal(rng: &mut impl Rng) -> Option<(GeneratedChoice, Vec<String>)> {
+    let keys = species::keys();
+    let with_photos: Vec<&String> = keys.iter().filter(|k| !asset_candidates(k).is_empty()).collect();
+    let target = (*with_photos.choose(rng)?).clone();
+    let file = asset_candidates(&target).choose(rng)?.clone();
+    let others: Vec<&String> = keys.iter().filter(|k| **k != target).collect();
+    let mut options: Vec<String> = others.choose_multiple(rng, 3).map(|k| (*k).clone()).collect();
+    options.push(target.clone());
+    options.shuffle(rng);
+    let token = format!("{:016x}", rng.gen::<u64>());
+    Some((GeneratedChoice { token, category: target, file: Some(file), procedural_key: None, degrade: None }, options))
+}
+
+fn reveal_response(id: String, quiz: &GeneratedQuiz, progress: &reveal::Progress, lang: &str) -> RevealQuizResponse {
+    let options = progress.options.iter().map(|key| RevealOption {
+        key: key.clone(),
+        label: species::get(key).map(|sp| sp.name(lang).to_string()).unwrap_or_else(|| key.clone()),
+    }).collect();
+    let token = quiz.choices.first().map(|c| c.token.clone()).unwrap_or_default();
+    RevealQuizResponse {
+        image_url: format!("/api/quiz_image/{}/{}", id, token),
+        id,
+        question: quiz.question.clone(),
+        options,
+        step: progress.step + 1,
+        steps: reveal::STEPS,
+        points: reveal::points(progress.step),
+    }
+}
+
+// ?style=blur|pixelate (default blur)
+async fn generate_reveal_quiz(State(state): State<AppState>, ConnectInfo(peer): ConnectInfo<SocketAddr>, Locale(lang): Locale, headers: HeaderMap, ApiQuery(q): ApiQuery<StdHashMap<String, String>>) -> Result<Json<RevealQuizResponse>, ApiError> {
+    let client = client_key(&headers, peer);
+    check_open_quizzes(&state, &client, lang).await?;
+    let style = match q.get("style") {
+        Some(s) => reveal::Style::parse(s).ok_or_else(|| ApiError::bad_request(i18n::t(lang, "error.reveal_style_invalid")))?,
+        None => reveal::Style::Blur,
+    };
+    let seed = quiz_seed(&headers, &q, lang)?;
+    let id = Uuid::new_v4().to_string();
+    let mut rng = StdRng::seed_from_u64(seed);
+    let (mut choice, options) = pick_reveal(&mut rng).ok_or_else(|| ApiError::unavailable(i18n::t(lang, "error.no_photos")))?;
+    let crop = reveal::random_crop(&mut rng);
+    choice.degrade = Some(reveal::frame(style, 0, crop));
+    let progress = reveal::Progress { style, step: 0, options, crop };
+    let question = i18n::t(lang, "question.reveal");
+    let quiz = GeneratedQuiz { kind: QuizKind::Reveal, seed, difficulty: Difficulty::Normal, question, answer_category: choice.category.clone(), choices: vec![choice], session_id: None, player_id: None, weights: None, time_limit_ms: None, hotspot: None, reveal: Some(progress.clone()) };
+    log_quiz(&id, &quiz, None);
+    let response_quiz = quiz.clone();
+    let id = state.quizzes.insert(StoredQuiz::new(&id, quiz, Some(client))).await;
+    Ok(Json(reveal_response(id, &response_quiz, &progress, lang)))
+}
+
+// the next, clearer frame under a new quiz id; the old id stops working
+async fn next_reveal_step(State(state): State<AppState>, Locale(lang): Locale, ApiJson(payload): ApiJson<RevealNext>) -> Result<Json<RevealQuizResponse>, ApiError> {
+    let not_found = || ApiError::not_found(i18n::t(lang, "error.quiz_not_found"));
+    let progress = match state.quizzes.get(&payload.quiz_id).await {
+        Some(stored) if stored.quiz.kind == QuizKind::Reveal => stored.quiz.reveal.ok_or_else(not_found)?,
+        _ => return Err(not_found()),
+    };
+    if progress.step + 1 >= reveal::STEPS { return Err(ApiError::conflict(i18n::t(lang, "error.reveal_complete"))); }
+    // Taking the old id marks it as used. With signed tokens that is a record in the shared replay
+    // directory, so an earlier frame's id cannot be answered later on any instance.
+    let stored = state.quizzes.take(&payload.quiz_id, None).await.ok_or_else(not_found)?;
+    let mut quiz = stored.quiz;
+    let progress = reveal::Progress { step: progress.step + 1, ..progress };
+    for choice in &mut quiz.choices {
+        choice.token = reveal::step_token(quiz.seed, progress.step);
+        choice.degrade = Some(reveal::frame(progress.style, progress.step, progress.crop));
+    }
+    quiz.reveal = Some(progress.clone());
+    let id = Uuid::new_v4().to_string();
+    log_quiz(&id, &quiz, None);
+    // one clock for the whole quiz: the TTL and the response time run from the first frame
+    let next = StoredQuiz { id, quiz: quiz.clone(), issued_at: stored.issued_at, client: stored.client };
+    let id = state.quizzes.insert(next).await;
+    Ok(Json(reveal_response(id, &quiz, &progress, lang)))
+}
+
+async fn submit_reveal_quiz(State(state): State<AppState>, Locale(lang): Locale, ApiJson(payload): ApiJson<RevealSubmit>) -> Result<Json<QuizResult>, ApiError> {
+    if let Some(receipt) = state.quizzes.receipt(&payload.quiz_id, QuizKind::Reveal).await { return replay_receipt(receipt, &payload.answer, lang); }
+    let progress = match state.quizzes.get(&payload.quiz_id).await {
+        Some(stored) if stored.quiz.kind == QuizKind::Reveal => stored.quiz.reveal,
+        _ => None,
+    };
+    if progress.as_ref().is_some_and(|p| !p.options.contains(&payload.answer)) {
+        return Err(ApiError::bad_request(i18n::t(lang, "error.unknown_choice")));
+    }
+    let found = progress.is_some();
+    let taken = if found { state.quizzes.take(&payload.quiz_id, Some(Receipt::pending(QuizKind::Reveal, &payload.answer))).await } else { None };
+    let removed = progress.zip(taken).map(|(p, stored)| {
+        let elapsed = stored.elapsed(chrono::Utc::now());
+        (stored, p, elapsed)
+    });
+    let Some((StoredQuiz { id: quiz_id, quiz, .. }, progress, elapsed)) = removed else {
+        // a concurrent duplicate took it first; wait for its result
+        if found { if let Some(receipt) = state.quizzes.raced_receipt(&payload.quiz_id, QuizKind::Reveal).await { return replay_receipt(receipt, &payload.answer, lang); } }
+        return Ok(Json(QuizResult::unknown(lang)));
+    };
+    let correct = payload.answer == quiz.answer_category;
+    let points = if correct { reveal::points(progress.step) } else { 0 };
+    let mut record = AnswerRecord::choice("reveal", quiz_id, &quiz.answer_category, &quiz.choices, None);
+    record.picked = Some(payload.answer.clone());
+    record.correct = correct;
+    record.response_ms = Some(elapsed.as_millis() as u64);
+    answer_log::log(vec![record]);
+    let sp = species::get(&quiz.answer_category);
+    let mut result = QuizResult::new(correct, quiz.answer_category.clone(), sp.clone(), lang);
+    result.correct_label = sp.map(|sp| sp.name(lang).to_string());
+    result.response_ms = Some(elapsed.as_millis() as u64);
+    result.points = Some(points);
+    keep_receipt(&state, &payload.quiz_id, QuizKind::Reveal, &payload.answer, &result).await;
+    Ok(Json(result))
+}
+
+// serves choice images by opaque token while the quiz is still active, so URLs never reveal the species
+// takes the /media size and filter parameters, e.g. ?w=640 for a phone or projector layout
+async fn quiz_image(State(state): State<AppState>, Locale(lang): Locale, ApiPath((quiz_id, token)): ApiPath<(String, String)>, ApiQuery(q): ApiQuery<StdHashMap<String, String>>) -> Result<impl IntoResponse, ApiError> {
+    let transform = image_transform(&q, lang)?;
+    let choice = state.quizzes.get(&quiz_id).await.and_then(|stored| stored.quiz.choices.into_iter().find(|c| c.token == token));
+    let choice = choice.ok_or_else(|| ApiError::not_found(i18n::t(lang, "error.quiz_image_not_found")))?;
+    choice_image_response(choice, &quiz_id, transform).await
+}
+
+// /media parameters on a quiz image; None without any, which keeps the quiz-size image
+fn image_transform(q: &StdHashMap<String, String>, lang: &str) -> Result<Option<media::Transform>, ApiError> {
+    let transform = media::Transform::from_query(q).map_err(|errors| ApiError::bad_request(i18n::t(lang, "error.image_params_invalid")).with_details(errors))?;
+    Ok(Some(transform).filter(|t| !t.is_identity()))
+}
+
+// decoding and re-encoding photos is CPU-bound, so it runs off the async workers
+async fn choice_image_response(choice: GeneratedChoice, quiz_id: &str, transform: Option<media::Transform>) -> Result<impl IntoResponse, ApiError> {
+    let cannot_render = || ApiError::internal(format!("cannot render image for quiz {}", quiz_id));
+    let (content_type, bytes) = match transform {
+        None => tokio::task::spawn_blocking(move || choice_image_bytes(&choice, None)).await.map_err(|e| ApiError::internal(e.to_string()))?.ok_or_else(cannot_render)?,
+        Some(transform) => {
+            // a sharp photo is a /media variant of the original, so it can be larger than the quiz size
+            // and shares its cache; anything else is the quiz image with the parameters applied
+            let source = choice.file.as_deref().filter(|_| choice.degrade.is_none()).and_then(media::source);
+            let cached = match source.clone() {
+                Some(source) => {
+                    let t = transform.clone();
+                    tokio::task::spawn_blocking(move || source.cached(&t).map(|bytes| (source.content_type(), bytes))).await.map_err(|e| ApiError::internal(e.to_string()))?
+                }
+                None => None,
+            };
+            match cached {
+                Some(hit) => hit,
+                None => {
+                    // the same render slots as /media
+                    let _slot = media::RENDER_SLOTS.acquire().await.map_err(|e| ApiError::internal(e.to_string()))?;
+                    tokio::task::spawn_blocking(move || match source {
+                        Some(source) => source.variant(&transform).ok().map(|bytes| (source.content_type(), bytes)),
+                        None => choice_image_bytes(&choice, Some(&transform)),
+                    }).await.map_err(|e| ApiError::internal(e.to_string()))?.ok_or_else(cannot_render)?
+                }
+            }
+        }
+    };
+    let mut headers = axum::http::HeaderMap::new();
+    headers.insert(header::CONTENT_TYPE, header::HeaderValue::from_static(content_type));
+    headers.insert(header::CACHE_CONTROL, header::HeaderValue::from_static("no-store"));
+    Ok((headers, Bytes::from(bytes)))
+}
+
+// Photos are always decoded and re-encoded, never sent as stored, so the bytes match no file a
+// player could look up. Only undegraded procedural images go out as generated.
+fn choice_image_bytes(choice: &GeneratedChoice, transform: Option<&media::Transform>) -> Option<(&'static str, Vec<u8>)> {
+    let img = match &choice.file {
+        Some(file) => {
+            // degraded variants start from the full-size original, plain ones from the thumbnail if there is one
+            let assets_dir = PathBuf::from("public").join("assets");
+            let thumb_path = assets_dir.join("thumbs").join(file);
+            let path = if choice.degrade.is_none() && thumb_path.exists() { thumb_path } else { assets_dir.join(file) };
+            image::open(path).ok()?
+        }
+        None => {
+            let png = generate_image_bytes(choice.procedural_key.as_deref()?).ok()?;
+            if choice.degrade.is_none() && transform.is_none() { return Some(("image/png", png)); }
+            image::load_from_memory(&png).ok()?
+        }
+    };
+    let out = match &choice.degrade {
+        Some(d) => imaging::degrade_image(&img, d),
+        None => imaging::fit_output(&img),
+    };
+    let out = match transform {
+        Some(t) => t.apply(&out),
+        None => out,
+    };
+    imaging::encode_jpeg(&out).ok().map(|b| ("image/jpeg", b))
+}
+
+// Questions for `date`, all generated from the date seed. Only today's challenge is served.
+fn daily_challenge(date: chrono::NaiveDate) -> Option<Vec<GeneratedQuiz>> {
+    if date != daily::today() { return None; }
+    let mut cached = DAILY_CHALLENGE.lock();
+    if let Some((d, quizzes)) = cached.as_ref() {
+        if *d == date { return Some(quizzes.clone()); }
+    }
+    let quizzes = generate_daily(date)?;
+    // answers are published from the key saved here, never from a later regeneration
+    let key = quizzes.iter().enumerate().map(|(index, quiz)| daily::KeyQuestion {
+        quiz_id: format!("{}#{}", date, index + 1),
+        answer: quiz.answer_category.clone(),
+        photos: quiz.choices.iter().filter(|c| c.category == quiz.answer_category)
+            .filter_map(|c| c.file.clone().or_else(|| c.procedural_key.clone())).collect(),
+    }).collect();
+    if let Err(e) = daily::save_answer_key(date, key) {
+        eprintln!("daily answer key write failed for {}: {}", date, e);
+        return None;
+    }
+    *cached = Some((date, quizzes.clone()));
+    Some(quizzes)
+}
+
+// the questions of any day, as they come out of the date seed
+fn generate_daily(date: chrono::NaiveDate) -> Option<Vec<GeneratedQuiz>> {
+    let seed = daily::seed_for(date);
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut quizzes = Vec::new();
+    for _ in 0..daily::rounds() {
+        let (choices, target_cat) = pick_choices(Difficulty::Normal, &mut rng, None)?;
+        let question = choice_question(&target_cat, i18n::DEFAULT_LOCALE);
+        // all rounds share one RNG stream, so `seed` alone reproduces the whole day, not one round
+        quizzes.push(GeneratedQuiz { kind: QuizKind::Choice, seed, difficulty: Difficulty::Normal, question, choices, answer_category: target_cat, session_id: None, player_id: None, weights: None, time_limit_ms: None, hotspot: None, reveal: None });
+    }
+    Some(quizzes)
+}
+
+// ?player_id=<id> starts the player's clock and reports whether they already played today
+async fn get_daily(Locale(lang): Locale, ApiQuery(q): ApiQuery<StdHashMap<String, String>>) -> Result<Json<DailyChallengeResponse>, ApiError> {
+    let date = daily::today();
+    let quizzes = daily_challenge(date).ok_or_else(|| ApiError::unavailable(i18n::t(lang, "error.daily_unavailable")))?;
+    let mut submitted = false;
+    if let Some(player_id) = q.get("player_id") {
+        if !daily::valid_player_id(player_id) { return Err(ApiError::bad_request(i18n::t(lang, "error.player_id_invalid"))); }
+        daily::mark_started(date, player_id);
+        submitted = daily::has_submitted(date, player_id).map_err(|e| ApiError::internal(format!("daily leaderboard read failed: {}", e)))?;
+    }
+    let questions = quizzes.iter().enumerate().map(|(index, quiz)| FixedQuestion {
+        index,
+        question: choice_question(&quiz.answer_category, lang),
+        choices: quiz.choices.iter().map(|c| PublicChoice { token: c.token.clone(), image_url: format!("/api/daily/image/{}/{}/{}", date, index, c.token) }).collect(),
+    }).collect();
+    Ok(Json(DailyChallengeResponse { date, rounds: quizzes.len(), questions, submitted }))
+}
+
+async fn daily_image(Locale(lang): Locale, ApiPath((date, index, token)): ApiPath<(String, usize, String)>, ApiQuery(q): ApiQuery<StdHashMap<String, String>>) -> Result<impl IntoResponse, ApiError> {
+    let transform = image_transform(&q, lang)?;
+    let not_found = || ApiError::not_found(i18n::t(lang, "error.quiz_image_not_found"));
+    let date: chrono::NaiveDate = date.parse().map_err(|_| not_found())?;
+    let quizzes = daily_challenge(date).ok_or_else(not_found)?;
+    let choice = quizzes.get(index).and_then(|quiz| quiz.choices.iter().find(|c| c.token == token)).ok_or_else(not_found)?;
+    choice_image_response(choice.clone(), &format!("daily {} #{}", date, index), transform).await
+}
+
+async fn submit_daily(Locale(lang): Locale, ApiJson(payload): ApiJson<DailySubmit>) -> Result<Json<DailySubmitResult>, ApiError> {
+    if !daily::valid_player_id(&payload.player_id) { return Err(ApiError::bad_request(i18n::t(lang, "error.player_id_invalid"))); }
+    let nickname = leaderboard::clean_nickname(&payload.nickname)
+        .ok_or_else(|| ApiError::bad_request(i18n::tf(lang, "error.nickname_invalid", &[("max", &leaderboard::MAX_NICKNAME_CHARS.to_string())])))?;
+    let quizzes = daily_challenge(payload.date).ok_or_else(|| ApiError::conflict(i18n::t(lang, "error.daily_ended")))?;
+    if payload.answers.len() != quizzes.len() {
+        return Err(ApiError::bad_request(i18n::tf(lang, "error.daily_answers_count", &[("rounds", &quizzes.len().to_string())])));
+    }
+    // an unknown token simply counts as a wrong answer; only the total goes back until the day is over
+    let score = quizzes.iter().zip(&payload.answers)
+        .filter(|(quiz, token)| quiz.choices.iter().any(|c| &c.token == *token && c.category == quiz.answer_category))
+        .count();
+    let entry = match daily::submit(payload.date, &payload.player_id, nickname.clone(), score, quizzes.len()) {
+        Ok(e) => e,
+        Err(daily::SubmitError::NotStarted) => return Err(ApiError::conflict(i18n::t(lang, "error.daily_not_started"))),
+        Err(daily::SubmitError::AlreadySubmitted) => return Err(ApiError::conflict(i18n::t(lang, "error.daily_already_submitted"))),
+        Err(daily::SubmitError::NicknameTaken) => return Err(ApiError::conflict(i18n::t(lang, "error.daily_nickname_taken"))),
+        Err(daily::SubmitError::Io(e)) => return Err(ApiError::internal(format!("daily leaderboard write failed: {}", e))),
+    };
+    // counted only once the submission is accepted, so rejected repeats do not skew the stats
+    let outcomes = quizzes.iter().zip(&payload.answers)
+        .flat_map(|(quiz, token)| choice_outcomes(&quiz.choices, &quiz.answer_category, quiz.choices.iter().find(|c| &c.token == token)))
+        .collect();
+    record_image_outcomes(outcomes);
+    answer_log::log(quizzes.iter().zip(&payload.answers).enumerate().map(|(index, (quiz, token))| AnswerRecord {
+        session: Some(payload.date.to_string()),
+        player_id: Some(payload.player_id.clone()),
+        nickname: Some(nickname.clone()),
+        ..AnswerRecord::choice("daily", format!("{}#{}", payload.date, index + 1), &quiz.answer_category, &quiz.choices, quiz.choices.iter().find(|c| &c.token == token))
+    }).collect());
+    Ok(Json(DailySubmitResult { date: entry.date, score, rounds: entry.rounds, time_taken_ms: entry.time_taken_ms }))
+}
+
+// ?date=YYYY-MM-DD of a day that is over: what each question was looking for
+async fn get_daily_answers(Locale(lang): Locale, ApiQuery(q): ApiQuery<StdHashMap<String, String>>) -> Result<Json<Vec<DailyAnswer>>, ApiError> {
+    let date: chrono::NaiveDate = q.get("date").and_then(|d| d.parse().ok()).ok_or_else(|| ApiError::bad_request(i18n::t(lang, "error.date_invalid")))?;
+    if !daily::is_over(date) { return Err(ApiError::conflict(i18n::t(lang, "error.daily_answers_not_yet"))); }
+    let key = daily::answer_key(date)
+        .map_err(|e| ApiError::internal(format!("daily answer key read failed: {}", e)))?
+        .ok_or_else(|| ApiError::not_found(i18n::t(lang, "error.daily_not_played")))?;
+    Ok(Json(key.into_iter().enumerate().map(|(index, question)| {
+        let sp = species::get(&question.answer);
+        DailyAnswer {
+            index,
+            quiz_id: question.quiz_id,
+            answer_label: sp.as_ref().map(|sp| sp.name(lang).to_string()).unwrap_or_else(|| question.answer.clone()),
+            explanation: sp.as_ref().and_then(|sp| sp.explanation(lang)).map(|s| s.to_string()),
+            info_url: sp.and_then(|sp| sp.info_url),
+            answer: question.answer,
+        }
+    }).collect()))
+}
+
+// ?date=YYYY-MM-DD (default today) &limit=<n> (default 10, max 100)
+async fn get_daily_leaderboard(Locale(lang): Locale, ApiQuery(q): ApiQuery<StdHashMap<String, String>>) -> Result<Json<Vec<daily::DailyRank>>, ApiError> {
+    let date = match q.get("date") {
+        Some(d) => d.parse().map_err(|_| ApiError::bad_request(i18n::t(lang, "error.date_invalid")))?,
+        None => daily::today(),
+    };
+    let limit: usize = q.get("limit").and_then(|s| s.parse().ok()).unwrap_or(10).clamp(1, 100);
+    daily::top(date, limit).map(Json).map_err(|e| ApiError::internal(format!("daily leaderboard read failed: {}", e)))
+}
+
+// what a player still needs to practise: their review cards and confusions
+async fn get_player_review(Locale(lang): Locale, ApiPath(player_id): ApiPath<String>) -> Result<Json<review::PlayerHistory>, ApiError> {
+    if !daily::valid_player_id(&player_id) { return Err(ApiError::bad_request(i18n::t(lang, "error.player_id_invalid"))); }
+    let history = tokio::task::spawn_blocking(move || review::get(&player_id)).await.ok().flatten();
+    Ok(Json(history.unwrap_or_default()))
+}
+
+async fn create_session(Locale(lang): Locale, payload: Option<Json<SessionCreate>>) -> Result<Json<SessionCreated>, ApiError> {
+    let payload = payload.map(|Json(p)| p).unwrap_or_default();
+    let rounds = payload.rounds.unwrap_or(session::DEFAULT_ROUNDS);
+    if rounds == 0 || rounds > session::MAX_ROUNDS { return Err(ApiError::bad_request(i18n::tf(lang, "error.session_rounds_invalid", &[("max", &session::MAX_ROUNDS.to_string())]))); }
+    let time_limit = time_limit_from(payload.time_limit_secs, lang)?;
+    let session_id = session::create_session(rounds, time_limit);
+    Ok(Json(SessionCreated { session_id, rounds, time_limit_secs: payload.time_limit_secs }))
+}
+
+#[derive(Deserialize, Default)]
+struct RoomCreate {
+    rounds: Option<usize>,
+    difficulty: Option<String>,
+    time_limit_secs: Option<u64>,
+}
+
+#[derive(Serialize)]
+struct RoomCreated {
+    code: String,
+    // whoever has the key can run the room; players only need the code
+    host_key: String,
+    rounds: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    time_limit_secs: Option<u64>,
+}
+
+// questions in a room are asked in the language of the request that created it
+async fn create_room(Locale(lang): Locale, payload: Option<Json<RoomCreate>>) -> Result<Json<RoomCreated>, ApiError> {
+    let payload = payload.map(|Json(p)| p).unwrap_or_default();
+    let rounds = payload.rounds.unwrap_or(room::DEFAULT_ROUNDS);
+    if rounds == 0 || rounds > room::MAX_ROUNDS { return Err(ApiError::bad_request(i18n::tf(lang, "error.room_rounds_invalid", &[("max", &room::MAX_ROUNDS.to_string())]))); }
+    let difficulty = match payload.difficulty.as_deref() {
+        Some(d) => Difficulty::parse(d).ok_or_else(|| ApiError::bad_request(i18n::t(lang, "error.difficulty_invalid")))?,
+        None => Difficulty::Normal,
+    };
+    let time_limit = time_limit_from(payload.time_limit_secs, lang)?;
+    let (code, host_key) = room::create(rounds, difficulty, time_limit, lang).ok_or_else(|| ApiError::unavailable(i18n::t(lang, "error.room_limit")))?;
+    Ok(Json(RoomCreated { code, host_key, rounds, time_limit_secs: payload.time_limit_secs }))
 }
 
-fn hamming_hex(a_hex: &str, b_hex: &str) -> Option<u32> {
-    if a_hex.len() != 16 || b_hex.len() != 16 { return None; }
-    let a = u64::from_str_radix(a_hex, 16).ok()?;
-    let b = u64::from_str_radix(b_hex, 16).ok()?;
-    Some((a ^ b).count_ones())
+async fn get_room(Locale(lang): Locale, ApiPath(code): ApiPath<String>) -> Result<Json<room::RoomInfo>, ApiError> {
+    let code = room::normalize_code(&code);
+    room::info(&code).map(Json).ok_or_else(|| ApiError::not_found(i18n::tf(lang, "error.room_not_found", &[("code", &code)])))
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use image::DynamicImage;
-    use image::RgbaImage;
+async fn room_image(Locale(lang): Locale, ApiPath((code, token)): ApiPath<(String, String)>, ApiQuery(q): ApiQuery<StdHashMap<String, String>>) -> Result<impl IntoResponse, ApiError> {
+    let transform = image_transform(&q, lang)?;
+    let choice = room::choice(&room::normalize_code(&code), &token).ok_or_else(|| ApiError::not_found(i18n::t(lang, "error.quiz_image_not_found")))?;
+    choice_image_response(choice, &code, transform).await
+}
 
-    #[test]
-    fn test_ahash_and_hamming_same_image() {
-        // create a small solid image
-        let mut im = RgbaImage::new(16, 16);
-        for p in im.pixels_mut() { *p = image::Rgba([200, 180, 160, 255]); }
-        let di = DynamicImage::ImageRgba8(im.clone());
-        let h1 = compute_ahash(&di);
-        let h2 = compute_ahash(&di);
-        assert_eq!(h1, h2);
-        let dist = hamming_hex(&h1, &h2).unwrap();
-        assert_eq!(dist, 0);
+fn room_join_error(lang: &str, code: &str, e: room::JoinError) -> ApiError {
+    match e {
+        room::JoinError::NotFound => ApiError::not_found(i18n::tf(lang, "error.room_not_found", &[("code", code)])),
+        room::JoinError::BadHostKey => ApiError::unauthorized(i18n::t(lang, "error.room_host_key_invalid")),
+        room::JoinError::NicknameTaken => ApiError::conflict(i18n::t(lang, "error.room_nickname_taken")),
+        room::JoinError::Full => ApiError::conflict(i18n::tf(lang, "error.room_full", &[("max", &room::MAX_PLAYERS.to_string())])),
+        room::JoinError::Finished => ApiError::conflict(i18n::t(lang, "error.room_finished")),
     }
+}
 
-    #[test]
-    fn test_ahash_and_hamming_different_images() {
-        let mut a = RgbaImage::new(16, 16);
-        for p in a.pixels_mut() { *p = image::Rgba([10, 10, 10, 255]); }
-        // make b with left half dark and right half bright so phash should differ
-        let mut b = RgbaImage::new(16, 16);
-        for y in 0..16 {
-            for x in 0..16 {
-                let v = if x < 8 { 20 } else { 230 };
-                b.put_pixel(x, y, image::Rgba([v, v, v, 255]));
-            }
+// ?role=host for the host, who then sends the host key as the first message;
+// otherwise ?player_id=<id>&nickname=<name>
+async fn room_ws(ws: WebSocketUpgrade, Locale(lang): Locale, ApiPath(code): ApiPath<String>, ApiQuery(q): ApiQuery<StdHashMap<String, String>>) -> Result<impl IntoResponse, ApiError> {
+    let code = room::normalize_code(&code);
+    let member = match q.get("role").map(String::as_str) {
+        Some("host") => room::Member::Host,
+        _ => {
+            let player_id = q.get("player_id").filter(|id| daily::valid_player_id(id)).ok_or_else(|| ApiError::bad_request(i18n::t(lang, "error.player_id_invalid")))?;
+            let nickname = leaderboard::clean_nickname(q.get("nickname").map(String::as_str).unwrap_or(""))
+                .ok_or_else(|| ApiError::bad_request(i18n::tf(lang, "error.nickname_invalid", &[("max", &leaderboard::MAX_NICKNAME_CHARS.to_string())])))?;
+            room::Member::Player { player_id: player_id.clone(), nickname }
         }
-        let ha = compute_ahash(&DynamicImage::ImageRgba8(a));
-        let hb = compute_ahash(&DynamicImage::ImageRgba8(b));
-        let dist = hamming_hex(&ha, &hb).unwrap();
-        assert!(dist > 0, "expected different images to have non-zero Hamming distance, got 0 (ha={} hb={})", ha, hb);
+    };
+    // refused before the upgrade, so the client gets an ordinary HTTP error; the host key is checked once it arrives
+    match &member {
+        room::Member::Host => if room::info(&code).is_none() { return Err(room_join_error(lang, &code, room::JoinError::NotFound)); },
+        room::Member::Player { .. } => room::check_join(&code, &member, None).map_err(|e| room_join_error(lang, &code, e))?,
     }
+    Ok(ws.on_upgrade(move |socket| room_socket(socket, lang, code, member)))
 }
 
-// similar search endpoint: ?filename=<name>&token=<token>&max_hamming=10
-async fn admin_similar(headers: HeaderMap, Query(q): Query<StdHashMap<String, String>>) -> Json<Vec<AdminListEntry>> {
-    // prefer Authorization: Bearer <token> header, fallback to ?token=
-    let header_token = token_from_headers(&headers);
-    let token = header_token.or_else(|| q.get("token").cloned()).unwrap_or_default();
-    if !check_admin_token_token(&token) { return Json(vec![]); }
-    let filename = match q.get("filename") { Some(s) => s.clone(), None => return Json(vec![]) };
-    let max_hamming: u32 = q.get("max_hamming").and_then(|s| s.parse().ok()).unwrap_or(10);
-    let idx = load_index();
-    let base = match idx.iter().find(|e| e.filename == filename) { Some(e) => e.phash.clone().unwrap_or_default(), None => return Json(vec![]) };
-    let mut out = Vec::new();
-    for e in idx.iter() {
-        if e.filename == filename { continue; }
-        if let (Some(a), Some(b)) = (Some(base.as_str()), e.phash.as_deref()) {
-            if let Some(dist) = hamming_hex(a, b) {
-                if dist <= max_hamming { out.push(AdminListEntry { filename: e.filename.clone(), size: e.size, thumb: e.thumb, uploaded_at: Some(e.uploaded_at.clone()), uploader: e.uploader.clone() }); }
-            }
-        }
+// how long a host socket may take to send its key
+const ROOM_HOST_AUTH_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum RoomMessage {
+    // host only, and only as the first message
+    Host { host_key: String },
+    // host only: close the open round, show the next question or the final ranking
+    Next,
+    Answer { choice: String },
+}
+
+// the key the host sent as its first message; None after a timeout or any other message
+async fn room_host_key(socket: &mut WebSocket) -> Option<String> {
+    let Ok(Some(Ok(Message::Text(text)))) = tokio::time::timeout(ROOM_HOST_AUTH_TIMEOUT, socket.recv()).await else { return None };
+    match serde_json::from_str(&text) {
+        Ok(RoomMessage::Host { host_key }) => Some(host_key),
+        _ => None,
     }
-    Json(out)
 }
 
-async fn generate_quiz() -> Json<GeneratedQuizResponse> {
-    // Use free image source URLs (no download). We'll return external URLs that the client can load directly.
-    let mut choices: Vec<GeneratedChoice> = Vec::new();
-    // categories and preferred local filenames (user should place real photos here)
-    let categories = vec!["tanuki", "anaguma", "hakubishin"];
-    // For each category, prefer local files public/assets/<category><n>.jpg (1..3)
-    let static_dir = PathBuf::from("public").join("assets");
-    let mut rng = rand::thread_rng();
-    for (i, cat_key) in categories.iter().enumerate() {
-        let mut image_url = String::new();
-        // look for any matching local files in public/assets (support any number)
-        if static_dir.exists() {
-            let mut candidates: Vec<String> = Vec::new();
-            if let Ok(entries) = std::fs::read_dir(&static_dir) {
-                for entry in entries.flatten() {
-                    if let Some(name_os) = entry.file_name().to_str() {
-                        let name = name_os.to_string();
-                        let lower = name.to_lowercase();
-                        if lower.starts_with(&cat_key.to_string()) && (lower.ends_with(".jpg") || lower.ends_with(".jpeg") || lower.ends_with(".png")) {
-                            candidates.push(name);
-                        }
+async fn send_room_event(socket: &mut WebSocket, event: &room::Event) -> Result<(), axum::Error> {
+    let text = serde_json::to_string(event).unwrap_or_default();
+    socket.send(Message::Text(text)).await
+}
+
+async fn room_socket(mut socket: WebSocket, lang: &'static str, code: String, member: room::Member) {
+    let host_key = match member {
+        room::Member::Host => room_host_key(&mut socket).await,
+        room::Member::Player { .. } => None,
+    };
+    // the room may have changed since check_join, e.g. a nickname was taken in between
+    let joined = match room::join(&code, &member, host_key.as_deref()) {
+        Ok(j) => j,
+        Err(e) => {
+            let _ = send_room_event(&mut socket, &room::Event::Error { message: room_join_error(lang, &code, e).message }).await;
+            return;
+        }
+    };
+    let mut rx = joined.rx;
+    for event in &joined.initial {
+        if send_room_event(&mut socket, event).await.is_err() {
+            room::leave(&code, &member, &answer_log::log_path());
+            return;
+        }
+    }
+    loop {
+        tokio::select! {
+            msg = socket.recv() => match msg {
+                Some(Ok(Message::Text(text))) => {
+                    if let Err(message) = handle_room_message(lang, &code, &member, &text).await {
+                        if send_room_event(&mut socket, &room::Event::Error { message }).await.is_err() { break; }
                     }
                 }
-            }
-            if !candidates.is_empty() {
-                let picked = candidates.choose(&mut rng).unwrap().clone();
-                // prefer thumbnail if exists
-                let thumb_path = PathBuf::from("public").join("assets").join("thumbs").join(&picked);
-                if thumb_path.exists() {
-                    image_url = format!("/assets/thumbs/{}", picked);
-                } else {
-                    image_url = format!("/assets/{}", picked);
-                }
-            }
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => {}
+            },
+            event = rx.recv() => match event {
+                Ok(event) => if send_room_event(&mut socket, &event).await.is_err() { break },
+                // a slow phone missed some events; the next ones still make sense on their own
+                Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => {}
+                Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+            },
         }
+    }
+    room::leave(&code, &member, &answer_log::log_path());
+}
 
-        // if no local file, fallback to Unsplash Source
-        if image_url.is_empty() {
-            let keywords = match *cat_key {
-                "tanuki" => "tanuki,raccoon dog,狸",
-                "anaguma" => "badger,anaguma,アナグマ",
-                "hakubishin" => "masked palm civet,hakubishin,ハクビシン",
-                _ => "animal,wildlife",
-            };
-            let sig: u64 = rng.gen();
-            image_url = format!("https://source.unsplash.com/800x600/?{}&sig={}", keywords, sig);
-        }
+// errors come back as a localized message for the sender only
+async fn handle_room_message(lang: &'static str, code: &str, member: &room::Member, text: &str) -> Result<(), String> {
+    let msg: RoomMessage = serde_json::from_str(text).map_err(|_| i18n::t(lang, "error.room_message_invalid"))?;
+    match (msg, member) {
+        (RoomMessage::Next, room::Member::Host) => room_next(lang, code).await,
+        (RoomMessage::Next, _) => Err(i18n::t(lang, "error.room_host_only")),
+        (RoomMessage::Answer { choice }, room::Member::Player { player_id, .. }) => room::answer(code, player_id, &choice, &answer_log::log_path()).map_err(|e| match e {
+            room::AnswerError::NotFound => i18n::tf(lang, "error.room_not_found", &[("code", code)]),
+            room::AnswerError::NoQuestion => i18n::t(lang, "error.room_no_question"),
+            room::AnswerError::UnknownChoice => i18n::t(lang, "error.unknown_choice"),
+            room::AnswerError::AlreadyAnswered => i18n::t(lang, "error.room_already_answered"),
+        }),
+        (RoomMessage::Answer { .. }, room::Member::Host) | (RoomMessage::Host { .. }, _) => Err(i18n::t(lang, "error.room_message_invalid")),
+    }
+}
 
-        choices.push(GeneratedChoice { id: i + 1, image_url, category: cat_key.to_string() });
+// questions come from the same generator as /api/generate_quiz, without player weighting.
+// room::next has released the room lock by the time the question is picked, and picking reads
+// the asset index from disk, so it runs on the blocking pool.
+async fn room_next(lang: &str, code: &str) -> Result<(), String> {
+    let next = room::next(code, &answer_log::log_path()).map_err(|e| room_join_error(lang, code, e).message)?;
+    let room::Next::NeedQuestion { round, difficulty, lang: room_lang } = next else { return Ok(()) };
+    let seed = quiz_log::random_seed();
+    let picked = tokio::task::spawn_blocking(move || pick_choices(difficulty, &mut StdRng::seed_from_u64(seed), None)).await.ok().flatten();
+    // only possible for easy rooms before admins have marked clear examples
+    let (choices, target) = picked.ok_or_else(|| i18n::t(lang, "error.not_enough_clear_examples"))?;
+    let question = choice_question(&target, room_lang);
+    if let Some(limit) = room::start_round(code, round, question, target, choices) {
+        let code = code.to_string();
+        tokio::spawn(async move {
+            tokio::time::sleep(limit + deadline_grace()).await;
+            room::close_round(&code, round, &answer_log::log_path());
+        });
     }
+    Ok(())
+}
 
-    // Shuffle so order isn't predictable
-    choices.shuffle(&mut rng);
+#[derive(Deserialize)]
+struct LeaderboardSubmit {
+    session_id: String,
+    nickname: String,
+}
 
-    // select target category
-    let target_cat = if let Some(c) = choices.choose(&mut rng) { c.category.clone() } else { "other".to_string() };
-    let label = match target_cat.as_str() {
-        "tanuki" => "たぬき",
-        "anaguma" => "アナグマ",
-        "hakubishin" => "ハクビシン",
-        "animal" => "動物",
-        _ => "特徴のある画像",
+// the score comes from the server-side session, never from the request body
+async fn submit_leaderboard(Locale(lang): Locale, ApiJson(payload): ApiJson<LeaderboardSubmit>) -> Result<Json<leaderboard::LeaderboardEntry>, ApiError> {
+    let nickname = leaderboard::clean_nickname(&payload.nickname)
+        .ok_or_else(|| ApiError::bad_request(i18n::tf(lang, "error.nickname_invalid", &[("max", &leaderboard::MAX_NICKNAME_CHARS.to_string())])))?;
+    let summary = match session::claim_for_leaderboard(&payload.session_id) {
+        Ok(s) => s,
+        Err(session::ClaimError::NotFound) => return Err(ApiError::not_found(i18n::t(lang, "error.session_not_found"))),
+        Err(session::ClaimError::NotFinished) => return Err(ApiError::conflict(i18n::t(lang, "error.session_not_finished"))),
+        Err(session::ClaimError::AlreadySubmitted) => return Err(ApiError::conflict(i18n::t(lang, "error.session_already_submitted"))),
     };
+    let entry = leaderboard::LeaderboardEntry {
+        nickname,
+        score: summary.score,
+        speed_bonus: summary.speed_bonus,
+        rounds: summary.rounds,
+        time_taken_ms: summary.time_taken_ms,
+        submitted_at: chrono::Utc::now(),
+        session_id: payload.session_id.clone(),
+    };
+    if let Err(e) = leaderboard::add_entry(entry.clone()) {
+        session::release_claim(&payload.session_id);
+        return Err(ApiError::internal(format!("leaderboard write failed: {}", e)));
+    }
+    Ok(Json(entry))
+}
 
-    let question = format!("次の画像のうち、{} はどれですか？", label);
-    let quiz = GeneratedQuiz { question: question.clone(), choices: choices.clone(), answer_category: target_cat.clone() };
-
-    // generate id and store it
-    let id = Uuid::new_v4().to_string();
-    QUIZ_STORE.lock().insert(id.clone(), (quiz, Instant::now()));
+// ?period=day|week|all (default all) &limit=<n> (default 10, max 100)
+async fn get_leaderboard(Locale(lang): Locale, ApiQuery(q): ApiQuery<StdHashMap<String, String>>) -> Result<Json<Vec<leaderboard::LeaderboardEntry>>, ApiError> {
+    let period = match q.get("period") {
+        Some(p) => leaderboard::Period::parse(p).ok_or_else(|| ApiError::bad_request(i18n::t(lang, "error.period_invalid")))?,
+        None => leaderboard::Period::All,
+    };
+    let limit: usize = q.get("limit").and_then(|s| s.parse().ok()).unwrap_or(10).clamp(1, 100);
+    leaderboard::top(period, limit).map(Json).map_err(|e| ApiError::internal(format!("leaderboard read failed: {}", e)))
+}
 
-    Json(GeneratedQuizResponse { id, question, choices })
+async fn get_session(Locale(lang): Locale, ApiPath(id): ApiPath<String>) -> Result<Json<session::SessionSummary>, ApiError> {
+    session::summary(&id).map(Json).ok_or_else(|| ApiError::not_found(i18n::t(lang, "error.session_not_found")))
 }
 
 // simple admin upload via JSON { filename, b64 }
-async fn admin_upload_json(headers: HeaderMap, Json(payload): Json<AdminUploadJson>) -> Json<AdminUploadResult> {
+async fn admin_upload_json(headers: HeaderMap, ApiJson(payload): ApiJson<AdminUploadJson>) -> Result<Json<AdminUploadResult>, ApiError> {
     // if uploads are not enabled in this environment, reject to avoid accidental public uploads
     if !uploads_enabled() {
-        return Json(AdminUploadResult { ok: false, saved_filename: None, thumb_filename: None, message: Some("uploads are disabled in this environment (ENABLE_ADMIN_UPLOADS=false)".to_string()) });
+        return Err(ApiError::forbidden("uploads are disabled in this environment (ENABLE_ADMIN_UPLOADS=false)"));
     }
 
     // require Authorization: Bearer <token>
-    let header_token = token_from_headers(&headers);
-    let token = header_token.unwrap_or_default();
-    if !check_admin_token_token(&token) {
-        return Json(AdminUploadResult { ok: false, saved_filename: None, thumb_filename: None, message: Some("unauthorized: invalid admin token".to_string()) });
-    }
+    let token = require_admin(&headers, None)?;
 
     // require rights confirmation
-    if payload.rights_confirmed.unwrap_or(false) == false {
-        return Json(AdminUploadResult { ok: false, saved_filename: None, thumb_filename: None, message: Some("upload rejected: uploader must confirm they have rights to use this image".to_string()) });
+    if !payload.rights_confirmed.unwrap_or(false) {
+        return Err(ApiError::bad_request("upload rejected: uploader must confirm they have rights to use this image"));
     }
     // sanitize filename: keep ascii alnum, dash, underscore and extension
     let name = payload.filename.clone();
     if name.contains('/') || name.contains('\\') {
-        return Json(AdminUploadResult { ok: false, saved_filename: None, thumb_filename: None, message: Some("invalid filename".to_string()) });
+        return Err(ApiError::bad_request("invalid filename"));
     }
     // lower-case extension
     let safe: String = name.chars().map(|c| {
         if c.is_ascii_alphanumeric() || c == '.' || c == '-' || c == '_' { c } else { '_' }
     }).collect();
-    let safe = safe;
 
     // decode base64
     let data = match BASE64.decode(payload.b64.trim()) {
         Ok(d) => d,
-        Err(e) => return Json(AdminUploadResult { ok: false, saved_filename: None, thumb_filename: None, message: Some(format!("base64 decode error: {}", e)) }),
+        Err(e) => return Err(ApiError::bad_request(format!("base64 decode error: {}", e))),
     };
+    if data.len() > max_upload_bytes() {
+        return Err(ApiError::payload_too_large(format!("image is larger than {} bytes", max_upload_bytes())));
+    }
 
     // verify image
     let img_dyn = match image::load_from_memory(&data) {
         Ok(d) => d,
-        Err(e) => return Json(AdminUploadResult { ok: false, saved_filename: None, thumb_filename: None, message: Some(format!("invalid image data: {}", e)) }),
+        Err(e) => return Err(ApiError::bad_request(format!("invalid image data: {}", e))),
     };
 
     // ensure dirs
     let assets_dir = PathBuf::from("public").join("assets");
     let thumbs_dir = assets_dir.join("thumbs");
-    if let Err(e) = std::fs::create_dir_all(&thumbs_dir) { return Json(AdminUploadResult { ok: false, saved_filename: None, thumb_filename: None, message: Some(format!("mkdir error: {}", e)) }); }
+    if let Err(e) = std::fs::create_dir_all(&thumbs_dir) { return Err(ApiError::internal(format!("mkdir error: {}", e))); }
 
     // ensure unique filename if exists
     let mut target = assets_dir.join(&safe);
@@ -367,50 +1791,48 @@ async fn admin_upload_json(headers: HeaderMap, Json(payload): Json<AdminUploadJs
     // write original
     match File::create(&target).and_then(|mut f| f.write_all(&data)) {
         Ok(_) => {},
-        Err(e) => return Json(AdminUploadResult { ok: false, saved_filename: None, thumb_filename: None, message: Some(format!("write error: {}", e)) }),
+        Err(e) => return Err(ApiError::internal(format!("write error: {}", e))),
     }
 
     // create thumbnail 320x240 (maintain aspect via thumbnail method)
     let thumb = img_dyn.thumbnail(320, 240).to_rgba8();
     let thumb_path = thumbs_dir.join(target.file_name().and_then(|s| s.to_str()).unwrap_or("thumb.png"));
     if let Err(e) = thumb.save(&thumb_path) {
-        return Json(AdminUploadResult { ok: false, saved_filename: target.file_name().and_then(|s| s.to_str()).map(|s| s.to_string()), thumb_filename: None, message: Some(format!("thumbnail save error: {}", e)) });
+        return Err(ApiError::internal(format!("thumbnail save error: {}", e)));
     }
 
     // compute phash and update index
     let phash = compute_ahash(&img_dyn);
     let size = target.metadata().map(|m| m.len()).unwrap_or(0);
     let uploaded_at = chrono::Utc::now().to_rfc3339();
-    let mut idx = load_index();
-    idx.retain(|e| e.filename != target.file_name().and_then(|s| s.to_str()).unwrap_or(""));
-    idx.push(AssetIndexEntry {
-        filename: target.file_name().and_then(|s| s.to_str()).unwrap_or("").to_string(),
-        size,
-        thumb: true,
-        phash: Some(phash.clone()),
-        uploaded_at: uploaded_at.clone(),
-        source: None,
-        license: None,
-        uploader: payload.uploader.clone().or_else(|| Some(mask_token(&token))),
+    update_index(|idx| {
+        idx.retain(|e| e.filename != target.file_name().and_then(|s| s.to_str()).unwrap_or(""));
+        idx.push(AssetIndexEntry {
+            filename: target.file_name().and_then(|s| s.to_str()).unwrap_or("").to_string(),
+            size,
+            thumb: true,
+            phash: Some(phash.clone()),
+            uploaded_at: uploaded_at.clone(),
+            source: None,
+            license: None,
+            uploader: payload.uploader.clone().or_else(|| Some(mask_token(&token))),
+            clear_example: false,
+            annotations: Vec::new(),
+        });
     });
-    save_index(&idx);
 
-    Json(AdminUploadResult { ok: true, saved_filename: target.file_name().and_then(|s| s.to_str()).map(|s| s.to_string()), thumb_filename: thumb_path.file_name().and_then(|s| s.to_str()).map(|s| s.to_string()), message: None })
+    Ok(Json(AdminUploadResult { ok: true, saved_filename: target.file_name().and_then(|s| s.to_str()).map(|s| s.to_string()), thumb_filename: thumb_path.file_name().and_then(|s| s.to_str()).map(|s| s.to_string()) }))
 }
 
 // multipart upload handler (form submit)
-async fn admin_upload_multipart(headers: HeaderMap, Query(q): Query<StdHashMap<String, String>>, mut multipart: Multipart) -> Json<AdminUploadResult> {
+async fn admin_upload_multipart(headers: HeaderMap, ApiQuery(q): ApiQuery<StdHashMap<String, String>>, mut multipart: Multipart) -> Result<Json<AdminUploadResult>, ApiError> {
     // uploads are gated by ENABLE_ADMIN_UPLOADS env var (disabled by default)
     if !uploads_enabled() {
-        return Json(AdminUploadResult { ok: false, saved_filename: None, thumb_filename: None, message: Some("uploads are disabled in this environment (ENABLE_ADMIN_UPLOADS=false)".to_string()) });
+        return Err(ApiError::forbidden("uploads are disabled in this environment (ENABLE_ADMIN_UPLOADS=false)"));
     }
 
     // prefer Authorization header, fallback to query ?token=
-    let header_token = token_from_headers(&headers);
-    let token = header_token.or_else(|| q.get("token").cloned()).unwrap_or_default();
-    if !check_admin_token_token(&token) {
-        return Json(AdminUploadResult { ok: false, saved_filename: None, thumb_filename: None, message: Some("unauthorized".to_string()) });
-    }
+    let token = require_admin(&headers, Some(&q))?;
 
     // collect fields and file bytes
     let mut collected_bytes: Option<Vec<u8>> = None;
@@ -418,7 +1840,8 @@ async fn admin_upload_multipart(headers: HeaderMap, Query(q): Query<StdHashMap<S
     let mut rights_confirmed: bool = false;
     let mut uploader_field: Option<String> = None;
 
-    while let Some(field) = multipart.next_field().await.unwrap_or(None) {
+    // a body over MAX_UPLOAD_BYTES surfaces here as a 413
+    while let Some(field) = multipart.next_field().await? {
         let name = field.name().map(|s| s.to_string()).unwrap_or_default();
         if field.file_name().is_some() {
             let filename = field.file_name().unwrap().to_string();
@@ -426,7 +1849,7 @@ async fn admin_upload_multipart(headers: HeaderMap, Query(q): Query<StdHashMap<S
             let safe: String = filename.chars().map(|c| if c.is_ascii_alphanumeric() || c == '.' || c == '-' || c == '_' { c } else { '_' }).collect();
             match field.bytes().await {
                 Ok(d) => { collected_bytes = Some(d.to_vec()); collected_filename = Some(safe); }
-                Err(e) => return Json(AdminUploadResult { ok: false, saved_filename: None, thumb_filename: None, message: Some(format!("read field error: {}", e)) }),
+                Err(e) => return Err(e.into()),
             }
         } else {
             if name == "rights_confirmed" {
@@ -442,23 +1865,26 @@ async fn admin_upload_multipart(headers: HeaderMap, Query(q): Query<StdHashMap<S
 
     let data = match collected_bytes {
         Some(d) => d,
-        None => return Json(AdminUploadResult { ok: false, saved_filename: None, thumb_filename: None, message: Some("no file field found".to_string()) }),
+        None => return Err(ApiError::bad_request("no file field found")),
     };
+    if data.len() > max_upload_bytes() {
+        return Err(ApiError::payload_too_large(format!("image is larger than {} bytes", max_upload_bytes())));
+    }
 
     if !rights_confirmed {
-        return Json(AdminUploadResult { ok: false, saved_filename: None, thumb_filename: None, message: Some("upload rejected: uploader must confirm they have rights to use this image".to_string()) });
+        return Err(ApiError::bad_request("upload rejected: uploader must confirm they have rights to use this image"));
     }
 
     let filename = collected_filename.unwrap_or_else(|| format!("upload-{}.png", chrono::Utc::now().timestamp()));
     // validate image
     let img_dyn = match image::load_from_memory(&data) {
         Ok(d) => d,
-        Err(e) => return Json(AdminUploadResult { ok: false, saved_filename: None, thumb_filename: None, message: Some(format!("invalid image data: {}", e)) }),
+        Err(e) => return Err(ApiError::bad_request(format!("invalid image data: {}", e))),
     };
 
     let assets_dir = PathBuf::from("public").join("assets");
     let thumbs_dir = assets_dir.join("thumbs");
-    if let Err(e) = std::fs::create_dir_all(&thumbs_dir) { return Json(AdminUploadResult { ok: false, saved_filename: None, thumb_filename: None, message: Some(format!("mkdir error: {}", e)) }); }
+    if let Err(e) = std::fs::create_dir_all(&thumbs_dir) { return Err(ApiError::internal(format!("mkdir error: {}", e))); }
 
     let mut target = assets_dir.join(&filename);
     let mut counter = 1;
@@ -471,31 +1897,33 @@ async fn admin_upload_multipart(headers: HeaderMap, Query(q): Query<StdHashMap<S
         counter += 1;
     }
 
-    if let Err(e) = std::fs::write(&target, &data) { return Json(AdminUploadResult { ok: false, saved_filename: None, thumb_filename: None, message: Some(format!("write error: {}", e)) }); }
+    if let Err(e) = std::fs::write(&target, &data) { return Err(ApiError::internal(format!("write error: {}", e))); }
 
     let thumb = img_dyn.thumbnail(320, 240).to_rgba8();
     let thumb_path = thumbs_dir.join(target.file_name().and_then(|s| s.to_str()).unwrap_or("thumb.png"));
-    if let Err(e) = thumb.save(&thumb_path) { return Json(AdminUploadResult { ok: false, saved_filename: target.file_name().and_then(|s| s.to_str()).map(|s| s.to_string()), thumb_filename: None, message: Some(format!("thumbnail save error: {}", e)) }); }
+    if let Err(e) = thumb.save(&thumb_path) { return Err(ApiError::internal(format!("thumbnail save error: {}", e))); }
 
     // compute phash and update index
     let phash = compute_ahash(&img_dyn);
     let size = target.metadata().map(|m| m.len()).unwrap_or(0);
     let uploaded_at = chrono::Utc::now().to_rfc3339();
-    let mut idx = load_index();
-    idx.retain(|e| e.filename != target.file_name().and_then(|s| s.to_str()).unwrap_or(""));
-    idx.push(AssetIndexEntry {
-        filename: target.file_name().and_then(|s| s.to_str()).unwrap_or("").to_string(),
-        size,
-        thumb: true,
-        phash: Some(phash.clone()),
-        uploaded_at: uploaded_at.clone(),
-        source: None,
-        license: None,
-        uploader: uploader_field.or_else(|| Some(mask_token(&token))),
+    update_index(|idx| {
+        idx.retain(|e| e.filename != target.file_name().and_then(|s| s.to_str()).unwrap_or(""));
+        idx.push(AssetIndexEntry {
+            filename: target.file_name().and_then(|s| s.to_str()).unwrap_or("").to_string(),
+            size,
+            thumb: true,
+            phash: Some(phash.clone()),
+            uploaded_at: uploaded_at.clone(),
+            source: None,
+            license: None,
+            uploader: uploader_field.or_else(|| Some(mask_token(&token))),
+            clear_example: false,
+            annotations: Vec::new(),
+        });
     });
-    save_index(&idx);
 
-    Json(AdminUploadResult { ok: true, saved_filename: target.file_name().and_then(|s| s.to_str()).map(|s| s.to_string()), thumb_filename: thumb_path.file_name().and_then(|s| s.to_str()).map(|s| s.to_string()), message: None })
+    Ok(Json(AdminUploadResult { ok: true, saved_filename: target.file_name().and_then(|s| s.to_str()).map(|s| s.to_string()), thumb_filename: thumb_path.file_name().and_then(|s| s.to_str()).map(|s| s.to_string()) }))
 }
 
 fn token_from_headers(headers: &HeaderMap) -> Option<String> {
@@ -515,11 +1943,23 @@ fn mask_token(token: &str) -> String {
     if t.len() <= 8 { t.to_string() } else { format!("{}...{}", &t[..4], &t[t.len()-4..]) }
 }
 
+// admin auth: Authorization: Bearer <token>, falling back to ?token= where the caller passes the query
+fn require_admin(headers: &HeaderMap, q: Option<&StdHashMap<String, String>>) -> Result<String, ApiError> {
+    let token = token_from_headers(headers).or_else(|| q.and_then(|q| q.get("token").cloned())).unwrap_or_default();
+    if !check_admin_token_token(&token) { return Err(ApiError::unauthorized("invalid admin token")); }
+    Ok(token)
+}
+
 fn check_admin_token_token(token: &str) -> bool {
     let expected = env::var("ADMIN_TOKEN").unwrap_or_else(|_| "admin-token".to_string());
     token == expected
 }
 
+// MAX_UPLOAD_BYTES (default 10 MiB) caps admin upload request bodies; larger requests get 413
+fn max_upload_bytes() -> usize {
+    env::var("MAX_UPLOAD_BYTES").ok().and_then(|s| s.parse().ok()).unwrap_or(10 * 1024 * 1024)
+}
+
 fn uploads_enabled() -> bool {
     // By default uploads are disabled in production. Set ENABLE_ADMIN_UPLOADS=true to allow.
     match env::var("ENABLE_ADMIN_UPLOADS") {
@@ -530,16 +1970,12 @@ fn uploads_enabled() -> bool {
 
 async fn populate_assets_from_commons() -> Result<(), String> {
     // This function will attempt to download a small curated set of images
-    // from Wikimedia Commons for categories tanuki/anaguma/hakubishin.
+    // from Wikimedia Commons for every species in the registry, using its search terms.
     // It only runs when triggered via env AUTO_POPULATE_ASSETS=true.
 
     let client = Client::builder().user_agent("tanuki-quiz/1.0 (contact: maintainers)").build().map_err(|e| format!("client build error: {}", e))?;
 
-    let categories = vec![
-        ("tanuki", vec!["Nyctereutes procyonoides", "raccoon dog", "狸"]),
-        ("anaguma", vec!["Japanese badger", "Meles anakuma", "あなぐま", "badger"]),
-        ("hakubishin", vec!["Paguma larvata", "masked palm civet", "ハクビシン"]),
-    ];
+    let categories: Vec<(String, Vec<String>)> = species::all().into_iter().map(|sp| (sp.key, sp.search_terms)).collect();
 
     let assets_dir = PathBuf::from("public").join("assets");
     let thumbs_dir = assets_dir.join("thumbs");
@@ -550,7 +1986,7 @@ async fn populate_assets_from_commons() -> Result<(), String> {
         let mut found = false;
         for term in terms {
             // build query to Wikimedia Commons API
-            let api = format!("https://commons.wikimedia.org/w/api.php?action=query&format=json&generator=search&gsrsearch=filetype:bitmap%20{}&gsrlimit=5&prop=imageinfo&iiprop=url|extmetadata", urlencoding::encode(term));
+            let api = format!("https://commons.wikimedia.org/w/api.php?action=query&format=json&generator=search&gsrsearch=filetype:bitmap%20{}&gsrlimit=5&prop=imageinfo&iiprop=url|extmetadata", urlencoding::encode(&term));
             let res = client.get(&api).send().await.map_err(|e| format!("api request error: {}", e))?;
             if !res.status().is_success() { continue; }
             let v: Value = res.json().await.map_err(|e| format!("json parse error: {}", e))?;
@@ -582,19 +2018,21 @@ async fn populate_assets_from_commons() -> Result<(), String> {
                                 let phash = compute_ahash(&img_dyn);
                                 let size = target.metadata().map(|m| m.len()).unwrap_or(0);
                                 let uploaded_at = chrono::Utc::now().to_rfc3339();
-                                let mut idx = load_index();
-                                idx.retain(|e| e.filename != filename);
-                                idx.push(AssetIndexEntry {
-                                    filename: filename.clone(),
-                                    size,
-                                    thumb: true,
-                                    phash: Some(phash.clone()),
-                                    uploaded_at: uploaded_at.clone(),
-                                    source: Some(url.to_string()),
-                                    license: Some(license.clone()),
-                                    uploader: Some("wikimedia-auto".to_string()),
+                                update_index(|idx| {
+                                    idx.retain(|e| e.filename != filename);
+                                    idx.push(AssetIndexEntry {
+                                        filename: filename.clone(),
+                                        size,
+                                        thumb: true,
+                                        phash: Some(phash.clone()),
+                                        uploaded_at: uploaded_at.clone(),
+                                        source: Some(url.to_string()),
+                                        license: Some(license.clone()),
+                                        uploader: Some("wikimedia-auto".to_string()),
+                                        clear_example: false,
+                                        annotations: Vec::new(),
+                                    });
                                 });
-                                save_index(&idx);
                                 found = true;
                                 break;
                             }
@@ -614,7 +2052,41 @@ async fn populate_assets_from_commons() -> Result<(), String> {
 
 // proxy handler removed to avoid heavy dependencies; the client will load external Unsplash URLs directly
 
-async fn serve_image(Path(name): Path<String>) -> impl IntoResponse {
+// A stored photo or its thumbnail (?thumb=1) for the admin page, e.g. /api/admin/assets/<file>?token=<admin token>.
+// public/assets is not served statically: file names give the species away.
+async fn admin_asset(headers: HeaderMap, ApiQuery(q): ApiQuery<StdHashMap<String, String>>, ApiPath(filename): ApiPath<String>) -> Result<impl IntoResponse, ApiError> {
+    require_admin(&headers, Some(&q))?;
+    let not_found = || ApiError::not_found(format!("no photo {}", filename));
+    if filename.is_empty() || filename.starts_with('.') || filename.contains('/') || filename.contains('\\') { return Err(not_found()); }
+    let assets_dir = PathBuf::from("public").join("assets");
+    let path = if q.get("thumb").is_some_and(|v| v == "1") { assets_dir.join("thumbs").join(&filename) } else { assets_dir.join(&filename) };
+    let content_type = match path.extension().and_then(|e| e.to_str()).map(|e| e.to_lowercase()).as_deref() {
+        Some("jpg") | Some("jpeg") => "image/jpeg",
+        Some("png") => "image/png",
+        _ => return Err(not_found()),
+    };
+    let bytes = tokio::fs::read(&path).await.map_err(|_| not_found())?;
+    Ok(([(header::CONTENT_TYPE, content_type), (header::CACHE_CONTROL, "private, no-store")], Bytes::from(bytes)))
+}
+
+// /assets/<path>: only the photo credits and photos the static catalog shows (its answers are public anyway).
+// Quiz photos, thumbnails and index.json stay on the server.
+async fn public_asset(ApiPath(path): ApiPath<String>) -> Result<impl IntoResponse, ApiError> {
+    let not_found = || ApiError::not_found("not found");
+    if path != "LICENSES.md" && !catalog::references_asset(&path) { return Err(not_found()); }
+    let file = PathBuf::from("public").join("assets").join(&path);
+    let content_type = match file.extension().and_then(|e| e.to_str()).map(|e| e.to_lowercase()).as_deref() {
+        Some("md") => "text/markdown; charset=utf-8",
+        Some("png") => "image/png",
+        Some("gif") => "image/gif",
+        Some("webp") => "image/webp",
+        _ => "image/jpeg",
+    };
+    let bytes = tokio::fs::read(&file).await.map_err(|_| not_found())?;
+    Ok(([(header::CONTENT_TYPE, content_type)], Bytes::from(bytes)))
+}
+
+async fn serve_image(ApiPath(name): ApiPath<String>) -> impl IntoResponse {
     // name could be like "tanuki1.png"; strip extension if present
     let key = name.split('.').next().unwrap_or(&name).to_string();
     match generate_image_bytes(&key) {
@@ -623,7 +2095,7 @@ async fn serve_image(Path(name): Path<String>) -> impl IntoResponse {
             headers.insert(header::CONTENT_TYPE, header::HeaderValue::from_static("image/png"));
             (axum::http::StatusCode::OK, headers, Bytes::from(bytes)).into_response()
         }
-        Err(_) => (axum::http::StatusCode::NOT_FOUND).into_response(),
+        Err(_) => ApiError::not_found(format!("no image for {}", name)).into_response(),
     }
 }
 
@@ -633,16 +2105,11 @@ fn generate_image_bytes(key: &str) -> Result<Vec<u8>, ()> {
     let height = 600u32;
     let mut img: ImageBuffer<Rgba<u8>, Vec<u8>> = ImageBuffer::new(width, height);
 
-    // Background and primary face color based on key prefix
-    let (bg, face) = if key.starts_with("tanuki") {
-        (Rgba([0xFF, 0xD7, 0x00, 0xFF]), Rgba([0xFF, 0xF5, 0xE6, 0xFF])) // gold bg
-    } else if key.starts_with("anaguma") {
-        (Rgba([0xA9, 0xA9, 0xA9, 0xFF]), Rgba([0xF5, 0xF5, 0xF5, 0xFF])) // gray
-    } else if key.starts_with("hakubishin") {
-        (Rgba([0x8B, 0x45, 0x13, 0xFF]), Rgba([0xFF, 0xFF, 0xFF, 0xFF])) // brown
-    } else {
-        (Rgba([0xDD, 0xDD, 0xDD, 0xFF]), Rgba([0xFF, 0xFF, 0xFF, 0xFF]))
-    };
+    // Background, face and mask colors come from the species matching the key prefix
+    let render = species::for_image_key(key).map(|sp| sp.render);
+    let bg = render.as_ref().and_then(|r| species::parse_color(&r.background)).unwrap_or(Rgba([0xDD, 0xDD, 0xDD, 0xFF]));
+    let face = render.as_ref().and_then(|r| species::parse_color(&r.face)).unwrap_or(Rgba([0xFF, 0xFF, 0xFF, 0xFF]));
+    let mask_stripe = render.as_ref().and_then(|r| r.mask_stripe.as_deref()).and_then(species::parse_color);
 
     // fill background
     for pixel in img.pixels_mut() {
@@ -661,9 +2128,8 @@ fn generate_image_bytes(key: &str) -> Result<Vec<u8>, ()> {
     draw_filled_circle(&mut img, cx + r/3, cy - r/6, r/10, eye);
 
     // species-specific mark
-    if key.starts_with("hakubishin") {
+    if let Some(stripe) = mask_stripe {
         // a mask stripe across face
-        let stripe = Rgba([0x80, 0x80, 0x80, 0xFF]);
         for y in (cy - r/6)..=(cy + r/8) {
             for x in (cx - r/2)..=(cx + r/2) {
                 if in_circle(cx, cy, r, x, y) {
@@ -703,37 +2169,92 @@ fn draw_filled_circle(img: &mut ImageBuffer<Rgba<u8>, Vec<u8>>, cx: i32, cy: i32
     }
 }
 
-async fn get_quiz_question() -> Json<QuizQuestion> {
-    let questions = get_all_questions();
-    let question = questions.choose(&mut rand::thread_rng()).unwrap();
-    Json(question.clone())
+// static catalog answers are species names; they are shown in the request locale
+fn localized_answer(answer: &str, lang: &str) -> String {
+    species::by_name(answer).map(|sp| sp.name(lang).to_string()).unwrap_or_else(|| answer.to_string())
 }
 
-async fn submit_answer(Json(payload): Json<QuizAnswer>) -> Json<QuizResult> {
-    let questions = get_all_questions();
-    let question = questions.iter().find(|q| q.id == payload.id).unwrap();
-    let correct = question.answer == payload.answer;
-    Json(QuizResult {
-        correct,
-        correct_answer: question.answer.clone(),
-    })
+async fn get_quiz_question(Locale(lang): Locale) -> Result<Json<QuizQuestion>, ApiError> {
+    let questions = catalog::all_questions();
+    // empty only when the catalog file failed to load at startup
+    let question = questions.choose(&mut rand::thread_rng()).ok_or_else(|| ApiError::unavailable(i18n::t(lang, "error.catalog_not_loaded")))?;
+    let mut question = question.clone();
+    question.answer = localized_answer(&question.answer, lang);
+    Ok(Json(question))
 }
 
-async fn submit_generated(Json(payload): Json<GeneratedSubmit>) -> Json<QuizResult> {
-    // lookup quiz by id
-    let mut store = QUIZ_STORE.lock();
-    if let Some((stored_quiz, _)) = store.remove(&payload.quiz_id) {
-        let correct = payload.selected_category == stored_quiz.answer_category;
-        Json(QuizResult {
-            correct,
-            correct_answer: stored_quiz.answer_category.clone(),
-        })
+async fn submit_answer(Locale(lang): Locale, ApiJson(payload): ApiJson<QuizAnswer>) -> Result<Json<QuizResult>, ApiError> {
+    let question = catalog::find_question(payload.id).ok_or_else(|| ApiError::not_found(i18n::tf(lang, "error.question_not_found", &[("id", &payload.id.to_string())])))?;
+    let sp = species::by_name(&question.answer);
+    // a species name in any language is accepted
+    let correct = question.answer == payload.answer || sp.as_ref().map(|sp| sp.names.values().any(|n| *n == payload.answer)).unwrap_or(false);
+    Ok(Json(QuizResult::new(correct, localized_answer(&question.answer, lang), sp, lang)))
+}
+
+async fn submit_generated(State(state): State<AppState>, Locale(lang): Locale, ApiJson(payload): ApiJson<GeneratedSubmit>) -> Result<Json<QuizResult>, ApiError> {
+    // a retry after a lost response gets the result it missed
+    if let Some(receipt) = state.quizzes.receipt(&payload.quiz_id, QuizKind::Choice).await { return replay_receipt(receipt, &payload.choice, lang); }
+    // lookup quiz by id; an unknown token is a client error and must not use up the quiz
+    let selected = match state.quizzes.get(&payload.quiz_id).await {
+        Some(stored) if stored.quiz.kind == QuizKind::Choice => match stored.quiz.choices.into_iter().find(|c| c.token == payload.choice) {
+            Some(c) => Some(c),
+            None => return Err(ApiError::bad_request(i18n::t(lang, "error.unknown_choice"))),
+        },
+        _ => None,
+    };
+    // timed on the server's clock from when the quiz was stored, never the client's
+    let found = selected.is_some();
+    let taken = if found { state.quizzes.take(&payload.quiz_id, Some(Receipt::pending(QuizKind::Choice, &payload.choice))).await } else { None };
+    let removed = selected.zip(taken).map(|(c, stored)| {
+        let elapsed = stored.elapsed(chrono::Utc::now());
+        (stored.id, stored.quiz, c, elapsed)
+    });
+    if let Some((quiz_id, stored_quiz, selected, elapsed)) = removed {
+        let mut record = AnswerRecord {
+            session: stored_quiz.session_id.clone(),
+            player_id: stored_quiz.player_id.clone(),
+            response_ms: Some(elapsed.as_millis() as u64),
+            ..AnswerRecord::choice("quiz", quiz_id.clone(), &stored_quiz.answer_category, &stored_quiz.choices, Some(&selected))
+        };
+        if past_deadline(&stored_quiz, elapsed) {
+            record.correct = false;
+            answer_log::log(vec![record]);
+            // refused rather than scored: a retry must not wait on a result that never comes
+            state.quizzes.drop_receipt(&payload.quiz_id).await;
+            // the round is used up either way, so a session can still finish
+            if let Some(sid) = &stored_quiz.session_id { session::record_answer(sid, &quiz_id, &stored_quiz.answer_category, session::Outcome::TimedOut); }
+            return Err(ApiError::conflict(i18n::tf(lang, "error.answer_too_late", &[("ms", &elapsed.as_millis().to_string())])));
+        }
+        let correct = selected.category == stored_quiz.answer_category;
+        let outcome = session::Outcome::Answered { correct, response: elapsed };
+        let session = stored_quiz.session_id.as_deref().and_then(|sid| session::record_answer(sid, &quiz_id, &stored_quiz.answer_category, outcome));
+        if let Some(pid) = stored_quiz.player_id.clone() {
+            let target = stored_quiz.answer_category.clone();
+            let target_files: Vec<String> = stored_quiz.choices.iter().filter(|c| c.category == target).filter_map(|c| c.file.clone()).collect();
+            let (picked, picked_file) = (selected.category.clone(), selected.file.clone());
+            // a read-modify-write of the player's history file, so it runs on the blocking pool
+            tokio::task::spawn_blocking(move || {
+                let answer = review::Answer { target: &target, target_files: target_files.iter().map(String::as_str).collect(), picked: &picked, picked_file: picked_file.as_deref() };
+                if let Err(e) = review::record(&pid, &answer) { eprintln!("player history write failed: {}", e); }
+            });
+        }
+        record_image_outcomes(choice_outcomes(&stored_quiz.choices, &stored_quiz.answer_category, Some(&selected)));
+        answer_log::log(vec![record]);
+        let sp = species::get(&stored_quiz.answer_category);
+        let mut result = QuizResult::new(correct, stored_quiz.answer_category.clone(), sp.clone(), lang);
+        result.correct_label = sp.map(|sp| sp.name(lang).to_string());
+        result.response_ms = Some(elapsed.as_millis() as u64);
+        // only sessions keep score; the same rule as session::Session::record
+        if let (Some(ms), true) = (stored_quiz.time_limit_ms, session.is_some()) {
+            result.speed_bonus = Some(if correct { session::speed_bonus(elapsed, Duration::from_millis(ms)) } else { 0 });
+        }
+        result.session = session;
+        keep_receipt(&state, &payload.quiz_id, QuizKind::Choice, &payload.choice, &result).await;
+        Ok(Json(result))
     } else {
-        // missing or expired quiz — treat as incorrect but provide a generic response
-        Json(QuizResult {
-            correct: false,
-            correct_answer: "unknown".to_string(),
-        })
+        // a concurrent duplicate took it first; wait for its result
+        if found { if let Some(receipt) = state.quizzes.raced_receipt(&payload.quiz_id, QuizKind::Choice).await { return replay_receipt(receipt, &payload.choice, lang); } }
+        Ok(Json(QuizResult::unknown(lang)))
     }
 }
 
@@ -744,19 +2265,20 @@ struct AdminListEntry {
     thumb: bool,
     uploaded_at: Option<String>,
     uploader: Option<String>,
+    clear_example: bool,
+    // answer statistics; None until the photo has been shown
+    stats: Option<ImageStats>,
 }
 
-async fn admin_list(headers: HeaderMap, Query(q): Query<StdHashMap<String, String>>) -> Json<Vec<AdminListEntry>> {
-    let header_token = token_from_headers(&headers);
-    let token = header_token.or_else(|| q.get("token").cloned()).unwrap_or_default();
-    if !check_admin_token_token(&token) { return Json(vec![]); }
+async fn admin_list(headers: HeaderMap, ApiQuery(q): ApiQuery<StdHashMap<String, String>>) -> Result<Json<Vec<AdminListEntry>>, ApiError> {
+    require_admin(&headers, Some(&q))?;
     let assets_dir = PathBuf::from("public").join("assets");
     let mut out = Vec::new();
     // enrich with index.json if present
     let index = load_index();
     if !index.is_empty() {
         for e in index {
-            out.push(AdminListEntry { filename: e.filename.clone(), size: e.size, thumb: e.thumb, uploaded_at: Some(e.uploaded_at.clone()), uploader: e.uploader.clone() });
+            out.push(AdminListEntry { stats: image_stats::get(&e.filename), filename: e.filename.clone(), size: e.size, thumb: e.thumb, uploaded_at: Some(e.uploaded_at.clone()), uploader: e.uploader.clone(), clear_example: e.clear_example });
         }
     } else {
         if let Ok(entries) = std::fs::read_dir(&assets_dir) {
@@ -765,22 +2287,21 @@ async fn admin_list(headers: HeaderMap, Query(q): Query<StdHashMap<String, Strin
                     if let Some(name) = e.file_name().to_str() {
                         // skip thumbs directory
                         if name == "thumbs" { continue; }
-                        out.push(AdminListEntry { filename: name.to_string(), size: mt.len(), thumb: PathBuf::from("public").join("assets").join("thumbs").join(name).exists(), uploaded_at: None, uploader: None });
+                        out.push(AdminListEntry { filename: name.to_string(), size: mt.len(), thumb: PathBuf::from("public").join("assets").join("thumbs").join(name).exists(), uploaded_at: None, uploader: None, clear_example: false, stats: image_stats::get(name) });
                     }
                 }
             }
         }
     }
-    Json(out)
+    Ok(Json(out))
 }
 
 #[derive(Deserialize)]
 struct AdminDeleteReq { filename: String }
 
-async fn admin_delete(headers: HeaderMap, Query(q): Query<StdHashMap<String, String>>, Json(payload): Json<AdminDeleteReq>) -> Json<AdminUploadResult> {
-    let header_token = token_from_headers(&headers);
-    let token = header_token.or_else(|| q.get("token").cloned()).unwrap_or_default();
-    if !check_admin_token_token(&token) { return Json(AdminUploadResult { ok: false, saved_filename: None, thumb_filename: None, message: Some("unauthorized".to_string()) }); }
+async fn admin_delete(headers: HeaderMap, ApiQuery(q): ApiQuery<StdHashMap<String, String>>, ApiJson(payload): ApiJson<AdminDeleteReq>) -> Result<Json<AdminUploadResult>, ApiError> {
+    require_admin(&headers, Some(&q))?;
+    if payload.filename.contains('/') || payload.filename.contains('\\') { return Err(ApiError::bad_request("invalid filename")); }
     let assets_dir = PathBuf::from("public").join("assets");
     let target = assets_dir.join(&payload.filename);
     let thumb = assets_dir.join("thumbs").join(&payload.filename);
@@ -793,25 +2314,448 @@ async fn admin_delete(headers: HeaderMap, Query(q): Query<StdHashMap<String, Str
     if thumb_existed {
         let _ = std::fs::remove_file(&thumb);
     }
+    media::purge(&payload.filename);
+    image_stats::remove(&payload.filename);
 
     if target_existed {
         // update index.json to remove any entry for this filename
-        let mut idx = load_index();
-        let before = idx.len();
-        idx.retain(|e| e.filename != payload.filename);
-        if idx.len() != before {
-            save_index(&idx);
-        }
+        update_index(|idx| idx.retain(|e| e.filename != payload.filename));
 
-        Json(AdminUploadResult {
+        Ok(Json(AdminUploadResult {
             ok: true,
             saved_filename: Some(payload.filename.clone()),
             thumb_filename: if thumb_existed { Some(payload.filename.clone()) } else { None },
-            message: None,
-        })
+        }))
     } else {
-        Json(AdminUploadResult { ok: false, saved_filename: None, thumb_filename: None, message: Some("not found".to_string()) })
+        Err(ApiError::not_found("not found"))
+    }
+}
+
+#[derive(Deserialize)]
+struct AdminMarkClearReq { filename: String, clear: bool }
+
+// mark or unmark an asset as a clear example for easy quizzes; assets missing from index.json get an entry
+async fn admin_mark_clear(headers: HeaderMap, ApiQuery(q): ApiQuery<StdHashMap<String, String>>, ApiJson(payload): ApiJson<AdminMarkClearReq>) -> Result<Json<AdminUploadResult>, ApiError> {
+    require_admin(&headers, Some(&q))?;
+    if payload.filename.contains('/') || payload.filename.contains('\\') {
+        return Err(ApiError::bad_request("invalid filename"));
+    }
+    let target = PathBuf::from("public").join("assets").join(&payload.filename);
+    if !target.is_file() { return Err(ApiError::not_found(format!("{} not found", payload.filename))); }
+    update_index(|idx| match idx.iter_mut().find(|e| e.filename == payload.filename) {
+        Some(e) => e.clear_example = payload.clear,
+        None => idx.push(AssetIndexEntry { clear_example: payload.clear, ..new_index_entry(&payload.filename) }),
+    });
+    Ok(Json(AdminUploadResult { ok: true, saved_filename: Some(payload.filename.clone()), thumb_filename: None }))
+}
+
+#[derive(Deserialize)]
+struct AdminAnnotationsReq { filename: String, annotations: Vec<hotspot::Annotation> }
+
+fn annotated_asset(filename: &str) -> Result<(), ApiError> {
+    if filename.contains('/') || filename.contains('\\') {
+        return Err(ApiError::bad_request("invalid filename"));
+    }
+    if !PathBuf::from("public").join("assets").join(filename).is_file() { return Err(ApiError::not_found(format!("{} not found", filename))); }
+    Ok(())
+}
+
+// ?filename=<asset>
+async fn admin_get_annotations(headers: HeaderMap, ApiQuery(q): ApiQuery<StdHashMap<String, String>>) -> Result<Json<Vec<hotspot::Annotation>>, ApiError> {
+    require_admin(&headers, Some(&q))?;
+    let filename = q.get("filename").ok_or_else(|| ApiError::bad_request("filename is required"))?;
+    annotated_asset(filename)?;
+    Ok(Json(cached_index().iter().find(|e| &e.filename == filename).map(|e| e.annotations.clone()).unwrap_or_default()))
+}
+
+// replace every region on a photo; an empty list removes them; assets missing from index.json get an entry
+async fn admin_set_annotations(headers: HeaderMap, ApiQuery(q): ApiQuery<StdHashMap<String, String>>, ApiJson(payload): ApiJson<AdminAnnotationsReq>) -> Result<Json<Vec<hotspot::Annotation>>, ApiError> {
+    require_admin(&headers, Some(&q))?;
+    annotated_asset(&payload.filename)?;
+    let errors = hotspot::validate(&payload.annotations);
+    if !errors.is_empty() { return Err(ApiError::bad_request("annotations are invalid").with_details(errors)); }
+    let annotations = payload.annotations;
+    update_index(|idx| match idx.iter_mut().find(|e| e.filename == payload.filename) {
+        Some(e) => e.annotations = annotations.clone(),
+        None => idx.push(AssetIndexEntry { annotations: annotations.clone(), ..new_index_entry(&payload.filename) }),
+    });
+    Ok(Json(annotations))
+}
+
+#[derive(Serialize)]
+struct AdminQuizReplay {
+    record: quiz_log::QuizRecord,
+    answer_category: String,
+    // server-side view of every choice, including the answer
+    choices: Vec<GeneratedChoice>,
+    // the feature and its regions, for hotspot quizzes
+    #[serde(skip_serializing_if = "Option::is_none")]
+    hotspot: Option<hotspot::Target>,
+    // false when the registry or assets changed since the quiz was generated
+    matches_original: bool,
+}
+
+// regenerate a logged quiz from its seed and parameters
+// the quiz log is scanned line by line, so the lookup runs off the async workers
+async fn find_logged_quiz(id: &str) -> Result<quiz_log::QuizRecord, ApiError> {
+    let lookup = id.to_string();
+    tokio::task::spawn_blocking(move || quiz_log::find(&lookup)).await
+        .map_err(|e| ApiError::internal(e.to_string()))?
+        .ok_or_else(|| ApiError::not_found(format!("quiz {} is not in the quiz log", id)))
+}
+
+// the choices, the answer and, for hotspot quizzes, the feature asked for
+fn replay_quiz(record: &quiz_log::QuizRecord) -> Option<(Vec<GeneratedChoice>, String, Option<hotspot::Target>)> {
+    let difficulty = Difficulty::parse(&record.difficulty)?;
+    let mut rng = StdRng::seed_from_u64(record.seed);
+    match QuizKind::parse(&record.kind)? {
+        QuizKind::Choice => pick_choices(difficulty, &mut rng, record.weights.as_ref()).map(|(choices, target)| (choices, target, None)),
+        QuizKind::TanukiOrNot => {
+            let choice = pick_tanuki_or_not(difficulty, record.tanuki_ratio.unwrap_or(0.5), &mut rng)?;
+            let shown = choice.category.clone();
+            Some((vec![choice], shown, None))
+        }
+        QuizKind::Hotspot => pick_hotspot(&mut rng).map(|(choice, target)| {
+            let shown = choice.category.clone();
+            (vec![choice], shown, Some(target))
+        }),
+        // the logged frame; records from before frames were logged give the sharp photo
+        QuizKind::Reveal => pick_reveal(&mut rng).map(|(mut choice, _)| {
+            let crop = reveal::random_crop(&mut rng);
+            if let (Some(style), Some(step)) = (record.style, record.step) {
+                if step > 0 { choice.token = reveal::step_token(record.seed, step); }
+                choice.degrade = Some(reveal::frame(style, step, crop));
+            }
+            let shown = choice.category.clone();
+            (vec![choice], shown, None)
+        }),
+    }
+}
+
+async fn admin_quiz_replay(headers: HeaderMap, ApiQuery(q): ApiQuery<StdHashMap<String, String>>, ApiPath(id): ApiPath<String>) -> Result<Json<AdminQuizReplay>, ApiError> {
+    require_admin(&headers, Some(&q))?;
+    let record = find_logged_quiz(&id).await?;
+    let (choices, answer_category, hotspot) = replay_quiz(&record).ok_or_else(|| ApiError::conflict(format!("quiz {} can no longer be generated from its seed", id)))?;
+    let tokens: Vec<String> = choices.iter().map(|c| c.token.clone()).collect();
+    let matches_original = tokens == record.tokens && answer_category == record.answer_category
+        && hotspot.as_ref().map(|t| &t.feature) == record.feature.as_ref();
+    Ok(Json(AdminQuizReplay { record, answer_category, choices, hotspot, matches_original }))
+}
+
+// image of a regenerated choice, e.g. /api/admin/quiz/<id>/image/<token>?token=<admin token>
+async fn admin_quiz_replay_image(headers: HeaderMap, ApiQuery(q): ApiQuery<StdHashMap<String, String>>, ApiPath((id, token)): ApiPath<(String, String)>) -> Result<impl IntoResponse, ApiError> {
+    require_admin(&headers, Some(&q))?;
+    let record = find_logged_quiz(&id).await?;
+    let (choices, _, _) = replay_quiz(&record).ok_or_else(|| ApiError::conflict(format!("quiz {} can no longer be generated from its seed", id)))?;
+    let choice = choices.iter().find(|c| c.token == token).ok_or_else(|| ApiError::not_found(format!("no choice {} in regenerated quiz {}", token, id)))?;
+    choice_image_response(choice.clone(), &id, image_transform(&q, "en")?).await
+}
+
+#[derive(Serialize)]
+struct ImageStatsEntry {
+    filename: String,
+    species: Option<String>,
+    shown: u64,
+    correct: u64,
+    accuracy: f64,
+    wrong_picks: BTreeMap<String, u64>,
+    mistaken_for: BTreeMap<String, u64>,
+}
+
+// answer statistics per photo, least often answered correctly first, to spot misleading or
+// mislabeled photos. ?min_shown=<n> (default 1) hides photos with too few answers to judge.
+async fn admin_image_stats(headers: HeaderMap, ApiQuery(q): ApiQuery<StdHashMap<String, String>>) -> Result<Json<Vec<ImageStatsEntry>>, ApiError> {
+    require_admin(&headers, Some(&q))?;
+    let min_shown: u64 = q.get("min_shown").and_then(|s| s.parse().ok()).unwrap_or(1);
+    let mut out: Vec<ImageStatsEntry> = image_stats::all().into_iter().filter(|(_, st)| st.shown >= min_shown.max(1)).map(|(filename, st)| ImageStatsEntry {
+        species: species::for_image_key(&filename).map(|sp| sp.key),
+        accuracy: st.correct as f64 / st.shown as f64,
+        filename,
+        shown: st.shown,
+        correct: st.correct,
+        wrong_picks: st.wrong_picks,
+        mistaken_for: st.mistaken_for,
+    }).collect();
+    out.sort_by(|a, b| a.accuracy.partial_cmp(&b.accuracy).unwrap_or(std::cmp::Ordering::Equal).then(b.shown.cmp(&a.shown)));
+    Ok(Json(out))
+}
+
+async fn admin_tanuki_or_not_stats(headers: HeaderMap, ApiQuery(q): ApiQuery<StdHashMap<String, String>>) -> Result<Json<tanuki_or_not::StatsReport>, ApiError> {
+    require_admin(&headers, Some(&q))?;
+    Ok(Json(tanuki_or_not::report()))
+}
+
+#[derive(Serialize)]
+struct CatalogReloadResult {
+    ok: bool,
+    species: usize,
+    questions: usize,
+}
+
+// re-read the quiz catalog file; on validation errors the previous catalog stays active
+async fn admin_reload_catalog(headers: HeaderMap, ApiQuery(q): ApiQuery<StdHashMap<String, String>>) -> Result<Json<CatalogReloadResult>, ApiError> {
+    require_admin(&headers, Some(&q))?;
+    // the catalog's answers are species names, so the registry is re-read first
+    let species = species::reload().map_err(|errors| ApiError::bad_request("species registry is invalid; the previous registry is still active").with_details(errors))?;
+    match catalog::reload() {
+        Ok(n) => Ok(Json(CatalogReloadResult { ok: true, species, questions: n })),
+        Err(errors) => Err(ApiError::bad_request("quiz catalog is invalid; the previous catalog is still active").with_details(errors)),
+    }
+}
+
+// species of a photo in public/assets, for quiz sets; None for anything that is not a plain file name there
+fn asset_category(file: &str) -> Option<String> {
+    if file.is_empty() || file.starts_with('.') || file.contains('/') || file.contains('\\') { return None; }
+    if !PathBuf::from("public").join("assets").join(file).is_file() { return None; }
+    species::for_image_key(file).map(|sp| sp.key)
+}
+
+fn build_set_questions(specs: &[classroom::QuestionSpec]) -> Result<Vec<classroom::SetQuestion>, ApiError> {
+    classroom::build_questions(specs, asset_category, &mut rand::thread_rng())
+        .map_err(|errors| ApiError::bad_request("quiz set is invalid").with_details(errors))
+}
+
+// a set choice as a quiz choice, for image serving and answer stats
+fn set_choices(question: &classroom::SetQuestion) -> Vec<GeneratedChoice> {
+    question.choices.iter().map(|c| GeneratedChoice { token: c.token.clone(), category: c.category.clone(), file: Some(c.file.clone()), procedural_key: None, degrade: None }).collect()
+}
+
+fn quiz_set_not_found() -> ApiError {
+    ApiError::not_found("quiz set not found")
+}
+
+async fn admin_create_quiz_set(headers: HeaderMap, ApiQuery(q): ApiQuery<StdHashMap<String, String>>, ApiJson(payload): ApiJson<QuizSetCreate>) -> Result<Json<classroom::QuizSet>, ApiError> {
+    require_admin(&headers, Some(&q))?;
+    let title = classroom::clean_title(&payload.title).map_err(ApiError::bad_request)?;
+    let questions = build_set_questions(&payload.questions)?;
+    let set = classroom::create(title, questions).map_err(|e| ApiError::internal(format!("quiz set write failed: {}", e)))?;
+    Ok(Json(set))
+}
+
+async fn admin_list_quiz_sets(headers: HeaderMap, ApiQuery(q): ApiQuery<StdHashMap<String, String>>) -> Result<Json<Vec<classroom::QuizSetSummary>>, ApiError> {
+    require_admin(&headers, Some(&q))?;
+    Ok(Json(classroom::list().iter().map(|s| s.summary()).collect()))
+}
+
+async fn admin_get_quiz_set(headers: HeaderMap, ApiQuery(q): ApiQuery<StdHashMap<String, String>>, ApiPath(id): ApiPath<String>) -> Result<Json<classroom::QuizSet>, ApiError> {
+    require_admin(&headers, Some(&q))?;
+    classroom::get(&id).map(Json).ok_or_else(quiz_set_not_found)
+}
+
+async fn admin_update_quiz_set(headers: HeaderMap, ApiQuery(q): ApiQuery<StdHashMap<String, String>>, ApiPath(id): ApiPath<String>, ApiJson(payload): ApiJson<QuizSetUpdate>) -> Result<Json<classroom::QuizSet>, ApiError> {
+    require_admin(&headers, Some(&q))?;
+    let title = payload.title.as_deref().map(classroom::clean_title).transpose().map_err(ApiError::bad_request)?;
+    let questions = payload.questions.as_deref().map(build_set_questions).transpose()?;
+    match classroom::update(&id, title, questions, payload.open) {
+        Ok(set) => Ok(Json(set)),
+        Err(classroom::UpdateError::NotFound) => Err(quiz_set_not_found()),
+        Err(classroom::UpdateError::HasSubmissions) => Err(ApiError::conflict("students have already answered this set; create a new set to change its questions")),
+        Err(classroom::UpdateError::Io(e)) => Err(ApiError::internal(format!("quiz set write failed: {}", e))),
+    }
+}
+
+async fn admin_delete_quiz_set(headers: HeaderMap, ApiQuery(q): ApiQuery<StdHashMap<String, String>>, ApiPath(id): ApiPath<String>) -> Result<Json<Value>, ApiError> {
+    require_admin(&headers, Some(&q))?;
+    match classroom::delete(&id) {
+        Ok(true) => Ok(Json(serde_json::json!({ "ok": true }))),
+        Ok(false) => Err(quiz_set_not_found()),
+        Err(e) => Err(ApiError::internal(format!("quiz set write failed: {}", e))),
+    }
+}
+
+async fn admin_quiz_set_results(headers: HeaderMap, ApiQuery(q): ApiQuery<StdHashMap<String, String>>, ApiPath(id): ApiPath<String>) -> Result<Json<classroom::SetResults>, ApiError> {
+    require_admin(&headers, Some(&q))?;
+    let set = classroom::get(&id).ok_or_else(quiz_set_not_found)?;
+    Ok(Json(classroom::results(&set)))
+}
+
+// a student opens a set by its join code
+async fn get_class_set(Locale(lang): Locale, ApiPath(code): ApiPath<String>, ApiQuery(q): ApiQuery<StdHashMap<String, String>>) -> Result<Json<ClassSetResponse>, ApiError> {
+    let set = classroom::by_code(&code).ok_or_else(|| ApiError::not_found(i18n::t(lang, "error.class_not_found")))?;
+    if !set.open { return Err(ApiError::conflict(i18n::t(lang, "error.class_closed"))); }
+    let mut submitted = false;
+    if let Some(player_id) = q.get("player_id") {
+        if !daily::valid_player_id(player_id) { return Err(ApiError::bad_request(i18n::t(lang, "error.player_id_invalid"))); }
+        submitted = set.has_submitted(player_id);
+    }
+    let questions = set.questions.iter().enumerate().map(|(index, question)| FixedQuestion {
+        index,
+        question: choice_question(&question.answer, lang),
+        choices: question.choices.iter().map(|c| PublicChoice { token: c.token.clone(), image_url: format!("/api/class/{}/image/{}/{}", set.code, index, c.token) }).collect(),
+    }).collect();
+    Ok(Json(ClassSetResponse { code: set.code, title: set.title, rounds: set.questions.len(), questions, submitted }))
+}
+
+async fn class_image(Locale(lang): Locale, ApiPath((code, index, token)): ApiPath<(String, usize, String)>, ApiQuery(q): ApiQuery<StdHashMap<String, String>>) -> Result<impl IntoResponse, ApiError> {
+    let transform = image_transform(&q, lang)?;
+    let not_found = || ApiError::not_found(i18n::t(lang, "error.quiz_image_not_found"));
+    let set = classroom::by_code(&code).ok_or_else(not_found)?;
+    let choice = set.questions.get(index).map(set_choices).and_then(|choices| choices.into_iter().find(|c| c.token == token)).ok_or_else(not_found)?;
+    choice_image_response(choice, &format!("quiz set {} #{}", set.code, index), transform).await
+}
+
+async fn submit_class_set(Locale(lang): Locale, ApiPath(code): ApiPath<String>, ApiJson(payload): ApiJson<ClassSubmit>) -> Result<Json<ClassSubmitResult>, ApiError> {
+    if !daily::valid_player_id(&payload.player_id) { return Err(ApiError::bad_request(i18n::t(lang, "error.player_id_invalid"))); }
+    let nickname = leaderboard::clean_nickname(&payload.nickname)
+        .ok_or_else(|| ApiError::bad_request(i18n::tf(lang, "error.nickname_invalid", &[("max", &leaderboard::MAX_NICKNAME_CHARS.to_string())])))?;
+    let (set, submission) = match classroom::submit(&code, &payload.player_id, nickname, payload.answers) {
+        Ok(r) => r,
+        Err(classroom::SubmitError::NotFound) => return Err(ApiError::not_found(i18n::t(lang, "error.class_not_found"))),
+        Err(classroom::SubmitError::Closed) => return Err(ApiError::conflict(i18n::t(lang, "error.class_closed"))),
+        Err(classroom::SubmitError::AnswerCount(n)) => return Err(ApiError::bad_request(i18n::tf(lang, "error.daily_answers_count", &[("rounds", &n.to_string())]))),
+        Err(classroom::SubmitError::AlreadySubmitted) => return Err(ApiError::conflict(i18n::t(lang, "error.class_already_submitted"))),
+        Err(classroom::SubmitError::NicknameTaken) => return Err(ApiError::conflict(i18n::t(lang, "error.class_nickname_taken"))),
+        Err(classroom::SubmitError::Io(e)) => return Err(ApiError::internal(format!("quiz set write failed: {}", e))),
+    };
+    let mut outcomes = Vec::new();
+    let mut records = Vec::new();
+    for (index, (question, token)) in set.questions.iter().zip(&submission.answers).enumerate() {
+        let choices = set_choices(question);
+        let picked = choices.iter().find(|c| &c.token == token);
+        outcomes.extend(choice_outcomes(&choices, &question.answer, picked));
+        records.push(AnswerRecord {
+            session: Some(set.code.clone()),
+            player_id: Some(submission.player_id.clone()),
+            nickname: Some(submission.nickname.clone()),
+            ..AnswerRecord::choice("class", format!("{}#{}", set.code, index + 1), &question.answer, &choices, picked)
+        });
+    }
+    record_image_outcomes(outcomes);
+    answer_log::log(records);
+    Ok(Json(class_result(&set, &submission, lang)))
+}
+
+async fn class_set_result(Locale(lang): Locale, ApiPath(code): ApiPath<String>, ApiQuery(q): ApiQuery<StdHashMap<String, String>>) -> Result<Json<ClassSubmitResult>, ApiError> {
+    let player_id = q.get("player_id").map(|s| s.as_str()).unwrap_or_default();
+    if !daily::valid_player_id(player_id) { return Err(ApiError::bad_request(i18n::t(lang, "error.player_id_invalid"))); }
+    let set = classroom::by_code(&code).ok_or_else(|| ApiError::not_found(i18n::t(lang, "error.class_not_found")))?;
+    let submission = set.submission(player_id).ok_or_else(|| ApiError::not_found(i18n::t(lang, "error.class_not_submitted")))?;
+    Ok(Json(class_result(&set, submission, lang)))
+}
+
+fn class_result(set: &classroom::QuizSet, submission: &classroom::Submission, lang: &str) -> ClassSubmitResult {
+    let mut result = ClassSubmitResult { code: set.code.clone(), rounds: set.questions.len(), closed: !set.open, score: None, results: None };
+    if set.open { return result; }
+    result.score = Some(submission.score);
+    result.results = Some(set.questions.iter().zip(&submission.answers).map(|(question, token)| {
+        let sp = species::get(&question.answer);
+        let mut r = QuizResult::new(question.is_correct(token), question.answer.clone(), sp.clone(), lang);
+        r.correct_label = sp.map(|sp| sp.name(lang).to_string());
+        r
+    }).collect());
+    result
+}
+
+// ?from= and ?to= take a date (YYYY-MM-DD, UTC) or an RFC 3339 time; a `to` date includes that whole day
+fn export_time(name: &str, value: &str, end: bool) -> Result<chrono::DateTime<chrono::Utc>, ApiError> {
+    if let Ok(t) = chrono::DateTime::parse_from_rfc3339(value) { return Ok(t.with_timezone(&chrono::Utc)); }
+    let date: chrono::NaiveDate = value.parse().map_err(|_| ApiError::bad_request(format!("{} must be a date (YYYY-MM-DD) or an RFC 3339 time", name)))?;
+    let date = if end { date.succ_opt().unwrap_or(date) } else { date };
+    Ok(date.and_hms_opt(0, 0, 0).unwrap_or_default().and_utc())
+}
+
+fn export_filter(q: &StdHashMap<String, String>) -> Result<answer_log::Filter, ApiError> {
+    let given = |name: &str| q.get(name).map(|v| v.trim()).filter(|v| !v.is_empty());
+    let source = given("source").map(|s| s.to_string());
+    if let Some(s) = &source {
+        if !answer_log::SOURCES.contains(&s.as_str()) {
+            return Err(ApiError::bad_request(format!("unknown source {}", s)).with_details(answer_log::SOURCES.iter().map(|s| s.to_string()).collect()));
+        }
+    }
+    Ok(answer_log::Filter {
+        from: given("from").map(|v| export_time("from", v, false)).transpose()?,
+        to: given("to").map(|v| export_time("to", v, true)).transpose()?,
+        source,
+        session: given("session").map(|s| s.to_string()),
+        player_id: given("player_id").map(|s| s.to_string()),
+    })
+}
+
+// chunks of about this size are sent as the log is read
+const EXPORT_CHUNK_BYTES: usize = 64 * 1024;
+
+// runs on a blocking thread; stops early when the client goes away. A read error is sent as an Err,
+// which aborts the response, so a partial export never looks like a complete one.
+fn write_answer_export(filter: &answer_log::Filter, json: bool, tx: tokio::sync::mpsc::Sender<std::io::Result<Bytes>>) {
+    // the BOM makes spreadsheet apps read Japanese nicknames as UTF-8
+    let mut buf = if json { "[".to_string() } else { format!("\u{feff}{}", answer_log::CSV_HEADER) };
+    let mut first = true;
+    let result = answer_log::for_each(filter, |record| {
+        if json {
+            if !first { buf.push(','); }
+            buf.push('\n');
+            buf.push_str(&serde_json::to_string(record).unwrap_or_default());
+        } else {
+            buf.push_str(&answer_log::csv_row(record));
+        }
+        first = false;
+        if buf.len() < EXPORT_CHUNK_BYTES { return true; }
+        tx.blocking_send(Ok(Bytes::from(std::mem::take(&mut buf)))).is_ok()
+    });
+    if let Err(e) = result {
+        eprintln!("answer export failed: {}", e);
+        let _ = tx.blocking_send(Err(std::io::Error::other(e)));
+        return;
+    }
+    if json { buf.push_str("\n]\n"); }
+    let _ = tx.blocking_send(Ok(Bytes::from(buf)));
+}
+
+// ?format=csv|json (default csv) &from= &to= &source= &session= &player_id=
+async fn admin_export_answers(headers: HeaderMap, ApiQuery(q): ApiQuery<StdHashMap<String, String>>) -> Result<impl IntoResponse, ApiError> {
+    require_admin(&headers, Some(&q))?;
+    let filter = export_filter(&q)?;
+    let json = match q.get("format").map(|s| s.as_str()) {
+        None | Some("csv") => false,
+        Some("json") => true,
+        Some(_) => return Err(ApiError::bad_request("format must be csv or json")),
+    };
+    let (tx, rx) = tokio::sync::mpsc::channel::<std::io::Result<Bytes>>(4);
+    tokio::task::spawn_blocking(move || write_answer_export(&filter, json, tx));
+    let chunks = futures_util::stream::unfold(rx, |mut rx| async move { rx.recv().await.map(|chunk| (chunk, rx)) });
+    let (content_type, extension) = if json { ("application/json", "json") } else { ("text/csv; charset=utf-8", "csv") };
+    let mut out = axum::http::HeaderMap::new();
+    out.insert(header::CONTENT_TYPE, header::HeaderValue::from_static(content_type));
+    let disposition = format!("attachment; filename=\"answers-{}.{}\"", chrono::Utc::now().format("%Y%m%d-%H%M%S"), extension);
+    if let Ok(v) = header::HeaderValue::from_str(&disposition) { out.insert(header::CONTENT_DISPOSITION, v); }
+    Ok((out, axum::body::Body::from_stream(chunks)))
+}
+
+// Resized, cropped or filtered variants of asset photos, cached on disk (see media.rs).
+// Quiz photos are named after their species, so without the admin token only the photos the
+// static catalog shows are served, as under /assets.
+async fn get_media(headers: HeaderMap, ApiPath(id): ApiPath<String>, ApiQuery(q): ApiQuery<StdHashMap<String, String>>) -> Result<axum::response::Response, ApiError> {
+    let transform = media::Transform::from_query(&q).map_err(|errors| ApiError::bad_request("invalid media parameters").with_details(errors))?;
+    let public = catalog::references_asset(&id);
+    if !public { require_admin(&headers, Some(&q))?; }
+    let source = media::source(&id).ok_or_else(|| ApiError::not_found(format!("no photo {}", id)))?;
+    let etag = format!("\"{}\"", source.key(&transform));
+    let mut out = HeaderMap::new();
+    out.insert(header::ETAG, header::HeaderValue::from_str(&etag).map_err(|e| ApiError::internal(e.to_string()))?);
+    // an hour is long enough for a lesson; afterwards the ETag makes checking cheap
+    out.insert(header::CACHE_CONTROL, header::HeaderValue::from_static(if public { "public, max-age=3600" } else { "private, max-age=3600" }));
+    if headers.get(header::IF_NONE_MATCH).and_then(|v| v.to_str().ok()).is_some_and(|v| v.split(',').any(|t| t.trim() == etag)) {
+        return Ok((axum::http::StatusCode::NOT_MODIFIED, out).into_response());
     }
+    out.insert(header::CONTENT_TYPE, header::HeaderValue::from_static(source.content_type()));
+    let (cached_source, cached_transform) = (source.clone(), transform.clone());
+    let cached = tokio::task::spawn_blocking(move || cached_source.cached(&cached_transform)).await.map_err(|e| ApiError::internal(e.to_string()))?;
+    let bytes = match cached {
+        Some(bytes) => bytes,
+        None => {
+            // rendering is CPU-heavy; past MEDIA_MAX_RENDERS at once, requests wait for a free slot
+            let _slot = media::RENDER_SLOTS.acquire().await.map_err(|e| ApiError::internal(e.to_string()))?;
+            tokio::task::spawn_blocking(move || source.variant(&transform)).await
+                .map_err(|e| ApiError::internal(e.to_string()))?
+                .map_err(ApiError::internal)?
+        }
+    };
+    Ok((out, Bytes::from(bytes)).into_response())
+}
+
+// Prometheus scrape endpoint; counters only, nothing about players or answers
+async fn get_metrics() -> impl IntoResponse {
+    ([(header::CONTENT_TYPE, "text/plain; version=0.0.4")], metrics::render())
 }
 
 #[tokio::main]
@@ -820,6 +2764,32 @@ async fn main() {
     let mut static_dir: PathBuf = env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
     static_dir.push("public");
 
+    match species::reload() {
+        Ok(n) => println!("loaded {} species from {}", n, species::config_path().display()),
+        Err(errors) => eprintln!("failed to load species registry {}, using bundled defaults: {}", species::config_path().display(), errors.join("; ")),
+    }
+    match i18n::reload() {
+        Ok(n) => println!("loaded {} locale catalogs from {}", n, i18n::locales_dir().display()),
+        Err(errors) => eprintln!("failed to load locale catalogs, using bundled text for: {}", errors.join("; ")),
+    }
+    match catalog::reload() {
+        Ok(n) => println!("loaded {} catalog questions from {}", n, catalog::catalog_path().display()),
+        Err(errors) => eprintln!("failed to load quiz catalog {}: {}", catalog::catalog_path().display(), errors.join("; ")),
+    }
+    match image_stats::load(&index_path()) {
+        Ok(n) => println!("loaded answer statistics for {} photos", n),
+        Err(e) => eprintln!("failed to load per-photo answer statistics, starting empty: {}", e),
+    }
+    match tanuki_or_not::load() {
+        Ok(n) => println!("loaded tanuki-or-not answer counts for {} species", n),
+        Err(e) => eprintln!("failed to load tanuki-or-not answer counts, starting empty: {}", e),
+    }
+    match review::migrate_single_file() {
+        Ok(0) => {}
+        Ok(n) => println!("moved {} player histories into per-player files", n),
+        Err(e) => eprintln!("player history migration failed: {}", e),
+    }
+
     // Optionally auto-populate assets from Wikimedia Commons if requested.
     if env::var("AUTO_POPULATE_ASSETS").map(|v| v.to_lowercase() == "true").unwrap_or(false) {
         match populate_assets_from_commons().await {
@@ -827,41 +2797,113 @@ async fn main() {
             Err(e) => eprintln!("AUTO_POPULATE_ASSETS failed: {}", e),
         }
     }
+    // without a secret anyone with the source could compute every day's answers
+    if let Err(e) = daily::secret() {
+        eprintln!("cannot load or create the daily seed secret: {}; set DAILY_SEED_SECRET", e);
+        std::process::exit(1);
+    }
 
     // API routes registered first, then serve static files as the fallback.
+    let limits = quiz_store::Limits::from_env();
+    // a misconfigured store must not quietly turn into one that loses quizzes on restart
+    let store = match quiz_store::from_env(limits) {
+        Ok(store) => store,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    let state = AppState { quizzes: quiz_store::Quizzes::new(store) };
     let app = Router::new()
         .route("/api/quiz", get(get_quiz_question))
         .route("/api/generate_quiz", get(generate_quiz))
+        .route("/api/quiz_image/:quiz_id/:token", get(quiz_image))
     .route("/images/:name", get(serve_image))
         .route("/api/submit", post(submit_answer))
         .route("/api/submit_generated", post(submit_generated))
-        .route("/api/admin/upload", post(admin_upload_json))
-        .route("/api/admin/upload_multipart", post(admin_upload_multipart))
+        .route("/api/tanuki_or_not", get(generate_tanuki_or_not))
+        .route("/api/tanuki_or_not/submit", post(submit_tanuki_or_not))
+        .route("/api/daily", get(get_daily))
+        .route("/api/daily/image/:date/:index/:token", get(daily_image))
+        .route("/api/daily/submit", post(submit_daily))
+        .route("/api/daily/leaderboard", get(get_daily_leaderboard))
+        .route("/api/daily/answers", get(get_daily_answers))
+        .route("/api/player/:player_id/review", get(get_player_review))
+        .route("/api/session", post(create_session))
+        .route("/api/rooms", post(create_room))
+        .route("/api/rooms/:code", get(get_room))
+        .route("/api/rooms/:code/ws", get(room_ws))
+        .route("/api/rooms/:code/image/:token", get(room_image))
+        .route("/api/session/:id", get(get_session))
+        .route("/api/leaderboard", get(get_leaderboard).post(submit_leaderboard))
+        // request body limits leave room for base64 / multipart overhead around MAX_UPLOAD_BYTES of image data
+        .route("/api/admin/upload", post(admin_upload_json).layer(DefaultBodyLimit::max(max_upload_bytes() / 3 * 4 + 64 * 1024)))
+        .route("/api/admin/upload_multipart", post(admin_upload_multipart).layer(DefaultBodyLimit::max(max_upload_bytes() + 64 * 1024)))
         .route("/api/admin/list", get(admin_list))
     .route("/api/admin/similar", get(admin_similar))
         .route("/api/admin/delete", post(admin_delete))
-        .nest_service("/", ServeDir::new(static_dir));
+        .route("/api/admin/catalog/reload", post(admin_reload_catalog))
+        .route("/api/admin/mark_clear", post(admin_mark_clear))
+        .route("/api/admin/tanuki_or_not/stats", get(admin_tanuki_or_not_stats))
+        .route("/api/admin/image_stats", get(admin_image_stats))
+        .route("/api/admin/assets/:filename", get(admin_asset))
+        // quiz photos are only served by token; see public_asset
+        .route("/assets/*path", get(public_asset))
+        .route("/api/admin/quiz/:id", get(admin_quiz_replay))
+        .route("/api/admin/quiz/:id/image/:token", get(admin_quiz_replay_image))
+        .route("/api/admin/quiz_sets", get(admin_list_quiz_sets).post(admin_create_quiz_set))
+        .route("/api/admin/quiz_sets/:id", get(admin_get_quiz_set).put(admin_update_quiz_set).delete(admin_delete_quiz_set))
+        .route("/api/admin/quiz_sets/:id/results", get(admin_quiz_set_results))
+        .route("/api/class/:code", get(get_class_set))
+        .route("/api/class/:code/image/:index/:token", get(class_image))
+        .route("/api/class/:code/submit", post(submit_class_set))
+        .route("/api/class/:code/result", get(class_set_result))
+        .route("/api/admin/export/answers", get(admin_export_answers))
+        .route("/api/admin/annotations", get(admin_get_annotations).post(admin_set_annotations))
+        .route("/api/hotspot_quiz", get(generate_hotspot_quiz))
+        .route("/api/hotspot_quiz/submit", post(submit_hotspot_quiz))
+        .route("/api/reveal_quiz", get(generate_reveal_quiz))
+        .route("/api/reveal_quiz/next", post(next_reveal_step))
+        .route("/api/reveal_quiz/submit", post(submit_reveal_quiz))
+        .route("/media/:id", get(get_media))
+        .route("/metrics", get(get_metrics))
+        .nest_service("/", ServeDir::new(static_dir))
+        .with_state(state.clone());
 
     let addr: SocketAddr = env::var("HOST_ADDR").unwrap_or_else(|_| "0.0.0.0:3000".to_string()).parse().unwrap();
     println!("listening on http://{}", addr);
 
     // Use axum's serve helper with a TcpListener
     // spawn a background cleanup task to remove old quizzes
+    let cleanup_store = state.quizzes.clone();
     let _cleanup_handle = tokio::spawn(async move {
-        let ttl = Duration::from_secs(60 * 5); // 5 minutes
+        let ttl = limits.ttl;
+        let mut last_history_sweep: Option<Instant> = None;
         loop {
-            tokio::time::sleep(Duration::from_secs(60)).await;
+            tokio::time::sleep(limits.sweep_interval()).await;
             let now = Instant::now();
-            let mut store = QUIZ_STORE.lock();
-            let keys_to_remove: Vec<String> = store.iter()
-                .filter_map(|(k, (_v, ts))| if now.duration_since(*ts) > ttl { Some(k.clone()) } else { None })
-                .collect();
-            for k in keys_to_remove {
-                store.remove(&k);
+            let wall_now = chrono::Utc::now();
+            let removed = cleanup_store.remove_expired(move |stored| {
+                let elapsed = stored.elapsed(wall_now);
+                elapsed > ttl || past_deadline(&stored.quiz, elapsed)
+            }).await;
+            room::sweep_expired(now);
+            drop_unanswered(removed);
+            // sessions live longer than single quizzes (SESSION_TTL_SECS)
+            session::sweep_expired(now);
+            tokio::task::spawn_blocking(|| {
+                if let Err(e) = image_stats::flush() { eprintln!("failed to write per-photo answer statistics: {}", e); }
+                if let Err(e) = tanuki_or_not::flush() { eprintln!("failed to write tanuki-or-not answer counts: {}", e); }
+            });
+            // histories last days, so an hourly look at the directory is plenty
+            if last_history_sweep.is_none_or(|t| now.duration_since(t) >= Duration::from_secs(60 * 60)) {
+                last_history_sweep = Some(now);
+                tokio::task::spawn_blocking(review::sweep_stale);
             }
         }
     });
 
     let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
-    axum::serve(listener, app).await.unwrap();
+    // peer addresses feed client_key
+    axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>()).await.unwrap();
 }
\ No newline at end of file
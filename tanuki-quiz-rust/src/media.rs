@@ -0,0 +1,357 @@
+// Resized and filtered variants of asset photos: GET /media/<filename>?w=&h=&fit=&crop=&grayscale=&blur=
+//
+// Uploads only get one 320x240 thumbnail; phones and projectors need other sizes, so variants are
+// derived from the original in public/assets on first request and kept under data/media_cache
+// (override with MEDIA_CACHE_DIR), one directory per photo. A variant's cache key covers the
+// canonical parameters and the original's size and modification time, so parameter order and
+// unknown parameters never create new files, and a changed original is never served stale.
+// When the cache grows past MEDIA_CACHE_MAX_BYTES the oldest variants are removed first.
+// Sizes, crops and blur are rounded to a coarse grid before they are keyed, so a client walking
+// through values cannot fill the cache with near-identical variants, and at most MEDIA_MAX_RENDERS
+// variants (default: one per CPU) are rendered at once.
+
+use image::imageops::FilterType;
+use image::DynamicImage;
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::env;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+use tokio::sync::Semaphore;
+
+use crate::{imaging, metrics};
+
+/// Largest width or height a variant can ask for.
+pub const MAX_DIM: u32 = 2048;
+/// Largest blur sigma, in output pixels.
+pub const MAX_BLUR: f32 = 20.0;
+// sizes are rounded to a multiple of this, crops to hundredths and blur to halves
+const SIZE_STEP: u32 = 16;
+const CROP_STEPS: f32 = 100.0;
+const BLUR_STEPS: f32 = 2.0;
+
+/// Renders that may run at once; a request for an uncached variant holds one while it renders.
+pub static RENDER_SLOTS: Lazy<Semaphore> = Lazy::new(|| {
+    let default = std::thread::available_parallelism().map(|n| n.get()).unwrap_or(4);
+    Semaphore::new(env::var("MEDIA_MAX_RENDERS").ok().and_then(|s| s.parse().ok()).filter(|&n| n > 0).unwrap_or(default))
+});
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Fit {
+    // the whole photo inside w x h, keeping its aspect ratio
+    Contain,
+    // w x h filled, keeping the aspect ratio and cutting off the overflow evenly on both sides
+    Cover,
+    // stretched to exactly w x h
+    Fill,
+}
+
+impl Fit {
+    fn parse(s: &str) -> Option<Fit> {
+        match s {
+            "contain" => Some(Fit::Contain),
+            "cover" => Some(Fit::Cover),
+            "fill" => Some(Fit::Fill),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Fit::Contain => "contain",
+            Fit::Cover => "cover",
+            Fit::Fill => "fill",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Transform {
+    pub w: Option<u32>,
+    pub h: Option<u32>,
+    pub fit: Fit,
+    // [x, y, w, h] as fractions of the original, applied before resizing
+    pub crop: Option<[f32; 4]>,
+    pub grayscale: bool,
+    // gaussian blur sigma in output pixels; 0 for none
+    pub blur: f32,
+}
+
+impl Transform {
+    /// Reads the query parameters, reporting every invalid one. Other parameters are ignored.
+    pub fn from_query(q: &HashMap<String, String>) -> Result<Transform, Vec<String>> {
+        let mut errors = Vec::new();
+        let mut dim = |name: &str| {
+            let v = q.get(name)?;
+            match v.parse::<u32>() {
+                Ok(n) if (1..=MAX_DIM).contains(&n) => Some(quantize_size(n)),
+                _ => { errors.push(format!("{} must be a whole number from 1 to {}", name, MAX_DIM)); None }
+            }
+        };
+        let (w, h) = (dim("w"), dim("h"));
+        let fit = match q.get("fit") {
+            None => Fit::Contain,
+            Some(v) => Fit::parse(v).unwrap_or_else(|| { errors.push("fit must be contain, cover or fill".to_string()); Fit::Contain }),
+        };
+        let crop = q.get("crop").and_then(|v| {
+            let parsed = parse_crop(v);
+            if parsed.is_none() { errors.push("crop must be x,y,w,h as fractions of the photo, inside it".to_string()); }
+            parsed
+        });
+        let grayscale = match q.get("grayscale").map(|v| v.as_str()) {
+            None | Some("false") | Some("0") => false,
+            Some("true") | Some("1") => true,
+            Some(_) => { errors.push("grayscale must be true or false".to_string()); false }
+        };
+        let blur = match q.get("blur") {
+            None => 0.0,
+            Some(v) => match v.parse::<f32>() {
+                Ok(b) if (0.0..=MAX_BLUR).contains(&b) => (b * BLUR_STEPS).round() / BLUR_STEPS,
+                _ => { errors.push(format!("blur must be a number from 0 to {}", MAX_BLUR)); 0.0 }
+            },
+        };
+        if errors.is_empty() { Ok(Transform { w, h, fit, crop, grayscale, blur }) } else { Err(errors) }
+    }
+
+    /// True when there is nothing to change: no size, crop or filter.
+    pub fn is_identity(&self) -> bool {
+        self.w.is_none() && self.h.is_none() && self.crop.is_none() && !self.grayscale && self.blur == 0.0
+    }
+
+    // one spelling per variant, for the cache key
+    fn canonical(&self) -> String {
+        let dim = |d: Option<u32>| d.map(|n| n.to_string()).unwrap_or_default();
+        let crop = self.crop.map(|c| c.map(|f| f.to_string()).join(",")).unwrap_or_default();
+        format!("w={};h={};fit={};crop={};grayscale={};blur={}", dim(self.w), dim(self.h), self.fit.name(), crop, self.grayscale, self.blur)
+    }
+
+    pub fn apply(&self, img: &DynamicImage) -> DynamicImage {
+        let mut out = match self.crop {
+            Some([x, y, w, h]) => imaging::crop_fraction(img, x, y, w, h),
+            None => img.clone(),
+        };
+        if let Some((w, h)) = self.output_size(out.width(), out.height()) {
+            out = match self.fit {
+                Fit::Cover => out.resize_to_fill(w, h, FilterType::Lanczos3),
+                _ => out.resize_exact(w, h, FilterType::Lanczos3),
+            };
+        }
+        if self.grayscale { out = DynamicImage::ImageLuma8(out.to_luma8()); }
+        if self.blur > 0.0 { out = out.blur(self.blur); }
+        out
+    }
+
+    // None keeps the size; contain and cover never enlarge the photo, fill gives exactly what was asked
+    fn output_size(&self, sw: u32, sh: u32) -> Option<(u32, u32)> {
+        let scaled = |n: u32, scale: f32| ((n as f32 * scale).round() as u32).clamp(1, MAX_DIM);
+        let (w, h) = match (self.w, self.h) {
+            (None, None) => return None,
+            // one side given: the other follows the aspect ratio
+            (Some(w), None) => { let s = (w as f32 / sw as f32).min(1.0); return Some((scaled(sw, s), scaled(sh, s))); }
+            (None, Some(h)) => { let s = (h as f32 / sh as f32).min(1.0); return Some((scaled(sw, s), scaled(sh, s))); }
+            (Some(w), Some(h)) => (w, h),
+        };
+        match self.fit {
+            Fit::Fill => Some((w, h)),
+            Fit::Contain => {
+                let s = (w as f32 / sw as f32).min(h as f32 / sh as f32).min(1.0);
+                Some((scaled(sw, s), scaled(sh, s)))
+            }
+            Fit::Cover => {
+                // shrink the box, not its shape, when the photo is smaller than asked
+                let s = (sw as f32 / w as f32).min(sh as f32 / h as f32).min(1.0);
+                Some((scaled(w, s), scaled(h, s)))
+            }
+        }
+    }
+}
+
+// nearest multiple of SIZE_STEP, never 0; MAX_DIM is one
+fn quantize_size(n: u32) -> u32 {
+    ((n + SIZE_STEP / 2) / SIZE_STEP * SIZE_STEP).clamp(SIZE_STEP, MAX_DIM)
+}
+
+// checked after rounding, so a rounded crop still lies inside the photo
+fn parse_crop(v: &str) -> Option<[f32; 4]> {
+    let parts: Vec<f32> = v.split(',').map(|p| p.trim().parse::<f32>().ok().filter(|f| f.is_finite()).map(|f| (f * CROP_STEPS).round() / CROP_STEPS)).collect::<Option<_>>()?;
+    let [x, y, w, h]: [f32; 4] = parts.try_into().ok()?;
+    let inside = x >= 0.0 && y >= 0.0 && w > 0.0 && h > 0.0 && x + w <= 1.0 + f32::EPSILON && y + h <= 1.0 + f32::EPSILON;
+    inside.then_some([x, y, w, h])
+}
+
+/// An original photo in public/assets.
+#[derive(Clone)]
+pub struct Source {
+    id: String,
+    path: PathBuf,
+    // size and modification time, so a replaced original gets new cache keys
+    version: String,
+}
+
+// a photo filename directly in public/assets, the same kinds of file the quizzes pick from
+fn valid_id(id: &str) -> bool {
+    let lower = id.to_lowercase();
+    !id.starts_with('.') && !id.contains(['/', '\\']) && (lower.ends_with(".jpg") || lower.ends_with(".jpeg") || lower.ends_with(".png"))
+}
+
+/// The original for a /media id, which is an asset filename; None when there is no such photo.
+pub fn source(id: &str) -> Option<Source> {
+    if !valid_id(id) { return None; }
+    let path = PathBuf::from("public").join("assets").join(id);
+    let meta = std::fs::metadata(&path).ok().filter(|m| m.is_file())?;
+    let modified = meta.modified().ok()?.duration_since(UNIX_EPOCH).ok()?.as_nanos();
+    Some(Source { id: id.to_string(), path, version: format!("{}:{}", meta.len(), modified) })
+}
+
+impl Source {
+    // png keeps transparency; everything else becomes jpeg
+    fn png(&self) -> bool {
+        self.id.to_lowercase().ends_with(".png")
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        if self.png() { "image/png" } else { "image/jpeg" }
+    }
+
+    /// Names the variant: its cache file name and its ETag.
+    pub fn key(&self, t: &Transform) -> String {
+        let digest = Sha256::digest(format!("{}|{}", self.version, t.canonical()).as_bytes());
+        digest[..16].iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    fn cache_file(&self, t: &Transform) -> PathBuf {
+        cache_dir().join(&self.id).join(format!("{}.{}", self.key(t), if self.png() { "png" } else { "jpg" }))
+    }
+
+    /// The encoded variant if it is cached. Blocking.
+    pub fn cached(&self, t: &Transform) -> Option<Vec<u8>> {
+        let bytes = std::fs::read(self.cache_file(t)).ok()?;
+        metrics::MEDIA_CACHE_HITS.inc();
+        Some(bytes)
+    }
+
+    /// The encoded variant, from the cache or rendered and cached now. Blocking; rendering a large
+    /// photo can take a while.
+    pub fn variant(&self, t: &Transform) -> Result<Vec<u8>, String> {
+        // another request may have rendered it while this one waited for a render slot
+        if let Some(bytes) = self.cached(t) { return Ok(bytes); }
+        let file = self.cache_file(t);
+        let img = image::open(&self.path).map_err(|e| format!("cannot read {}: {}", self.id, e))?;
+        let out = t.apply(&img);
+        let bytes = if self.png() { imaging::encode_png(&out)? } else { imaging::encode_jpeg(&out)? };
+        metrics::MEDIA_VARIANTS_RENDERED.inc();
+        // a failed cache write still serves the variant
+        if let Err(e) = store(&file, &bytes) { eprintln!("media cache write failed: {}", e); }
+        Ok(bytes)
+    }
+}
+
+fn cache_dir() -> PathBuf {
+    env::var("MEDIA_CACHE_DIR").map(PathBuf::from).unwrap_or_else(|_| PathBuf::from("data").join("media_cache"))
+}
+
+fn cache_max_bytes() -> u64 {
+    env::var("MEDIA_CACHE_MAX_BYTES").ok().and_then(|s| s.parse().ok()).unwrap_or(512 * 1024 * 1024)
+}
+
+// bytes in the cache directory; None until the directory has been measured
+static CACHE_BYTES: Lazy<Mutex<Option<u64>>> = Lazy::new(|| Mutex::new(None));
+
+fn store(file: &Path, bytes: &[u8]) -> Result<(), String> {
+    if let Some(dir) = file.parent() { std::fs::create_dir_all(dir).map_err(|e| format!("mkdir error: {}", e))?; }
+    // write then rename, so a request racing this one never reads half a file
+    let tmp = file.with_extension(format!("tmp-{}", uuid::Uuid::new_v4()));
+    std::fs::write(&tmp, bytes).map_err(|e| format!("write error: {}", e))?;
+    // renamed under the lock, so two renders of the same variant count its bytes once
+    let mut total = CACHE_BYTES.lock();
+    let replaced = std::fs::metadata(file).map(|m| m.len()).unwrap_or(0);
+    std::fs::rename(&tmp, file).map_err(|e| { let _ = std::fs::remove_file(&tmp); format!("rename error: {}", e) })?;
+    let size = match *total {
+        Some(n) => (n + bytes.len() as u64).saturating_sub(replaced),
+        // measured once, with this variant already in it
+        None => cached_files().iter().map(|(_, len, _)| len).sum(),
+    };
+    *total = Some(if size > cache_max_bytes() { evict(cache_max_bytes() / 4 * 3) } else { size });
+    Ok(())
+}
+
+// (path, length, modified) of every cached variant
+fn cached_files() -> Vec<(PathBuf, u64, std::time::SystemTime)> {
+    let Ok(dirs) = std::fs::read_dir(cache_dir()) else { return Vec::new() };
+    dirs.flatten()
+        .filter_map(|d| std::fs::read_dir(d.path()).ok())
+        .flat_map(|files| files.flatten())
+        .filter_map(|f| {
+            let meta = f.metadata().ok().filter(|m| m.is_file())?;
+            Some((f.path(), meta.len(), meta.modified().ok()?))
+        })
+        .collect()
+}
+
+// removes the oldest variants until at most `target` bytes are left; returns what is left
+fn evict(target: u64) -> u64 {
+    let mut files = cached_files();
+    files.sort_by_key(|(_, _, modified)| *modified);
+    let mut total: u64 = files.iter().map(|(_, len, _)| len).sum();
+    for (path, len, _) in files {
+        if total <= target { break; }
+        if std::fs::remove_file(&path).is_ok() {
+            total -= len;
+            metrics::MEDIA_VARIANTS_EVICTED.inc();
+        }
+    }
+    total
+}
+
+/// Drops every cached variant of a photo, e.g. when it is deleted.
+pub fn purge(id: &str) {
+    if !valid_id(id) { return; }
+    let dir = cache_dir().join(id);
+    if dir.exists() {
+        let _ = std::fs::remove_dir_all(dir);
+        *CACHE_BYTES.lock() = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::RgbaImage;
+
+    fn query(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    #[test]
+    fn test_from_query_reports_each_bad_parameter() {
+        let t = Transform::from_query(&query(&[("h", "300"), ("w", "400"), ("fit", "cover"), ("crop", "0.1,0.2,0.5,0.5"), ("grayscale", "1"), ("lang", "en")])).unwrap();
+        assert_eq!((t.w, t.h, t.fit, t.crop, t.grayscale, t.blur), (Some(400), Some(304), Fit::Cover, Some([0.1, 0.2, 0.5, 0.5]), true, 0.0));
+        // nearby values land on the same variant
+        let rounded = Transform::from_query(&query(&[("w", "7"), ("crop", "0.123,0.2,0.5,0.5"), ("blur", "2.7")])).unwrap();
+        assert_eq!((rounded.w, rounded.crop, rounded.blur), (Some(16), Some([0.12, 0.2, 0.5, 0.5]), 2.5));
+        // parameter order and unrelated parameters do not change the variant
+        let same = Transform::from_query(&query(&[("grayscale", "true"), ("crop", "0.1,0.2,0.5,0.5"), ("fit", "cover"), ("w", "400"), ("h", "300")])).unwrap();
+        assert_eq!(t.canonical(), same.canonical());
+        let errors = Transform::from_query(&query(&[("w", "0"), ("h", "9999"), ("fit", "zoom"), ("crop", "0.6,0,0.5,1"), ("grayscale", "yes"), ("blur", "NaN")])).unwrap_err();
+        assert_eq!(errors.len(), 6, "{:?}", errors);
+    }
+
+    #[test]
+    fn test_fit_modes_crop_and_never_enlarge() {
+        let img = DynamicImage::ImageRgba8(RgbaImage::new(800, 600));
+        let size = |pairs: &[(&str, &str)]| {
+            let out = Transform::from_query(&query(pairs)).unwrap().apply(&img);
+            (out.width(), out.height())
+        };
+        assert_eq!(size(&[]), (800, 600));
+        assert_eq!(size(&[("w", "400"), ("h", "400")]), (400, 300));
+        assert_eq!(size(&[("w", "400"), ("h", "400"), ("fit", "cover")]), (400, 400));
+        assert_eq!(size(&[("w", "400"), ("h", "400"), ("fit", "fill")]), (400, 400));
+        assert_eq!(size(&[("h", "144")]), (192, 144));
+        assert_eq!(size(&[("w", "1600")]), (800, 600));
+        assert_eq!(size(&[("w", "1600"), ("h", "896"), ("fit", "cover")]), (800, 448));
+        assert_eq!(size(&[("crop", "0.5,0.5,0.5,0.5"), ("w", "96")]), (96, 72));
+    }
+}
@@ -0,0 +1,57 @@
+// Process-wide counters, served at GET /metrics in the Prometheus text format.
+// Values start at zero with every process; Prometheus handles the resets.
+
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+pub struct Metric {
+    name: &'static str,
+    help: &'static str,
+    kind: &'static str,
+    value: AtomicU64,
+}
+
+impl Metric {
+    const fn new(name: &'static str, kind: &'static str, help: &'static str) -> Metric {
+        Metric { name, help, kind, value: AtomicU64::new(0) }
+    }
+
+    pub fn inc(&self) {
+        self.add(1);
+    }
+
+    pub fn add(&self, n: u64) {
+        self.value.fetch_add(n, Ordering::Relaxed);
+    }
+
+    pub fn set(&self, n: u64) {
+        self.value.store(n, Ordering::Relaxed);
+    }
+
+    pub fn get(&self) -> u64 {
+        self.value.load(Ordering::Relaxed)
+    }
+}
+
+pub static QUIZ_STORE_ENTRIES: Metric = Metric::new("tanuki_quiz_store_entries", "gauge", "Generated quizzes waiting for an answer.");
+pub static QUIZZES_STORED: Metric = Metric::new("tanuki_quizzes_stored_total", "counter", "Generated quizzes put in the quiz store.");
+pub static QUIZZES_EXPIRED: Metric = Metric::new("tanuki_quizzes_expired_total", "counter", "Quizzes removed unanswered after QUIZ_TTL_SECS or their time limit.");
+pub static QUIZZES_EVICTED: Metric = Metric::new("tanuki_quizzes_evicted_total", "counter", "Least recently used quizzes evicted because the store was full.");
+pub static QUIZZES_REJECTED: Metric = Metric::new("tanuki_quizzes_rejected_total", "counter", "Quiz requests refused because the client had too many open quizzes.");
+pub static QUIZ_TOKENS_REPLAYED: Metric = Metric::new("tanuki_quiz_tokens_replayed_total", "counter", "Lookups of a quiz token already answered on this instance.");
+pub static MEDIA_CACHE_HITS: Metric = Metric::new("tanuki_media_cache_hits_total", "counter", "Photo variants served from the media cache.");
+pub static MEDIA_VARIANTS_RENDERED: Metric = Metric::new("tanuki_media_variants_rendered_total", "counter", "Photo variants rendered because they were not cached.");
+pub static MEDIA_VARIANTS_EVICTED: Metric = Metric::new("tanuki_media_variants_evicted_total", "counter", "Cached photo variants removed because the cache passed MEDIA_CACHE_MAX_BYTES.");
+
+static ALL: &[&Metric] = &[&QUIZ_STORE_ENTRIES, &QUIZZES_STORED, &QUIZZES_EXPIRED, &QUIZZES_EVICTED, &QUIZZES_REJECTED, &QUIZ_TOKENS_REPLAYED, &MEDIA_CACHE_HITS, &MEDIA_VARIANTS_RENDERED, &MEDIA_VARIANTS_EVICTED];
+
+/// Every metric in the text exposition format.
+pub fn render() -> String {
+    let mut out = String::new();
+    for m in ALL {
+        let _ = writeln!(out, "# HELP {} {}", m.name, m.help);
+        let _ = writeln!(out, "# TYPE {} {}", m.name, m.kind);
+        let _ = writeln!(out, "{} {}", m.name, m.get());
+    }
+    out
+}
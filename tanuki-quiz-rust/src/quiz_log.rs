@@ -0,0 +1,153 @@
+// Log of generated quizzes.
+//
+// Every generated quiz is drawn from an RNG seeded with a per-quiz seed. The seed and the
+// generator parameters are appended to data/quiz_log.jsonl (override with QUIZ_LOG_PATH),
+// one JSON record per line, so an admin can regenerate the exact quiz a player saw from
+// its id long after it has expired from QUIZ_STORE. Once the log reaches QUIZ_LOG_MAX_BYTES
+// (default 50 MiB) it is renamed to quiz_log.jsonl.1, replacing the previous one, so at most
+// two files' worth of quizzes can be replayed and a lookup never scans more than that.
+
+use chrono::{DateTime, Utc};
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+use crate::reveal::Style;
+use crate::review::Weights;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct QuizRecord {
+    pub id: String,
+    // "choice" or "tanuki_or_not"
+    pub kind: String,
+    pub seed: u64,
+    pub difficulty: String,
+    // TANUKI_RATIO in effect when a tanuki_or_not quiz was generated
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tanuki_ratio: Option<f64>,
+    // what the player was shown, to check that a regenerated quiz is really the same
+    pub answer_category: String,
+    pub tokens: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session_id: Option<String>,
+    // selection weights from the player's mistake history, if any (see review)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub weights: Option<Weights>,
+    // the feature a hotspot quiz asked for
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub feature: Option<String>,
+    // the frame of a reveal quiz; each frame is logged under its own id
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub style: Option<Style>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub step: Option<usize>,
+    pub created_at: DateTime<Utc>,
+}
+
+// serializes appends so records never interleave
+static LOG_LOCK: Lazy<Mutex<()>> = Lazy::new(|| Mutex::new(()));
+
+fn log_path() -> PathBuf {
+    env::var("QUIZ_LOG_PATH").map(PathBuf::from).unwrap_or_else(|_| PathBuf::from("data").join("quiz_log.jsonl"))
+}
+
+/// Fresh seed for a quiz. Kept to 53 bits so it survives a round trip through JavaScript numbers.
+pub fn random_seed() -> u64 {
+    rand::thread_rng().gen::<u64>() >> 11
+}
+
+fn max_bytes() -> u64 {
+    env::var("QUIZ_LOG_MAX_BYTES").ok().and_then(|s| s.parse().ok()).unwrap_or(50 * 1024 * 1024)
+}
+
+// the previous log, e.g. data/quiz_log.jsonl.1
+fn rotated_path(p: &Path) -> PathBuf {
+    let mut name = p.as_os_str().to_os_string();
+    name.push(".1");
+    PathBuf::from(name)
+}
+
+pub fn append(record: &QuizRecord) -> Result<(), String> {
+    append_to(&log_path(), record, max_bytes())
+}
+
+fn append_to(p: &Path, record: &QuizRecord, max_bytes: u64) -> Result<(), String> {
+    let line = serde_json::to_string(record).map_err(|e| e.to_string())?;
+    let _guard = LOG_LOCK.lock();
+    if let Some(dir) = p.parent() { std::fs::create_dir_all(dir).map_err(|e| format!("mkdir error: {}", e))?; }
+    if std::fs::metadata(p).is_ok_and(|m| m.len() >= max_bytes) {
+        std::fs::rename(p, rotated_path(p)).map_err(|e| format!("rotate error: {}", e))?;
+    }
+    let mut f = std::fs::OpenOptions::new().create(true).append(true).open(p).map_err(|e| format!("open error: {}", e))?;
+    writeln!(f, "{}", line).map_err(|e| format!("write error: {}", e))
+}
+
+/// Most recent record for a quiz id, from the current log and then the previous one. Unreadable
+/// lines are skipped. This reads up to two whole files, so call it from a blocking task.
+pub fn find(id: &str) -> Option<QuizRecord> {
+    find_at(&log_path(), id)
+}
+
+// no lock: appends only add whole lines, and a half-written last line is skipped like any bad line
+fn find_at(p: &Path, id: &str) -> Option<QuizRecord> {
+    [p.to_path_buf(), rotated_path(p)].iter().find_map(|path| {
+        let f = std::fs::File::open(path).ok()?;
+        find_in(BufReader::new(f), id)
+    })
+}
+
+fn find_in(reader: impl BufRead, id: &str) -> Option<QuizRecord> {
+    reader.lines()
+        .map_while(Result::ok)
+        .filter_map(|line| serde_json::from_str::<QuizRecord>(&line).ok())
+        .filter(|r| r.id == id)
+        .last()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(id: &str, seed: u64) -> QuizRecord {
+        QuizRecord { id: id.to_string(), kind: "choice".to_string(), seed, difficulty: "normal".to_string(), tanuki_ratio: None, answer_category: "tanuki".to_string(), tokens: vec!["ab".to_string()], session_id: None, weights: None, feature: None, style: None, step: None, created_at: Utc::now() }
+    }
+
+    #[test]
+    fn test_find_in_skips_bad_lines_and_takes_latest() {
+        let lines = [
+            serde_json::to_string(&record("a", 1)).unwrap(),
+            "not json".to_string(),
+            serde_json::to_string(&record("b", 2)).unwrap(),
+            serde_json::to_string(&record("a", 3)).unwrap(),
+        ].join("\n");
+        assert_eq!(find_in(lines.as_bytes(), "a").map(|r| r.seed), Some(3));
+        assert_eq!(find_in(lines.as_bytes(), "b").map(|r| r.seed), Some(2));
+        assert!(find_in(lines.as_bytes(), "c").is_none());
+    }
+
+    #[test]
+    fn test_log_rotates_and_finds_in_previous_file() {
+        let p = std::env::temp_dir().join(format!("tanuki-quiz-log-{}.jsonl", uuid::Uuid::new_v4()));
+        let line_len = serde_json::to_string(&record("a", 1)).unwrap().len() as u64 + 1;
+        append_to(&p, &record("a", 1), line_len).unwrap();
+        // the log is full, so this append moves "a" to the previous file
+        append_to(&p, &record("b", 2), line_len).unwrap();
+        assert_eq!(find_at(&p, "a").map(|r| r.seed), Some(1));
+        assert_eq!(find_at(&p, "b").map(|r| r.seed), Some(2));
+        // a second rotation drops "a" for good
+        append_to(&p, &record("c", 3), line_len).unwrap();
+        assert!(find_at(&p, "a").is_none());
+        assert_eq!(find_at(&p, "b").map(|r| r.seed), Some(2));
+        let _ = std::fs::remove_file(&p);
+        let _ = std::fs::remove_file(rotated_path(&p));
+    }
+
+    #[test]
+    fn test_random_seed_fits_in_a_js_number() {
+        for _ in 0..100 { assert!(random_seed() < (1u64 << 53)); }
+    }
+}
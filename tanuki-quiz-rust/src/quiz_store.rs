@@ -0,0 +1,746 @@
+// Storage for generated quizzes between generation and answer.
+//
+// Handlers reach the store through axum State as `Quizzes`, which runs the calls of a store that
+// touches the disk on the blocking pool. QUIZ_STORE picks the implementation:
+//   memory (default)  a HashMap behind a mutex; everything in flight is lost on restart
+//   file              one JSON file per quiz under QUIZ_STORE_DIR (default data/quiz_store),
+//                     so quizzes survive restarts and deploys
+//   token             nothing is stored: the quiz travels inside its id (see quiz_token), so any
+//                     instance sharing QUIZ_TOKEN_SECRET and QUIZ_TOKEN_REPLAY_DIR can score it
+// A store that cannot be opened stops the server rather than quietly keeping quizzes in memory.
+// Times are wall-clock (UTC) so they mean the same thing after a restart.
+//
+// Only quizzes and receipts are stored here. Sessions, rooms and the daily challenge are kept in
+// memory (see session, room), so after a restart a stored session round is still scored, but the
+// session it belonged to is gone and the answer counts as a single quiz.
+//
+// A scored quiz leaves a Receipt behind for QUIZ_RECEIPT_SECS (default 5 minutes), so a client that
+// retries after a dropped response gets its original result instead of "unknown". The receipt starts out
+// pending when the quiz is taken, so a duplicate that arrives while the answer is being scored waits for
+// the result instead of finding neither quiz nor receipt. Receipts carry the quiz kind, and a lookup for
+// another kind does not see them.
+//
+// Memory and file stores are wrapped in a BoundedStore, which enforces QUIZ_TTL_SECS,
+// QUIZ_STORE_MAX_ENTRIES and QUIZ_MAX_PER_CLIENT (see Limits). Its index of ids, last use and owning
+// client lives in this process, so with a shared file store each process bounds only the quizzes it
+// has seen.
+
+use chrono::{DateTime, Utc};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::env;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::{metrics, quiz_token};
+use crate::{GeneratedQuiz, QuizKind};
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct StoredQuiz {
+    // server-made UUID; sessions and the quiz log know the quiz by this, whatever id the client holds
+    pub id: String,
+    pub quiz: GeneratedQuiz,
+    pub issued_at: DateTime<Utc>,
+    // who asked for the quiz (see client_key in main); counts toward their outstanding limit
+    #[serde(default)]
+    pub client: Option<String>,
+}
+
+impl StoredQuiz {
+    pub fn new(id: &str, quiz: GeneratedQuiz, client: Option<String>) -> StoredQuiz {
+        StoredQuiz { id: id.to_string(), quiz, issued_at: Utc::now(), client }
+    }
+
+    /// Time since the quiz was issued; zero if the clock went backwards.
+    pub fn elapsed(&self, now: DateTime<Utc>) -> Duration {
+        (now - self.issued_at).to_std().unwrap_or(Duration::ZERO)
+    }
+}
+
+/// The result a scored quiz was answered with.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Receipt {
+    pub kind: QuizKind,
+    // what the client submitted, e.g. the choice token
+    pub answer: String,
+    // the response body that was sent; Null while pending
+    pub result: serde_json::Value,
+    // the quiz was taken and is being scored
+    #[serde(default)]
+    pub pending: bool,
+    pub expires_at: DateTime<Utc>,
+}
+
+impl Receipt {
+    pub fn new(kind: QuizKind, answer: &str, result: serde_json::Value) -> Receipt {
+        let ttl = chrono::Duration::from_std(receipt_ttl()).unwrap_or_else(|_| chrono::Duration::minutes(5));
+        Receipt { kind, answer: answer.to_string(), result, pending: false, expires_at: Utc::now() + ttl }
+    }
+
+    /// Left by `take` until the result is known.
+    pub fn pending(kind: QuizKind, answer: &str) -> Receipt {
+        Receipt { pending: true, ..Receipt::new(kind, answer, serde_json::Value::Null) }
+    }
+
+    /// The receipt, unless it has expired by `now`.
+    pub fn live(self, now: DateTime<Utc>) -> Option<Receipt> {
+        Some(self).filter(|r| r.expires_at > now)
+    }
+}
+
+/// How long receipts are kept, QUIZ_RECEIPT_SECS (default 5 minutes).
+pub fn receipt_ttl() -> Duration {
+    Duration::from_secs(env::var("QUIZ_RECEIPT_SECS").ok().and_then(|s| s.parse().ok()).unwrap_or(5 * 60))
+}
+
+// How long a duplicate submit waits for a pending receipt, and how often it looks.
+const RECEIPT_WAIT: Duration = Duration::from_secs(3);
+const RECEIPT_POLL: Duration = Duration::from_millis(25);
+
+// `get` and `take` look quizzes up by the id `insert` returned, which is what clients hold.
+pub trait QuizStore: Send + Sync {
+    /// Store a quiz and return the id clients use for it: `quiz.id`, unless the store seals the quiz into the id.
+    fn insert(&self, quiz: StoredQuiz) -> String;
+    fn get(&self, id: &str) -> Option<StoredQuiz>;
+    /// Remove and return a quiz. Of several concurrent callers only one gets it, so an answer is scored once.
+    /// The one that does leaves `pending` as the quiz's receipt.
+    fn take(&self, id: &str, pending: Option<Receipt>) -> Option<StoredQuiz>;
+    /// Keep the receipt of a scored quiz under the id the client answered with.
+    fn put_receipt(&self, id: &str, receipt: Receipt);
+    /// Forget a pending receipt when the answer was refused rather than scored.
+    fn drop_receipt(&self, id: &str);
+    /// The receipt for `id`, until it expires.
+    fn receipt(&self, id: &str) -> Option<Receipt>;
+    /// Remove every quiz `expired` says is expired and return them, along with any dropped unanswered for
+    /// other reasons. Expired receipts are dropped too.
+    fn remove_expired(&self, expired: &dyn Fn(&StoredQuiz) -> bool) -> Vec<StoredQuiz>;
+    /// Every stored quiz, used to rebuild BoundedStore's index at startup.
+    fn list(&self) -> Vec<StoredQuiz>;
+    /// Refuse a new quiz for a client already holding too many open ones.
+    fn check_client(&self, _client: &str) -> Result<(), ClientLimit> {
+        Ok(())
+    }
+    /// True when calls do file I/O and must stay off the async workers.
+    fn blocking(&self) -> bool {
+        false
+    }
+}
+
+/// A store as handlers use it: the QuizStore calls as async methods. Calls into a store that touches
+/// the disk run on the blocking pool; in-memory stores are called directly.
+#[derive(Clone)]
+pub struct Quizzes {
+    store: Arc<dyn QuizStore>,
+}
+
+impl Quizzes {
+    pub fn new(store: Arc<dyn QuizStore>) -> Quizzes {
+        Quizzes { store }
+    }
+
+    async fn run<T: Send + 'static>(&self, f: impl FnOnce(&dyn QuizStore) -> T + Send + 'static) -> T {
+        if !self.store.blocking() { return f(self.store.as_ref()); }
+        let store = self.store.clone();
+        match tokio::task::spawn_blocking(move || f(store.as_ref())).await {
+            Ok(out) => out,
+            Err(e) => std::panic::resume_unwind(e.into_panic()),
+        }
+    }
+
+    pub async fn insert(&self, quiz: StoredQuiz) -> String {
+        self.run(move |s| s.insert(quiz)).await
+    }
+
+    pub async fn get(&self, id: &str) -> Option<StoredQuiz> {
+        let id = id.to_string();
+        self.run(move |s| s.get(&id)).await
+    }
+
+    pub async fn take(&self, id: &str, pending: Option<Receipt>) -> Option<StoredQuiz> {
+        let id = id.to_string();
+        self.run(move |s| s.take(&id, pending)).await
+    }
+
+    pub async fn put_receipt(&self, id: &str, receipt: Receipt) {
+        let id = id.to_string();
+        self.run(move |s| s.put_receipt(&id, receipt)).await
+    }
+
+    pub async fn drop_receipt(&self, id: &str) {
+        let id = id.to_string();
+        self.run(move |s| s.drop_receipt(&id)).await
+    }
+
+    /// The receipt of a `kind` quiz answered under `id`. A pending one is waited for, up to RECEIPT_WAIT.
+    pub async fn receipt(&self, id: &str, kind: QuizKind) -> Option<Receipt> {
+        self.settled_receipt(id, kind, false).await
+    }
+
+    /// Like `receipt`, for a caller that just lost `take` for `id`: the winner may not have written its
+    /// pending receipt yet, so a missing one is waited for too.
+    pub async fn raced_receipt(&self, id: &str, kind: QuizKind) -> Option<Receipt> {
+        self.settled_receipt(id, kind, true).await
+    }
+
+    async fn settled_receipt(&self, id: &str, kind: QuizKind, wait_for_missing: bool) -> Option<Receipt> {
+        let deadline = tokio::time::Instant::now() + RECEIPT_WAIT;
+        loop {
+            let key = id.to_string();
+            let receipt = self.run(move |s| s.receipt(&key)).await.filter(|r| r.kind == kind);
+            let settled = match &receipt {
+                Some(r) => !r.pending,
+                None => !wait_for_missing,
+            };
+            if settled { return receipt; }
+            if tokio::time::Instant::now() >= deadline { return None; }
+            tokio::time::sleep(RECEIPT_POLL).await;
+        }
+    }
+
+    pub async fn remove_expired(&self, expired: impl Fn(&StoredQuiz) -> bool + Send + 'static) -> Vec<StoredQuiz> {
+        self.run(move |s| s.remove_expired(&expired)).await
+    }
+
+    pub async fn check_client(&self, client: &str) -> Result<(), ClientLimit> {
+        let client = client.to_string();
+        self.run(move |s| s.check_client(&client)).await
+    }
+}
+
+#[derive(Default)]
+pub struct MemoryStore {
+    quizzes: Mutex<HashMap<String, StoredQuiz>>,
+    receipts: Mutex<HashMap<String, Receipt>>,
+}
+
+impl QuizStore for MemoryStore {
+    fn insert(&self, quiz: StoredQuiz) -> String {
+        let id = quiz.id.clone();
+        self.quizzes.lock().insert(id.clone(), quiz);
+        id
+    }
+
+    fn get(&self, id: &str) -> Option<StoredQuiz> {
+        self.quizzes.lock().get(id).cloned()
+    }
+
+    // the pending receipt is in place before the quiz lock is released, so no caller sees neither
+    fn take(&self, id: &str, pending: Option<Receipt>) -> Option<StoredQuiz> {
+        let mut quizzes = self.quizzes.lock();
+        let quiz = quizzes.remove(id)?;
+        if let Some(r) = pending { self.receipts.lock().insert(id.to_string(), r); }
+        Some(quiz)
+    }
+
+    fn put_receipt(&self, id: &str, receipt: Receipt) {
+        self.receipts.lock().insert(id.to_string(), receipt);
+    }
+
+    fn drop_receipt(&self, id: &str) {
+        self.receipts.lock().remove(id);
+    }
+
+    fn receipt(&self, id: &str) -> Option<Receipt> {
+        self.receipts.lock().get(id).cloned()?.live(Utc::now())
+    }
+
+    fn remove_expired(&self, expired: &dyn Fn(&StoredQuiz) -> bool) -> Vec<StoredQuiz> {
+        let now = Utc::now();
+        self.receipts.lock().retain(|_, r| r.expires_at > now);
+        let mut quizzes = self.quizzes.lock();
+        let ids: Vec<String> = quizzes.iter().filter(|(_, q)| expired(q)).map(|(id, _)| id.clone()).collect();
+        ids.into_iter().filter_map(|id| quizzes.remove(&id)).collect()
+    }
+
+    fn list(&self) -> Vec<StoredQuiz> {
+        self.quizzes.lock().values().cloned().collect()
+    }
+}
+
+// a temporary file this old belongs to a write that never finished
+const STALE_TMP_AGE: Duration = Duration::from_secs(60);
+
+pub struct FileStore {
+    dir: PathBuf,
+}
+
+impl FileStore {
+    /// Opens `dir`, creating it if needed, and checks that quizzes can be written there.
+    pub fn new(dir: PathBuf) -> Result<FileStore, String> {
+        std::fs::create_dir_all(&dir).map_err(|e| format!("mkdir error: {}", e))?;
+        let probe = dir.join(format!(".probe-{}", uuid::Uuid::new_v4()));
+        std::fs::write(&probe, b"").and_then(|_| std::fs::remove_file(&probe)).map_err(|e| format!("not writable: {}", e))?;
+        Ok(FileStore { dir })
+    }
+
+    // ids are server-made UUIDs; anything else is never a file name
+    fn path(&self, id: &str, extension: &str) -> Option<PathBuf> {
+        if id.is_empty() || !id.chars().all(|c| c.is_ascii_hexdigit() || c == '-') { return None; }
+        Some(self.dir.join(format!("{}.{}", id, extension)))
+    }
+
+    // written to a temporary name and renamed, so readers never see half a file
+    fn write(path: &PathBuf, value: &impl Serialize) -> Result<(), String> {
+        let mut tmp = path.clone().into_os_string();
+        tmp.push(".tmp");
+        serde_json::to_vec(value).map_err(|e| e.to_string())
+            .and_then(|bytes| std::fs::write(&tmp, bytes).map_err(|e| e.to_string()))
+            .and_then(|_| std::fs::rename(&tmp, path).map_err(|e| e.to_string()))
+    }
+
+    fn read(path: &PathBuf) -> Option<StoredQuiz> {
+        std::fs::read_to_string(path).ok().and_then(|s| serde_json::from_str(&s).ok())
+    }
+
+    // every file in the directory with this extension
+    fn files(&self, extension: &str) -> Vec<PathBuf> {
+        let Ok(entries) = std::fs::read_dir(&self.dir) else { return Vec::new() };
+        entries.flatten().map(|entry| entry.path()).filter(|path| path.extension().is_some_and(|e| e == extension)).collect()
+    }
+
+    // every <id>.json quiz; None when the file cannot be read
+    fn scan(&self) -> Vec<(PathBuf, Option<StoredQuiz>)> {
+        self.files("json").into_iter().map(|path| {
+            let quiz = FileStore::read(&path);
+            (path, quiz)
+        }).collect()
+    }
+
+    fn read_receipt(path: &PathBuf) -> Option<Receipt> {
+        std::fs::read_to_string(path).ok().and_then(|s| serde_json::from_str(&s).ok())
+    }
+}
+
+impl QuizStore for FileStore {
+    fn insert(&self, quiz: StoredQuiz) -> String {
+        let id = quiz.id.clone();
+        let Some(path) = self.path(&id, "json") else { return id };
+        if let Err(e) = FileStore::write(&path, &quiz) { eprintln!("quiz store write failed for {}: {}", id, e); }
+        id
+    }
+
+    fn get(&self, id: &str) -> Option<StoredQuiz> {
+        FileStore::read(&self.path(id, "json")?)
+    }
+
+    // only the caller whose remove_file succeeds gets the quiz; a duplicate that loses waits for the
+    // pending receipt written right after (see Quizzes::raced_receipt)
+    fn take(&self, id: &str, pending: Option<Receipt>) -> Option<StoredQuiz> {
+        let path = self.path(id, "json")?;
+        let quiz = FileStore::read(&path)?;
+        std::fs::remove_file(&path).ok()?;
+        if let Some(r) = pending { self.put_receipt(id, r); }
+        Some(quiz)
+    }
+
+    fn put_receipt(&self, id: &str, receipt: Receipt) {
+        let Some(path) = self.path(id, "receipt") else { return };
+        if let Err(e) = FileStore::write(&path, &receipt) { eprintln!("quiz receipt write failed for {}: {}", id, e); }
+    }
+
+    fn drop_receipt(&self, id: &str) {
+        if let Some(path) = self.path(id, "receipt") { let _ = std::fs::remove_file(path); }
+    }
+
+    fn receipt(&self, id: &str) -> Option<Receipt> {
+        FileStore::read_receipt(&self.path(id, "receipt")?)?.live(Utc::now())
+    }
+
+    fn remove_expired(&self, expired: &dyn Fn(&StoredQuiz) -> bool) -> Vec<StoredQuiz> {
+        let now = Utc::now();
+        // left behind by a crash between write and rename; recent ones may still be renamed by another process
+        for path in self.files("tmp") {
+            let stale = path.metadata().and_then(|m| m.modified()).ok().and_then(|t| t.elapsed().ok()).is_some_and(|age| age > STALE_TMP_AGE);
+            if stale { let _ = std::fs::remove_file(&path); }
+        }
+        for path in self.files("receipt") {
+            if FileStore::read_receipt(&path).and_then(|r| r.live(now)).is_none() { let _ = std::fs::remove_file(&path); }
+        }
+        let mut removed = Vec::new();
+        for (path, quiz) in self.scan() {
+            match quiz {
+                Some(q) if expired(&q) => {
+                    if std::fs::remove_file(&path).is_ok() { removed.push(q); }
+                }
+                Some(_) => {}
+                // unreadable leftovers, e.g. from an older version, are dropped
+                None => { let _ = std::fs::remove_file(&path); }
+            }
+        }
+        removed
+    }
+
+    fn list(&self) -> Vec<StoredQuiz> {
+        self.scan().into_iter().filter_map(|(_, quiz)| quiz).collect()
+    }
+
+    fn blocking(&self) -> bool {
+        true
+    }
+}
+
+/// Bounds on the quizzes waiting for an answer.
+#[derive(Clone, Copy, Debug)]
+pub struct Limits {
+    /// QUIZ_TTL_SECS (default 5 minutes): unanswered quizzes are dropped after this long.
+    pub ttl: Duration,
+    /// QUIZ_STORE_MAX_ENTRIES (default 10000): past this the least recently used quiz is evicted.
+    pub max_entries: usize,
+    /// QUIZ_MAX_PER_CLIENT (default 100, 0 for no limit): open quizzes one client may hold.
+    pub max_per_client: usize,
+}
+
+impl Limits {
+    pub fn from_env() -> Limits {
+        fn var<T: std::str::FromStr>(name: &str) -> Option<T> { env::var(name).ok().and_then(|s| s.parse().ok()) }
+        Limits {
+            ttl: Duration::from_secs(var("QUIZ_TTL_SECS").filter(|&s: &u64| s > 0).unwrap_or(5 * 60)),
+            max_entries: var("QUIZ_STORE_MAX_ENTRIES").filter(|&n: &usize| n > 0).unwrap_or(10_000),
+            max_per_client: var("QUIZ_MAX_PER_CLIENT").unwrap_or(100),
+        }
+    }
+
+    /// How often expired quizzes are swept: a fifth of the TTL, between 1 second and 1 minute.
+    pub fn sweep_interval(&self) -> Duration {
+        (self.ttl / 5).clamp(Duration::from_secs(1), Duration::from_secs(60))
+    }
+}
+
+/// The client already holds `max` open quizzes.
+#[derive(Debug, PartialEq)]
+pub struct ClientLimit {
+    pub max: usize,
+}
+
+struct IndexEntry {
+    used: u64,
+    client: Option<String>,
+    issued_at: DateTime<Utc>,
+    // part of a session; evicted only when nothing else is left
+    session_round: bool,
+}
+
+// recency and ownership of every stored id; `lru` maps a use counter to the id so the oldest is first
+#[derive(Default)]
+struct Index {
+    clock: u64,
+    entries: HashMap<String, IndexEntry>,
+    lru: BTreeMap<u64, String>,
+    per_client: HashMap<String, usize>,
+    // evicted since the last sweep, handed out by remove_expired like expired quizzes
+    evicted: Vec<StoredQuiz>,
+}
+
+impl Index {
+    fn add(&mut self, quiz: &StoredQuiz) {
+        self.forget(&quiz.id);
+        self.clock += 1;
+        if let Some(c) = &quiz.client { *self.per_client.entry(c.clone()).or_default() += 1; }
+        self.lru.insert(self.clock, quiz.id.clone());
+        let entry = IndexEntry { used: self.clock, client: quiz.client.clone(), issued_at: quiz.issued_at, session_round: quiz.quiz.session_id.is_some() };
+        self.entries.insert(quiz.id.clone(), entry);
+    }
+
+    fn touch(&mut self, id: &str) -> bool {
+        let Some(entry) = self.entries.get_mut(id) else { return false };
+        self.lru.remove(&entry.used);
+        self.clock += 1;
+        entry.used = self.clock;
+        self.lru.insert(self.clock, id.to_string());
+        true
+    }
+
+    fn forget(&mut self, id: &str) {
+        let Some(entry) = self.entries.remove(id) else { return };
+        self.lru.remove(&entry.used);
+        if let Some(c) = entry.client {
+            if let Some(n) = self.per_client.get_mut(&c) {
+                *n -= 1;
+                if *n == 0 { self.per_client.remove(&c); }
+            }
+        }
+    }
+
+    // the least recently used quiz, passing over session rounds: evicting one of those costs a player
+    // a round of their game, so it only happens when the store holds nothing else
+    fn eviction_candidate(&self) -> Option<String> {
+        let single = self.lru.values().find(|id| self.entries.get(*id).is_some_and(|e| !e.session_round));
+        single.or_else(|| self.lru.values().next()).cloned()
+    }
+
+    fn outstanding(&self, client: &str) -> usize {
+        self.per_client.get(client).copied().unwrap_or(0)
+    }
+}
+
+/// A backend with Limits applied. The index is updated under its lock and the backend is called after
+/// the lock is released, so a slow backend (the file store) never holds up other requests. The two can
+/// briefly disagree when calls race; an index entry whose quiz is gone is dropped when it is evicted, when
+/// a `get` misses, or by the sweep once it is older than the TTL.
+/// Expiry is left to `remove_expired`, which the cleanup task runs every `Limits::sweep_interval`;
+/// until then an expired quiz still counts toward its client's limit. Evicted quizzes are handed out by
+/// the next sweep too, so an evicted session round is recorded as timed out.
+pub struct BoundedStore {
+    inner: Arc<dyn QuizStore>,
+    limits: Limits,
+    index: Mutex<Index>,
+}
+
+impl BoundedStore {
+    /// Wraps `inner`, indexing the quizzes it already holds (a file store after a restart) oldest first.
+    pub fn new(inner: Arc<dyn QuizStore>, limits: Limits) -> BoundedStore {
+        let mut existing = inner.list();
+        existing.sort_by_key(|q| q.issued_at);
+        let mut index = Index::default();
+        for q in &existing { index.add(q); }
+        metrics::QUIZ_STORE_ENTRIES.set(index.entries.len() as u64);
+        BoundedStore { inner, limits, index: Mutex::new(index) }
+    }
+}
+
+impl QuizStore for BoundedStore {
+    // checked before any work (and before a session round is reserved), so concurrent requests can
+    // overshoot slightly; max_entries is the hard bound
+    fn check_client(&self, client: &str) -> Result<(), ClientLimit> {
+        let max = self.limits.max_per_client;
+        if max > 0 && self.index.lock().outstanding(client) >= max {
+            metrics::QUIZZES_REJECTED.inc();
+            return Err(ClientLimit { max });
+        }
+        Ok(())
+    }
+
+    // evicts the least recently used quizzes when full
+    fn insert(&self, quiz: StoredQuiz) -> String {
+        let (victims, entries) = {
+            let mut index = self.index.lock();
+            let mut victims = Vec::new();
+            while index.entries.len() >= self.limits.max_entries {
+                let Some(victim) = index.eviction_candidate() else { break };
+                index.forget(&victim);
+                victims.push(victim);
+            }
+            index.add(&quiz);
+            (victims, index.entries.len())
+        };
+        metrics::QUIZZES_EVICTED.add(victims.len() as u64);
+        let evicted: Vec<StoredQuiz> = victims.iter().filter_map(|id| self.inner.take(id, None)).collect();
+        if !evicted.is_empty() { self.index.lock().evicted.extend(evicted); }
+        let id = self.inner.insert(quiz);
+        metrics::QUIZZES_STORED.inc();
+        metrics::QUIZ_STORE_ENTRIES.set(entries as u64);
+        id
+    }
+
+    // marks the quiz as recently used; quizzes another process stored are picked up too
+    fn get(&self, id: &str) -> Option<StoredQuiz> {
+        let quiz = self.inner.get(id);
+        let mut index = self.index.lock();
+        match &quiz {
+            Some(q) => { if !index.touch(id) { index.add(q); } }
+            None => index.forget(id),
+        }
+        metrics::QUIZ_STORE_ENTRIES.set(index.entries.len() as u64);
+        quiz
+    }
+
+    fn take(&self, id: &str, pending: Option<Receipt>) -> Option<StoredQuiz> {
+        let quiz = self.inner.take(id, pending);
+        let mut index = self.index.lock();
+        index.forget(id);
+        metrics::QUIZ_STORE_ENTRIES.set(index.entries.len() as u64);
+        quiz
+    }
+
+    // receipts are small and short-lived, so they do not count toward the limits
+    fn put_receipt(&self, id: &str, receipt: Receipt) {
+        self.inner.put_receipt(id, receipt);
+    }
+
+    fn drop_receipt(&self, id: &str) {
+        self.inner.drop_receipt(id);
+    }
+
+    fn receipt(&self, id: &str) -> Option<Receipt> {
+        self.inner.receipt(id)
+    }
+
+    fn remove_expired(&self, expired: &dyn Fn(&StoredQuiz) -> bool) -> Vec<StoredQuiz> {
+        let mut removed = self.inner.remove_expired(expired);
+        let mut index = self.index.lock();
+        for q in &removed { index.forget(&q.id); }
+        // anything this old is gone from the backend, whatever a racing call left in the index
+        let cutoff = Utc::now() - chrono::Duration::from_std(self.limits.ttl).unwrap_or_else(|_| chrono::Duration::zero());
+        let stale: Vec<String> = index.entries.iter().filter(|(_, e)| e.issued_at < cutoff).map(|(id, _)| id.clone()).collect();
+        for id in stale { index.forget(&id); }
+        metrics::QUIZZES_EXPIRED.add(removed.len() as u64);
+        metrics::QUIZ_STORE_ENTRIES.set(index.entries.len() as u64);
+        removed.append(&mut index.evicted);
+        removed
+    }
+
+    fn list(&self) -> Vec<StoredQuiz> {
+        self.inner.list()
+    }
+
+    fn blocking(&self) -> bool {
+        self.inner.blocking()
+    }
+}
+
+/// Store selected by QUIZ_STORE (memory, file or token) with `limits` applied. An error when the
+/// file store cannot be opened, QUIZ_TOKEN_SECRET is missing or QUIZ_STORE is unknown.
+pub fn from_env(limits: Limits) -> Result<Arc<dyn QuizStore>, String> {
+    let bounded = |inner: Arc<dyn QuizStore>| -> Arc<dyn QuizStore> { Arc::new(BoundedStore::new(inner, limits)) };
+    match env::var("QUIZ_STORE").as_deref() {
+        Ok("file") => {
+            let dir = env::var("QUIZ_STORE_DIR").map(PathBuf::from).unwrap_or_else(|_| PathBuf::from("data").join("quiz_store"));
+            let store = FileStore::new(dir.clone()).map_err(|e| format!("cannot open quiz store {}: {}", dir.display(), e))?;
+            println!("storing quizzes in {}", dir.display());
+            Ok(bounded(Arc::new(store)))
+        }
+        // nothing is stored, so only the TTL applies
+        Ok("token") => {
+            let store = quiz_token::TokenStore::from_env(limits.ttl)?;
+            println!("issuing quizzes as signed tokens");
+            Ok(Arc::new(store))
+        }
+        Ok("memory") | Err(_) => Ok(bounded(Arc::new(MemoryStore::default()))),
+        Ok(other) => Err(format!("unknown QUIZ_STORE {:?}; use memory, file or token", other)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Difficulty, QuizKind};
+
+    fn quiz(id: &str, answer: &str) -> StoredQuiz {
+        let quiz = GeneratedQuiz { kind: QuizKind::Choice, seed: 1, difficulty: Difficulty::Normal, question: "q".to_string(), choices: Vec::new(), answer_category: answer.to_string(), session_id: None, player_id: None, weights: None, time_limit_ms: None, hotspot: None, reveal: None };
+        StoredQuiz::new(id, quiz, None)
+    }
+
+    // the contract every implementation has to keep
+    fn check_store(store: &dyn QuizStore) {
+        assert_eq!(store.insert(quiz("0a-1", "tanuki")), "0a-1");
+        store.insert(quiz("0a-2", "anaguma"));
+        assert_eq!(store.get("0a-1").map(|q| q.quiz.answer_category), Some("tanuki".to_string()));
+        assert!(store.take("0a-1", Some(Receipt::pending(QuizKind::Choice, "token-1"))).is_some());
+        assert!(store.take("0a-1", None).is_none());
+        // the loser of a take finds the winner's pending receipt
+        assert!(store.receipt("0a-1").is_some_and(|r| r.pending));
+        assert!(store.get("0a-1").is_none());
+        store.put_receipt("0a-1", Receipt::new(QuizKind::Choice, "token-1", serde_json::json!({ "correct": true })));
+        let mut stale = Receipt::new(QuizKind::Choice, "token-2", serde_json::Value::Null);
+        stale.expires_at = Utc::now() - chrono::Duration::seconds(1);
+        store.put_receipt("0a-3", stale);
+        assert!(store.receipt("0a-3").is_none());
+        let removed = store.remove_expired(&|q| q.quiz.answer_category == "anaguma");
+        assert_eq!(removed.len(), 1);
+        assert_eq!(removed[0].id, "0a-2");
+        assert!(store.get("0a-2").is_none());
+        // receipts outlive the quiz
+        assert_eq!(store.receipt("0a-1").map(|r| (r.answer, r.pending)), Some(("token-1".to_string(), false)));
+        store.drop_receipt("0a-1");
+        assert!(store.receipt("0a-1").is_none());
+    }
+
+    #[test]
+    fn test_file_store_refuses_unusable_dir() {
+        let file = env::temp_dir().join(format!("tanuki-quiz-store-{}", uuid::Uuid::new_v4()));
+        std::fs::write(&file, b"").unwrap();
+        assert!(FileStore::new(file.clone()).is_err());
+        let _ = std::fs::remove_file(&file);
+    }
+
+    #[test]
+    fn test_memory_store() {
+        check_store(&MemoryStore::default());
+    }
+
+    #[tokio::test]
+    async fn test_duplicate_waits_for_pending_receipt_of_its_kind() {
+        let quizzes = Quizzes::new(Arc::new(MemoryStore::default()));
+        quizzes.insert(quiz("0d", "tanuki")).await;
+        assert!(quizzes.take("0d", Some(Receipt::pending(QuizKind::Choice, "t"))).await.is_some());
+        let scorer = quizzes.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            scorer.put_receipt("0d", Receipt::new(QuizKind::Choice, "t", serde_json::json!({ "correct": true }))).await;
+        });
+        let receipt = quizzes.raced_receipt("0d", QuizKind::Choice).await.unwrap();
+        assert!(!receipt.pending && receipt.result["correct"] == true);
+        // the same id submitted as another kind of quiz has no receipt
+        assert!(quizzes.receipt("0d", QuizKind::Hotspot).await.is_none());
+    }
+
+    #[test]
+    fn test_bounded_store_evicts_least_recently_used_and_limits_clients() {
+        let limits = Limits { ttl: Duration::from_secs(60), max_entries: 3, max_per_client: 2 };
+        let store = BoundedStore::new(Arc::new(MemoryStore::default()), limits);
+        check_store(&store);
+        let owned = |id: &str, answer: &str, client: &str| StoredQuiz { client: Some(client.to_string()), ..quiz(id, answer) };
+        store.insert(owned("a", "tanuki", "alice"));
+        store.insert(owned("b", "tanuki", "alice"));
+        assert_eq!(store.check_client("alice"), Err(ClientLimit { max: 2 }));
+        store.insert(owned("c", "anaguma", "bob"));
+        // reading "a" makes "b" the least recently used
+        assert!(store.get("a").is_some());
+        store.insert(owned("d", "hakubishin", "bob"));
+        assert!(store.get("b").is_none());
+        // the eviction and an answer both free up room for alice
+        assert_eq!(store.check_client("alice"), Ok(()));
+        assert!(store.take("a", None).is_some());
+        store.insert(owned("e", "tanuki", "alice"));
+        // the next sweep reports the evicted quiz along with the expired one
+        let mut removed: Vec<String> = store.remove_expired(&|q| q.quiz.answer_category == "tanuki").into_iter().map(|q| q.id).collect();
+        removed.sort();
+        assert_eq!(removed, vec!["b", "e"]);
+        assert!(store.get("c").is_some() && store.get("d").is_some());
+    }
+
+    #[test]
+    fn test_bounded_store_keeps_session_rounds_while_it_can() {
+        let limits = Limits { ttl: Duration::from_secs(60), max_entries: 2, max_per_client: 0 };
+        let store = BoundedStore::new(Arc::new(MemoryStore::default()), limits);
+        let round = |id: &str| {
+            let mut q = quiz(id, "tanuki");
+            q.quiz.session_id = Some("s".to_string());
+            q
+        };
+        store.insert(round("r1"));
+        store.insert(quiz("a", "tanuki"));
+        store.insert(quiz("b", "tanuki"));
+        // "a" goes although the session round is older
+        assert!(store.get("r1").is_some() && store.get("a").is_none());
+        store.insert(round("r2"));
+        store.insert(round("r3"));
+        // with only session rounds left, the least recently used one goes, and the sweep reports it
+        let removed: Vec<String> = store.remove_expired(&|_| false).into_iter().map(|q| q.id).collect();
+        assert_eq!(removed, vec!["a", "b", "r1"]);
+    }
+
+    #[test]
+    fn test_file_store_survives_reopen_and_rejects_odd_ids() {
+        let dir = env::temp_dir().join(format!("tanuki-quiz-store-{}", uuid::Uuid::new_v4()));
+        check_store(&FileStore::new(dir.clone()).unwrap());
+        FileStore::new(dir.clone()).unwrap().insert(quiz("0b", "tanuki"));
+        // a new instance (a restarted server) still finds the quiz
+        assert!(FileStore::new(dir.clone()).unwrap().take("0b", None).is_some());
+        let store = FileStore::new(dir.clone()).unwrap();
+        store.insert(quiz("../escape", "tanuki"));
+        assert!(store.get("../escape").is_none());
+        assert!(!dir.parent().unwrap().join("escape.json").exists());
+        // a write that died before its rename is cleared once it is old enough
+        let tmp = dir.join("0c.json.tmp");
+        std::fs::write(&tmp, b"{").unwrap();
+        store.remove_expired(&|_| false);
+        assert!(tmp.exists());
+        std::fs::File::options().write(true).open(&tmp).unwrap().set_modified(std::time::SystemTime::now() - STALE_TMP_AGE * 2).unwrap();
+        store.remove_expired(&|_| false);
+        assert!(!tmp.exists());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
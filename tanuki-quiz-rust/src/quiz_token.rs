@@ -0,0 +1,319 @@
+// Stateless quiz store (QUIZ_STORE=token) for several instances behind a load balancer.
+//
+// Nothing is kept between generation and answer: the id handed to the client is the StoredQuiz itself,
+// encrypted with ChaCha20 and then signed with HMAC-SHA256 (encrypt-then-MAC). Both keys are derived
+// from QUIZ_TOKEN_SECRET, which every instance must share. A token is accepted until QUIZ_TTL_SECS
+// after it was issued.
+//
+// Token layout, base64url without padding:
+//   version (1 byte) | nonce (12 bytes) | ciphertext | HMAC-SHA256 of everything before it (32 bytes)
+//
+// Tokens cannot be revoked, so answered quiz ids are recorded, with their receipts, in
+// QUIZ_TOKEN_REPLAY_DIR until both the token and the receipt have expired. Every instance must share
+// that directory (e.g. an NFS mount); a record is created with create_new, so of several instances
+// scoring the same token only one succeeds. Token mode refuses to start without the directory.
+//
+// QUIZ_STORE_MAX_ENTRIES and QUIZ_MAX_PER_CLIENT do not apply here: they bound what a store holds for
+// unanswered quizzes, and a token store holds nothing for them, so QUIZ_TTL_SECS is the only bound.
+// The exception is session rounds. The instance that issued one keeps a copy until it is answered or
+// expires, so an abandoned round is handed to the sweep and recorded as timed out like in the other
+// stores. There is at most one open round per session, so that copy stays small.
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD as BASE64URL;
+use base64::Engine;
+use chacha20::cipher::{KeyIvInit, StreamCipher};
+use chacha20::ChaCha20;
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use parking_lot::Mutex;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::collections::HashMap;
+use std::env;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::metrics;
+use crate::quiz_store::{QuizStore, Receipt, StoredQuiz};
+
+type HmacSha256 = Hmac<Sha256>;
+
+const VERSION: u8 = 1;
+const NONCE_LEN: usize = 12;
+const MAC_LEN: usize = 32;
+/// Shortest QUIZ_TOKEN_SECRET accepted, in bytes.
+pub const MIN_SECRET_LEN: usize = 32;
+// a record or temporary file this old that cannot be read was left by a crash
+const STALE_FILE_AGE: Duration = Duration::from_secs(60);
+
+pub struct TokenStore {
+    enc_key: [u8; 32],
+    mac_key: [u8; 32],
+    ttl: Duration,
+    // shared with the other instances: one <quiz id>.json Answered record per answered quiz
+    replay_dir: PathBuf,
+    // session rounds this instance issued that are not answered yet, by quiz id
+    open_rounds: Mutex<HashMap<String, StoredQuiz>>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Answered {
+    // when the token expires; until then it must not be scored again
+    expires_at: DateTime<Utc>,
+    receipt: Option<Receipt>,
+}
+
+// a separate key per purpose, so the secret itself never keys the cipher
+fn derive_key(secret: &[u8], purpose: &[u8]) -> [u8; 32] {
+    let mut mac = HmacSha256::new_from_slice(secret).expect("HMAC accepts keys of any length");
+    mac.update(purpose);
+    mac.finalize().into_bytes().into()
+}
+
+impl TokenStore {
+    pub fn new(secret: &[u8], ttl: Duration, replay_dir: PathBuf) -> Result<TokenStore, String> {
+        std::fs::create_dir_all(&replay_dir).map_err(|e| format!("mkdir error: {}", e))?;
+        let probe = replay_dir.join(format!(".probe-{}", uuid::Uuid::new_v4()));
+        std::fs::write(&probe, b"").and_then(|_| std::fs::remove_file(&probe)).map_err(|e| format!("not writable: {}", e))?;
+        Ok(TokenStore {
+            enc_key: derive_key(secret, b"tanuki-quiz token encryption"),
+            mac_key: derive_key(secret, b"tanuki-quiz token signature"),
+            ttl,
+            replay_dir,
+            open_rounds: Mutex::new(HashMap::new()),
+        })
+    }
+
+    pub fn from_env(ttl: Duration) -> Result<TokenStore, String> {
+        let secret = env::var("QUIZ_TOKEN_SECRET").unwrap_or_default();
+        if secret.len() < MIN_SECRET_LEN {
+            return Err(format!("QUIZ_STORE=token needs a QUIZ_TOKEN_SECRET of at least {} bytes", MIN_SECRET_LEN));
+        }
+        // a per-instance default would let every instance score the same token once
+        let dir = env::var("QUIZ_TOKEN_REPLAY_DIR").map(PathBuf::from)
+            .map_err(|_| "QUIZ_STORE=token needs QUIZ_TOKEN_REPLAY_DIR, a directory all instances share".to_string())?;
+        TokenStore::new(secret.as_bytes(), ttl, dir.clone()).map_err(|e| format!("cannot use QUIZ_TOKEN_REPLAY_DIR {}: {}", dir.display(), e))
+    }
+
+    // quiz ids are server-made UUIDs; anything else is never a file name
+    fn record_path(&self, quiz_id: &str) -> Option<PathBuf> {
+        if quiz_id.is_empty() || !quiz_id.chars().all(|c| c.is_ascii_hexdigit() || c == '-') { return None; }
+        Some(self.replay_dir.join(format!("{}.json", quiz_id)))
+    }
+
+    fn answered(&self, quiz_id: &str) -> bool {
+        self.record_path(quiz_id).is_none_or(|p| p.exists())
+    }
+
+    // None while the record is being written, or if it is unreadable
+    fn read_record(path: &PathBuf) -> Option<Answered> {
+        std::fs::read(path).ok().and_then(|b| serde_json::from_slice(&b).ok())
+    }
+
+    // the one instance that creates the record gets to score the quiz
+    fn claim(&self, quiz_id: &str, expires_at: DateTime<Utc>, pending: Option<Receipt>) -> bool {
+        let Some(path) = self.record_path(quiz_id) else { return false };
+        let Ok(mut f) = OpenOptions::new().write(true).create_new(true).open(&path) else { return false };
+        let body = serde_json::to_vec(&Answered { expires_at, receipt: pending }).unwrap_or_default();
+        if let Err(e) = f.write_all(&body) { eprintln!("quiz token replay record write failed for {}: {}", quiz_id, e); }
+        true
+    }
+
+    // replaces the record in one rename, so other instances read either the old or the new one
+    fn set_receipt(&self, id: &str, receipt: Option<Receipt>) {
+        let Some(quiz) = self.open(id) else { return };
+        let Some(path) = self.record_path(&quiz.id) else { return };
+        let Some(mut answered) = TokenStore::read_record(&path) else { return };
+        answered.receipt = receipt;
+        let tmp = path.with_extension("json.tmp");
+        let written = serde_json::to_vec(&answered).map_err(|e| e.to_string())
+            .and_then(|b| std::fs::write(&tmp, b).map_err(|e| e.to_string()))
+            .and_then(|_| std::fs::rename(&tmp, &path).map_err(|e| e.to_string()));
+        if let Err(e) = written { eprintln!("quiz receipt write failed for {}: {}", quiz.id, e); }
+    }
+
+    fn signer(&self) -> HmacSha256 {
+        HmacSha256::new_from_slice(&self.mac_key).expect("HMAC accepts keys of any length")
+    }
+
+    fn seal(&self, quiz: &StoredQuiz) -> String {
+        let mut nonce = [0u8; NONCE_LEN];
+        rand::thread_rng().fill_bytes(&mut nonce);
+        let mut body = serde_json::to_vec(quiz).unwrap_or_default();
+        ChaCha20::new(&self.enc_key.into(), &nonce.into()).apply_keystream(&mut body);
+        let mut token = vec![VERSION];
+        token.extend_from_slice(&nonce);
+        token.extend_from_slice(&body);
+        let mut signer = self.signer();
+        signer.update(&token);
+        token.extend_from_slice(&signer.finalize().into_bytes());
+        BASE64URL.encode(token)
+    }
+
+    // the quiz inside a token, if the signature checks out and the token has not expired
+    fn open_live(&self, token: &str, now: DateTime<Utc>) -> Option<StoredQuiz> {
+        self.open(token).filter(|quiz| quiz.elapsed(now) <= self.ttl)
+    }
+
+    // the quiz inside a token if the signature checks out, expired or not
+    fn open(&self, token: &str) -> Option<StoredQuiz> {
+        let bytes = BASE64URL.decode(token).ok()?;
+        if bytes.len() < 1 + NONCE_LEN + MAC_LEN || bytes[0] != VERSION { return None; }
+        let (signed, tag) = bytes.split_at(bytes.len() - MAC_LEN);
+        // constant-time comparison; nothing is decrypted before the signature is verified
+        let mut signer = self.signer();
+        signer.update(signed);
+        signer.verify_slice(tag).ok()?;
+        let nonce: [u8; NONCE_LEN] = signed[1..1 + NONCE_LEN].try_into().ok()?;
+        let mut body = signed[1 + NONCE_LEN..].to_vec();
+        ChaCha20::new(&self.enc_key.into(), &nonce.into()).apply_keystream(&mut body);
+        serde_json::from_slice(&body).ok()
+    }
+}
+
+impl QuizStore for TokenStore {
+    fn insert(&self, quiz: StoredQuiz) -> String {
+        metrics::QUIZZES_STORED.inc();
+        let token = self.seal(&quiz);
+        if quiz.quiz.session_id.is_some() { self.open_rounds.lock().insert(quiz.id.clone(), quiz); }
+        token
+    }
+
+    fn get(&self, id: &str) -> Option<StoredQuiz> {
+        let quiz = self.open_live(id, Utc::now())?;
+        if self.answered(&quiz.id) {
+            metrics::QUIZ_TOKENS_REPLAYED.inc();
+            return None;
+        }
+        Some(quiz)
+    }
+
+    fn take(&self, id: &str, pending: Option<Receipt>) -> Option<StoredQuiz> {
+        let quiz = self.open_live(id, Utc::now())?;
+        let expires_at = quiz.issued_at + chrono::Duration::from_std(self.ttl).ok()?;
+        if !self.claim(&quiz.id, expires_at, pending) {
+            metrics::QUIZ_TOKENS_REPLAYED.inc();
+            return None;
+        }
+        self.open_rounds.lock().remove(&quiz.id);
+        Some(quiz)
+    }
+
+    fn put_receipt(&self, id: &str, receipt: Receipt) {
+        self.set_receipt(id, Some(receipt));
+    }
+
+    // the record stays: the token must still not be scored again
+    fn drop_receipt(&self, id: &str) {
+        self.set_receipt(id, None);
+    }
+
+    // a receipt can outlive its token, so the token is only checked for a valid signature
+    fn receipt(&self, id: &str) -> Option<Receipt> {
+        let quiz = self.open(id)?;
+        TokenStore::read_record(&self.record_path(&quiz.id)?)?.receipt?.live(Utc::now())
+    }
+
+    // trims the replay records and returns this instance's session rounds that expired unanswered
+    fn remove_expired(&self, expired: &dyn Fn(&StoredQuiz) -> bool) -> Vec<StoredQuiz> {
+        let now = Utc::now();
+        if let Ok(entries) = std::fs::read_dir(&self.replay_dir) {
+            for path in entries.flatten().map(|e| e.path()) {
+                let is_record = path.extension().is_some_and(|e| e == "json");
+                let keep = match (is_record, TokenStore::read_record(&path)) {
+                    (true, Some(a)) => a.expires_at > now || a.receipt.is_some_and(|r| r.expires_at > now),
+                    // being written right now, or left behind by a crash
+                    _ => path.metadata().and_then(|m| m.modified()).ok().and_then(|t| t.elapsed().ok()).is_none_or(|age| age <= STALE_FILE_AGE),
+                };
+                if !keep { let _ = std::fs::remove_file(&path); }
+            }
+        }
+        let mut open_rounds = self.open_rounds.lock();
+        // rounds answered on another instance are simply forgotten
+        open_rounds.retain(|quiz_id, _| !self.answered(quiz_id));
+        let ids: Vec<String> = open_rounds.iter().filter(|(_, q)| expired(q)).map(|(id, _)| id.clone()).collect();
+        ids.into_iter().filter_map(|id| open_rounds.remove(&id)).collect()
+    }
+
+    fn list(&self) -> Vec<StoredQuiz> {
+        Vec::new()
+    }
+
+    fn blocking(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Difficulty, GeneratedQuiz, QuizKind};
+
+    const SECRET: &[u8] = b"0123456789abcdef0123456789abcdef";
+
+    fn replay_dir() -> PathBuf {
+        env::temp_dir().join(format!("tanuki-quiz-replay-{}", uuid::Uuid::new_v4()))
+    }
+
+    fn quiz(id: &str) -> StoredQuiz {
+        let quiz = GeneratedQuiz { kind: QuizKind::Choice, seed: 1, difficulty: Difficulty::Normal, question: "q".to_string(), choices: Vec::new(), answer_category: "tanuki".to_string(), session_id: None, player_id: None, weights: None, time_limit_ms: None, hotspot: None, reveal: None };
+        StoredQuiz::new(id, quiz, None)
+    }
+
+    #[test]
+    fn test_token_round_trip_and_replay() {
+        let dir = replay_dir();
+        let store = TokenStore::new(SECRET, Duration::from_secs(60), dir.clone()).unwrap();
+        let token = store.insert(quiz("0a-1"));
+        assert!(!token.contains("tanuki"));
+        assert_eq!(store.get(&token).map(|q| q.id), Some("0a-1".to_string()));
+        // another instance with the same secret and replay directory can score it, and then nobody can again
+        let other = TokenStore::new(SECRET, Duration::from_secs(60), dir.clone()).unwrap();
+        assert_eq!(other.take(&token, Some(Receipt::pending(QuizKind::Choice, "choice-1"))).map(|q| q.quiz.answer_category), Some("tanuki".to_string()));
+        assert!(other.take(&token, None).is_none());
+        assert!(store.take(&token, None).is_none());
+        assert!(store.receipt(&token).is_some_and(|r| r.pending));
+        assert!(store.get(&token).is_none());
+        other.put_receipt(&token, Receipt::new(QuizKind::Choice, "choice-1", serde_json::Value::Null));
+        assert_eq!(store.receipt(&token).map(|r| r.answer), Some("choice-1".to_string()));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_token_store_hands_expired_session_rounds_to_the_sweep() {
+        let dir = replay_dir();
+        let store = TokenStore::new(SECRET, Duration::from_secs(60), dir.clone()).unwrap();
+        let round = |id: &str| {
+            let mut q = quiz(id);
+            q.quiz.session_id = Some("s".to_string());
+            q
+        };
+        let answered = store.insert(round("0b-1"));
+        store.insert(round("0b-2"));
+        store.insert(quiz("0b-3"));
+        assert!(store.take(&answered, None).is_some());
+        let removed: Vec<String> = store.remove_expired(&|_| true).into_iter().map(|q| q.id).collect();
+        assert_eq!(removed, vec!["0b-2"]);
+        assert!(store.remove_expired(&|_| true).is_empty());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_token_rejects_tampering_other_secrets_and_expiry() {
+        let dir = replay_dir();
+        let store = TokenStore::new(SECRET, Duration::from_secs(60), dir.clone()).unwrap();
+        let token = store.insert(quiz("0c-2"));
+        let mut bytes = BASE64URL.decode(&token).unwrap();
+        bytes[20] ^= 1;
+        assert!(store.get(&BASE64URL.encode(&bytes)).is_none());
+        assert!(TokenStore::new(b"another secret that is long enough!!", Duration::from_secs(60), dir.clone()).unwrap().get(&token).is_none());
+        assert!(store.get("not-a-token").is_none());
+        let mut old = quiz("0c-3");
+        old.issued_at -= chrono::Duration::seconds(61);
+        assert!(store.take(&store.insert(old), None).is_none());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,111 @@
+// Progressive reveal quizzes.
+//
+// One photo starts heavily blurred or pixelated and gets clearer with every step the player
+// asks for; answering earlier scores more. Frames are rendered on the server (see imaging) and
+// served under a fresh opaque token per step, so a client only ever holds the frame it has
+// paid for. Each step takes the quiz out of the store and puts it back under a new id, which
+// keeps the step count on the server without a store update and works with signed tokens too.
+// Every frame, the last one included, is cut from a slightly smaller random part of the photo
+// and rendered at quiz image size, so no frame is byte for byte what /media renders for the
+// same photo. A later frame's token comes from the quiz seed and the step, and every frame is
+// logged under its own id, so the admin replay can show any frame a player was given.
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+use crate::imaging::Degrade;
+
+/// Frames per quiz; the last one is the photo without blur or pixelation.
+pub const STEPS: usize = 5;
+// sigma, then width, per step before the last; each step roughly halves the damage
+const BLUR_SIGMAS: [f32; STEPS - 1] = [16.0, 9.0, 5.0, 2.5];
+const PIXEL_WIDTHS: [u32; STEPS - 1] = [8, 16, 32, 64];
+// share of the photo's width and height that the frames show
+const CROP_MIN: f32 = 0.9;
+const CROP_MAX: f32 = 0.97;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Style {
+    Blur,
+    Pixelate,
+}
+
+impl Style {
+    pub fn parse(s: &str) -> Option<Style> {
+        match s {
+            "blur" => Some(Style::Blur),
+            "pixelate" => Some(Style::Pixelate),
+            _ => None,
+        }
+    }
+}
+
+/// Where a reveal quiz stands.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Progress {
+    pub style: Style,
+    // 0-based; the frame currently shown
+    pub step: usize,
+    // species keys the player chooses from
+    pub options: Vec<String>,
+    // [x, y, w, h] of the photo every frame shows, as fractions of it
+    #[serde(default = "whole_photo")]
+    pub crop: [f32; 4],
+}
+
+fn whole_photo() -> [f32; 4] {
+    [0.0, 0.0, 1.0, 1.0]
+}
+
+/// The part of the photo a new quiz shows, drawn once per quiz.
+pub fn random_crop<R: Rng + ?Sized>(rng: &mut R) -> [f32; 4] {
+    let w: f32 = rng.gen_range(CROP_MIN..=CROP_MAX);
+    let h: f32 = rng.gen_range(CROP_MIN..=CROP_MAX);
+    [rng.gen_range(0.0..=(1.0 - w)), rng.gen_range(0.0..=(1.0 - h)), w, h]
+}
+
+/// How frame `step` of a quiz showing `crop` is rendered.
+pub fn frame(style: Style, step: usize, crop: [f32; 4]) -> Degrade {
+    let (low_res_width, blur) = match style {
+        _ if step + 1 >= STEPS => (0, 0.0),
+        Style::Blur => (0, BLUR_SIGMAS[step]),
+        Style::Pixelate => (PIXEL_WIDTHS[step], 0.0),
+    };
+    let [crop_x, crop_y, crop_w, crop_h] = crop;
+    Degrade { crop_x, crop_y, crop_w, crop_h, grayscale: false, low_res_width, blur }
+}
+
+/// Token of the photo in frame `step` of the quiz drawn from `seed`. Frame 0 keeps the token
+/// the quiz was generated with; later ones are derived here so a replay can rebuild them.
+pub fn step_token(seed: u64, step: usize) -> String {
+    let mut rng = StdRng::seed_from_u64(seed ^ (step as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15));
+    format!("{:016x}", rng.gen::<u64>())
+}
+
+/// Points for a correct answer at `step`: STEPS on the first frame, one less per step revealed.
+pub fn points(step: usize) -> u32 {
+    STEPS.saturating_sub(step) as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frames_get_clearer_and_points_drop() {
+        let crop = random_crop(&mut rand::thread_rng());
+        assert!(crop[2] < 1.0 && crop[0] + crop[2] <= 1.0 && crop[1] + crop[3] <= 1.0);
+        for style in [Style::Blur, Style::Pixelate] {
+            let frames: Vec<Degrade> = (0..STEPS).map(|step| frame(style, step, crop)).collect();
+            for pair in frames[..STEPS - 1].windows(2) {
+                assert!(pair[1].blur < pair[0].blur || pair[1].low_res_width > pair[0].low_res_width);
+            }
+            // the last frame is sharp, but still cropped and rendered like the others
+            let last = &frames[STEPS - 1];
+            assert_eq!((last.blur, last.low_res_width, last.crop_w), (0.0, 0, crop[2]));
+        }
+        assert_eq!((0..=STEPS).map(points).collect::<Vec<_>>(), vec![5, 4, 3, 2, 1, 0]);
+    }
+}
@@ -0,0 +1,285 @@
+// Per-player mistake history for adaptive quizzes.
+//
+// When a quiz is generated with ?player_id=..., every answer to it updates that player's
+// history: the species they failed to find, the photos involved and which species they
+// mistook it for. Each of those gets a Leitner-style card: a mistake drops it to level 0
+// and makes it due on the next round, a correct answer moves it up a level and pushes the
+// next review out to 2^level rounds later. Cards that reach MAX_LEVEL are dropped as learned.
+// generate_quiz turns the cards into selection weights, so due, low-level items come up
+// more often. Each player's history is a small file in data/player_history/ (override with
+// PLAYER_HISTORY_DIR), so an answer reads and writes only that player's file. Files untouched
+// for PLAYER_HISTORY_DAYS (default 90) are removed by a periodic sweep.
+
+use chrono::{DateTime, Duration, Utc};
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::env;
+use std::path::{Path, PathBuf};
+
+pub const MAX_LEVEL: u8 = 4;
+// a confused pair never outweighs this many ordinary species
+const MAX_PARTNER_WEIGHT: f64 = 5.0;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Card {
+    pub level: u8,
+    // the player's answered-round count at which this card is due again
+    pub due_at: u64,
+    pub mistakes: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PlayerHistory {
+    pub answered: u64,
+    // species the player failed to pick out, by key
+    pub species: BTreeMap<String, Card>,
+    // photos involved in mistakes, by filename in public/assets
+    pub images: BTreeMap<String, Card>,
+    // target species -> species picked instead -> times
+    pub confusions: BTreeMap<String, BTreeMap<String, u64>>,
+    pub last_seen: DateTime<Utc>,
+}
+
+/// One answer to a quiz, as far as the history is concerned.
+pub struct Answer<'a> {
+    pub target: &'a str,
+    // photo(s) of the target species that were shown
+    pub target_files: Vec<&'a str>,
+    pub picked: &'a str,
+    pub picked_file: Option<&'a str>,
+}
+
+/// Selection weights derived from a history. Missing entries weigh 1.0.
+/// Stored in the quiz log so weighted quizzes can be regenerated.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct Weights {
+    pub species: BTreeMap<String, f64>,
+    pub partners: BTreeMap<String, BTreeMap<String, f64>>,
+    pub images: BTreeMap<String, f64>,
+}
+
+impl Weights {
+    pub fn species_weight(&self, key: &str) -> f64 {
+        self.species.get(key).copied().unwrap_or(1.0)
+    }
+
+    /// Weight of showing `other` next to `target`: higher for species the player mistook for it.
+    pub fn partner_weight(&self, target: &str, other: &str) -> f64 {
+        self.partners.get(target).and_then(|p| p.get(other)).copied().unwrap_or(1.0)
+    }
+
+    pub fn image_weight(&self, file: &str) -> f64 {
+        self.images.get(file).copied().unwrap_or(1.0)
+    }
+}
+
+impl Card {
+    fn new(answered: u64) -> Card {
+        Card { level: 0, due_at: answered + 1, mistakes: 0 }
+    }
+
+    fn weight(&self, answered: u64) -> f64 {
+        let urgency = (MAX_LEVEL - self.level.min(MAX_LEVEL)) as f64;
+        // cards not yet due still count a little so they do not vanish completely
+        1.0 + urgency * if self.due_at <= answered { 1.0 } else { 0.25 }
+    }
+}
+
+impl PlayerHistory {
+    pub fn new() -> PlayerHistory {
+        PlayerHistory { answered: 0, species: BTreeMap::new(), images: BTreeMap::new(), confusions: BTreeMap::new(), last_seen: Utc::now() }
+    }
+
+    /// None when there is nothing to review, so the quiz stays uniformly random.
+    pub fn weights(&self) -> Option<Weights> {
+        if self.species.is_empty() && self.images.is_empty() { return None; }
+        let species = self.species.iter().map(|(k, c)| (k.clone(), c.weight(self.answered))).collect();
+        let images = self.images.iter().map(|(f, c)| (f.clone(), c.weight(self.answered))).collect();
+        let partners = self.confusions.iter().map(|(target, picked)| {
+            (target.clone(), picked.iter().map(|(p, n)| (p.clone(), (1.0 + *n as f64).min(MAX_PARTNER_WEIGHT))).collect())
+        }).collect();
+        Some(Weights { species, partners, images })
+    }
+
+    pub fn record(&mut self, answer: &Answer) {
+        self.answered += 1;
+        self.last_seen = Utc::now();
+        let answered = self.answered;
+        if answer.picked == answer.target {
+            promote(&mut self.species, answer.target, answered);
+            for f in &answer.target_files { promote(&mut self.images, f, answered); }
+            // once the species is learned its confusions no longer need extra weight
+            if !self.species.contains_key(answer.target) { self.confusions.remove(answer.target); }
+        } else {
+            demote(&mut self.species, answer.target, answered);
+            for f in answer.target_files.iter().copied().chain(answer.picked_file) { demote(&mut self.images, f, answered); }
+            *self.confusions.entry(answer.target.to_string()).or_default().entry(answer.picked.to_string()).or_insert(0) += 1;
+        }
+    }
+}
+
+impl Default for PlayerHistory {
+    fn default() -> Self {
+        PlayerHistory::new()
+    }
+}
+
+// only items the player has got wrong have cards; correct answers on other items change nothing
+fn promote(cards: &mut BTreeMap<String, Card>, key: &str, answered: u64) {
+    let Some(card) = cards.get_mut(key) else { return };
+    card.level += 1;
+    if card.level >= MAX_LEVEL {
+        cards.remove(key);
+        return;
+    }
+    card.due_at = answered + (1u64 << card.level);
+}
+
+fn demote(cards: &mut BTreeMap<String, Card>, key: &str, answered: u64) {
+    let card = cards.entry(key.to_string()).or_insert_with(|| Card::new(answered));
+    card.level = 0;
+    card.due_at = answered + 1;
+    card.mistakes += 1;
+}
+
+// serializes read-modify-write cycles on history files
+static HISTORY_LOCK: Lazy<Mutex<()>> = Lazy::new(|| Mutex::new(()));
+
+fn history_dir() -> PathBuf {
+    env::var("PLAYER_HISTORY_DIR").map(PathBuf::from).unwrap_or_else(|_| PathBuf::from("data").join("player_history"))
+}
+
+// player ids are checked by daily::valid_player_id (letters, digits, '-', '_'), so they are safe file names
+fn history_path(dir: &Path, player_id: &str) -> PathBuf {
+    dir.join(format!("{}.json", player_id))
+}
+
+fn retention() -> Duration {
+    Duration::days(env::var("PLAYER_HISTORY_DAYS").ok().and_then(|s| s.parse().ok()).unwrap_or(90))
+}
+
+fn load(dir: &Path, player_id: &str) -> Option<PlayerHistory> {
+    std::fs::read_to_string(history_path(dir, player_id)).ok().and_then(|s| serde_json::from_str(&s).ok())
+}
+
+fn save(dir: &Path, player_id: &str, history: &PlayerHistory) -> Result<(), String> {
+    std::fs::create_dir_all(dir).map_err(|e| format!("mkdir error: {}", e))?;
+    let s = serde_json::to_string(history).map_err(|e| e.to_string())?;
+    // write then rename, so a crash never leaves half a history behind
+    let p = history_path(dir, player_id);
+    let tmp = p.with_extension("json.tmp");
+    std::fs::write(&tmp, s).map_err(|e| format!("write error: {}", e))?;
+    std::fs::rename(&tmp, &p).map_err(|e| format!("rename error: {}", e))
+}
+
+pub fn weights_for(player_id: &str) -> Option<Weights> {
+    get(player_id).and_then(|h| h.weights())
+}
+
+pub fn get(player_id: &str) -> Option<PlayerHistory> {
+    let _guard = HISTORY_LOCK.lock();
+    load(&history_dir(), player_id).filter(|h| h.last_seen >= Utc::now() - retention())
+}
+
+pub fn record(player_id: &str, answer: &Answer) -> Result<(), String> {
+    let dir = history_dir();
+    let _guard = HISTORY_LOCK.lock();
+    let mut history = load(&dir, player_id).filter(|h| h.last_seen >= Utc::now() - retention()).unwrap_or_default();
+    history.record(answer);
+    save(&dir, player_id, &history)
+}
+
+/// Remove histories without activity for PLAYER_HISTORY_DAYS. Goes by file modification time,
+/// which every recorded answer updates. Returns how many were removed.
+pub fn sweep_stale() -> usize {
+    sweep_dir(&history_dir(), retention())
+}
+
+fn sweep_dir(dir: &Path, retention: Duration) -> usize {
+    let Ok(entries) = std::fs::read_dir(dir) else { return 0 };
+    let Ok(max_age) = retention.to_std() else { return 0 };
+    let _guard = HISTORY_LOCK.lock();
+    let mut removed = 0;
+    for entry in entries.flatten() {
+        let stale = entry.metadata().ok().and_then(|m| m.modified().ok()).and_then(|t| t.elapsed().ok()).is_some_and(|age| age > max_age);
+        if stale && std::fs::remove_file(entry.path()).is_ok() { removed += 1; }
+    }
+    removed
+}
+
+/// Split a data/player_history.json from older versions (PLAYER_HISTORY_PATH) into per-player files
+/// and rename it to .migrated. Returns the number of players moved.
+pub fn migrate_single_file() -> Result<usize, String> {
+    let old = env::var("PLAYER_HISTORY_PATH").map(PathBuf::from).unwrap_or_else(|_| PathBuf::from("data").join("player_history.json"));
+    let Ok(s) = std::fs::read_to_string(&old) else { return Ok(0) };
+    let all: BTreeMap<String, PlayerHistory> = serde_json::from_str(&s).map_err(|e| format!("cannot parse {}: {}", old.display(), e))?;
+    let dir = history_dir();
+    let _guard = HISTORY_LOCK.lock();
+    let mut moved = 0;
+    for (player_id, history) in &all {
+        if crate::daily::valid_player_id(player_id) && load(&dir, player_id).is_none() {
+            save(&dir, player_id, history)?;
+            moved += 1;
+        }
+    }
+    std::fs::rename(&old, old.with_extension("json.migrated")).map_err(|e| format!("rename error: {}", e))?;
+    Ok(moved)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn answer<'a>(target: &'a str, picked: &'a str) -> Answer<'a> {
+        Answer { target, target_files: vec!["tanuki1.jpg"], picked, picked_file: Some("hakubishin2.jpg") }
+    }
+
+    #[test]
+    fn test_mistake_creates_due_cards_and_confusion() {
+        let mut h = PlayerHistory::new();
+        assert!(h.weights().is_none());
+        h.record(&answer("tanuki", "hakubishin"));
+        assert_eq!(h.species["tanuki"], Card { level: 0, due_at: 2, mistakes: 1 });
+        assert!(h.images.contains_key("tanuki1.jpg") && h.images.contains_key("hakubishin2.jpg"));
+        let w = h.weights().unwrap();
+        // not due until the next round
+        assert_eq!(w.species_weight("tanuki"), 2.0);
+        assert_eq!(w.species_weight("anaguma"), 1.0);
+        assert_eq!(w.partner_weight("tanuki", "hakubishin"), 2.0);
+        assert_eq!(w.partner_weight("tanuki", "anaguma"), 1.0);
+        h.answered += 1;
+        assert_eq!(h.weights().unwrap().species_weight("tanuki"), 1.0 + MAX_LEVEL as f64);
+    }
+
+    #[test]
+    fn test_histories_are_kept_per_player() {
+        let dir = std::env::temp_dir().join(format!("tanuki-history-{}", uuid::Uuid::new_v4()));
+        let mut h = PlayerHistory::new();
+        h.record(&answer("tanuki", "hakubishin"));
+        save(&dir, "p1", &h).unwrap();
+        assert_eq!(load(&dir, "p1").map(|h| h.answered), Some(1));
+        assert!(load(&dir, "p2").is_none());
+        // nothing is old enough yet; with no retention at all everything goes
+        assert_eq!(sweep_dir(&dir, Duration::days(1)), 0);
+        assert_eq!(sweep_dir(&dir, Duration::zero()), 1);
+        assert!(load(&dir, "p1").is_none());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_correct_answers_space_out_and_retire_cards() {
+        let mut h = PlayerHistory::new();
+        h.record(&answer("tanuki", "hakubishin"));
+        h.record(&answer("tanuki", "tanuki"));
+        assert_eq!(h.species["tanuki"].level, 1);
+        assert_eq!(h.species["tanuki"].due_at, h.answered + 2);
+        for _ in 1..MAX_LEVEL { h.record(&answer("tanuki", "tanuki")); }
+        assert!(!h.species.contains_key("tanuki"));
+        assert!(!h.confusions.contains_key("tanuki"));
+        // a correct answer on a species without a card does not create one
+        h.record(&answer("anaguma", "anaguma"));
+        assert!(!h.species.contains_key("anaguma"));
+    }
+}
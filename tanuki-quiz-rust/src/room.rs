@@ -0,0 +1,532 @@
+// Live multiplayer rooms.
+//
+// A host creates a room with POST /api/rooms and gets a short join code and a secret host key.
+// Players open a WebSocket at /api/rooms/<code>/ws with a nickname. The host connects to the
+// same socket with ?role=host and sends the host key as its first message, so the key stays out
+// of URLs and access logs. Each "next" from the host sends everyone the same question. The
+// round closes when every connected player has answered, when the time limit runs out, or when
+// the host sends "next" again. Closing a round broadcasts its results and the standings, and
+// the last round is followed by the final ranking. Scoring follows timed sessions: a point per
+// correct answer, plus a speed bonus when the room has a time limit.
+// Rooms are kept in memory only and expire after ROOM_TTL_SECS (default 2 hours) without activity.
+
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use rand::Rng;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::env;
+use std::path::Path;
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast;
+use uuid::Uuid;
+
+use crate::answer_log::{self, AnswerRecord};
+use crate::session;
+use crate::species;
+use crate::{Difficulty, GeneratedChoice};
+
+pub const DEFAULT_ROUNDS: usize = 10;
+pub const MAX_ROUNDS: usize = 30;
+pub const MAX_PLAYERS: usize = 100;
+// open rooms at once; creating a room needs no login
+pub const MAX_ROOMS: usize = 500;
+const CODE_LEN: usize = 6;
+// no 0/O or 1/I, so codes can be read off a projector
+const CODE_ALPHABET: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+// events a slow connection may fall behind before it skips ahead
+const EVENT_BUFFER: usize = 64;
+
+/// Who is on the other end of a room socket.
+#[derive(Clone, Debug)]
+pub enum Member {
+    Host,
+    Player { player_id: String, nickname: String },
+}
+
+struct Player {
+    nickname: String,
+    score: usize,
+    speed_bonus: u32,
+    // open sockets; a player can reconnect and keep their score
+    connections: usize,
+}
+
+struct Round {
+    number: usize,
+    question: String,
+    answer_category: String,
+    choices: Vec<GeneratedChoice>,
+    started: Instant,
+    // player_id -> (choice token, server-measured response time)
+    answers: HashMap<String, (String, Duration)>,
+}
+
+enum Phase {
+    Lobby,
+    Question(Round),
+    // the round just closed and its result; the images stay available, and sockets that join
+    // during the reveal are sent the result
+    Reveal(Round, Event),
+    Finished,
+}
+
+struct Room {
+    code: String,
+    host_key: String,
+    lang: &'static str,
+    difficulty: Difficulty,
+    rounds: usize,
+    time_limit: Option<Duration>,
+    players: HashMap<String, Player>,
+    phase: Phase,
+    tx: broadcast::Sender<Event>,
+    last_active: Instant,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct RoomChoice {
+    pub token: String,
+    pub image_url: String,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct PlayerResult {
+    pub nickname: String,
+    pub correct: bool,
+    // None when the player did not answer
+    pub response_ms: Option<u64>,
+    pub speed_bonus: u32,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct Standing {
+    pub rank: usize,
+    pub nickname: String,
+    pub score: usize,
+    pub speed_bonus: u32,
+}
+
+/// Messages sent to room sockets, as JSON objects with a "type" field.
+#[derive(Serialize, Clone, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Event {
+    Welcome { code: String, host: bool, nickname: Option<String>, rounds: usize },
+    Lobby { players: Vec<String> },
+    Question { round: usize, rounds: usize, question: String, choices: Vec<RoomChoice>, time_limit_ms: Option<u64> },
+    // how many players have answered the current round, so the host knows when to move on
+    Progress { round: usize, answered: usize, players: usize },
+    RoundResult { round: usize, rounds: usize, answer: String, answer_label: String, explanation: Option<String>, results: Vec<PlayerResult>, standings: Vec<Standing> },
+    Final { standings: Vec<Standing> },
+    Error { message: String },
+}
+
+#[derive(Serialize)]
+pub struct RoomInfo {
+    pub code: String,
+    pub phase: &'static str,
+    pub round: usize,
+    pub rounds: usize,
+    pub players: usize,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum JoinError {
+    NotFound,
+    BadHostKey,
+    NicknameTaken,
+    Full,
+    Finished,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum AnswerError {
+    NotFound,
+    NoQuestion,
+    UnknownChoice,
+    AlreadyAnswered,
+}
+
+/// What the host's "next" turned into.
+pub enum Next {
+    // the caller generates the question and hands it to start_round
+    NeedQuestion { round: usize, difficulty: Difficulty, lang: &'static str },
+    ClosedRound,
+    Finished,
+}
+
+pub struct Joined {
+    pub rx: broadcast::Receiver<Event>,
+    // sent to the new socket only: the welcome and whatever is on screen right now
+    pub initial: Vec<Event>,
+}
+
+static ROOMS: Lazy<Mutex<HashMap<String, Room>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Idle lifetime of a room, ROOM_TTL_SECS (default 2 hours).
+pub fn room_ttl() -> Duration {
+    Duration::from_secs(env::var("ROOM_TTL_SECS").ok().and_then(|s| s.parse().ok()).unwrap_or(2 * 60 * 60))
+}
+
+pub fn new_code<R: Rng + ?Sized>(rng: &mut R) -> String {
+    (0..CODE_LEN).map(|_| CODE_ALPHABET[rng.gen_range(0..CODE_ALPHABET.len())] as char).collect()
+}
+
+/// Join codes are case-insensitive.
+pub fn normalize_code(code: &str) -> String {
+    code.trim().to_ascii_uppercase()
+}
+
+/// Create a room; returns its join code and the host key, or None when MAX_ROOMS are open.
+pub fn create(rounds: usize, difficulty: Difficulty, time_limit: Option<Duration>, lang: &'static str) -> Option<(String, String)> {
+    let mut rooms = ROOMS.lock();
+    if rooms.len() >= MAX_ROOMS { return None; }
+    let mut rng = rand::thread_rng();
+    let code = loop {
+        let c = new_code(&mut rng);
+        if !rooms.contains_key(&c) { break c; }
+    };
+    let host_key = Uuid::new_v4().simple().to_string();
+    let (tx, _) = broadcast::channel(EVENT_BUFFER);
+    let room = Room { code: code.clone(), host_key: host_key.clone(), lang, difficulty, rounds, time_limit, players: HashMap::new(), phase: Phase::Lobby, tx, last_active: Instant::now() };
+    rooms.insert(code.clone(), room);
+    Some((code, host_key))
+}
+
+pub fn info(code: &str) -> Option<RoomInfo> {
+    let rooms = ROOMS.lock();
+    let room = rooms.get(code)?;
+    let (phase, round) = match &room.phase {
+        Phase::Lobby => ("lobby", 0),
+        Phase::Question(r) => ("question", r.number),
+        Phase::Reveal(r, _) => ("reveal", r.number),
+        Phase::Finished => ("finished", room.rounds),
+    };
+    Some(RoomInfo { code: code.to_string(), phase, round, rounds: room.rounds, players: room.players.values().filter(|p| p.connections > 0).count() })
+}
+
+/// The checks `join` makes, without joining, so a bad request fails before the WebSocket upgrade.
+pub fn check_join(code: &str, member: &Member, host_key: Option<&str>) -> Result<(), JoinError> {
+    let rooms = ROOMS.lock();
+    let room = rooms.get(code).ok_or(JoinError::NotFound)?;
+    room.check_join(member, host_key)
+}
+
+pub fn join(code: &str, member: &Member, host_key: Option<&str>) -> Result<Joined, JoinError> {
+    let mut rooms = ROOMS.lock();
+    let room = rooms.get_mut(code).ok_or(JoinError::NotFound)?;
+    room.check_join(member, host_key)?;
+    // subscribe before anything is broadcast, so the new socket misses nothing
+    let rx = room.tx.subscribe();
+    let nickname = match member {
+        Member::Host => None,
+        Member::Player { player_id, nickname } => {
+            let p = room.players.entry(player_id.clone()).or_insert_with(|| Player { nickname: nickname.clone(), score: 0, speed_bonus: 0, connections: 0 });
+            p.connections += 1;
+            Some(p.nickname.clone())
+        }
+    };
+    room.last_active = Instant::now();
+    let mut initial = vec![Event::Welcome { code: code.to_string(), host: matches!(member, Member::Host), nickname, rounds: room.rounds }];
+    match &room.phase {
+        Phase::Question(r) => initial.push(room.question_event(code, r)),
+        Phase::Reveal(_, result) => initial.push(result.clone()),
+        Phase::Finished => initial.push(Event::Final { standings: room.standings() }),
+        Phase::Lobby => {}
+    }
+    if matches!(member, Member::Player { .. }) { room.broadcast(room.lobby_event()); } else { initial.push(room.lobby_event()); }
+    Ok(Joined { rx, initial })
+}
+
+/// `log` is the answer log that a round closed by this call is written to; see Room::close_round.
+pub fn leave(code: &str, member: &Member, log: &Path) {
+    let Member::Player { player_id, .. } = member else { return };
+    let records = {
+        let mut rooms = ROOMS.lock();
+        let Some(room) = rooms.get_mut(code) else { return };
+        if let Some(p) = room.players.get_mut(player_id) { p.connections = p.connections.saturating_sub(1); }
+        room.broadcast(room.lobby_event());
+        // the round may now be waiting only on players who left
+        room.close_if_all_answered()
+    };
+    answer_log::log_to(log, records);
+}
+
+/// Host's "next": close the open round, or ask for the next question, or finish the game.
+pub fn next(code: &str, log: &Path) -> Result<Next, JoinError> {
+    let mut rooms = ROOMS.lock();
+    let room = rooms.get_mut(code).ok_or(JoinError::NotFound)?;
+    room.last_active = Instant::now();
+    let played = match &room.phase {
+        Phase::Question(_) => {
+            let records = room.close_round();
+            drop(rooms);
+            answer_log::log_to(log, records);
+            return Ok(Next::ClosedRound);
+        }
+        Phase::Finished => return Err(JoinError::Finished),
+        Phase::Lobby => 0,
+        Phase::Reveal(r, _) => r.number,
+    };
+    if played >= room.rounds {
+        room.phase = Phase::Finished;
+        room.broadcast(Event::Final { standings: room.standings() });
+        return Ok(Next::Finished);
+    }
+    Ok(Next::NeedQuestion { round: played + 1, difficulty: room.difficulty, lang: room.lang })
+}
+
+/// Open round `number` with a generated question and broadcast it. Returns the time limit to enforce,
+/// or None when the round is untimed or the room has moved on in the meantime.
+pub fn start_round(code: &str, number: usize, question: String, answer_category: String, choices: Vec<GeneratedChoice>) -> Option<Duration> {
+    let mut rooms = ROOMS.lock();
+    let room = rooms.get_mut(code)?;
+    let expected = match &room.phase {
+        Phase::Lobby => 1,
+        Phase::Reveal(r, _) => r.number + 1,
+        Phase::Question(_) | Phase::Finished => return None,
+    };
+    if number != expected { return None; }
+    let round = Round { number, question, answer_category, choices, started: Instant::now(), answers: HashMap::new() };
+    room.broadcast(room.question_event(code, &round));
+    room.phase = Phase::Question(round);
+    room.last_active = Instant::now();
+    room.time_limit
+}
+
+/// Close round `number` if it is still open; used when its time limit runs out.
+pub fn close_round(code: &str, number: usize, log: &Path) {
+    let records = {
+        let mut rooms = ROOMS.lock();
+        let Some(room) = rooms.get_mut(code) else { return };
+        if !matches!(&room.phase, Phase::Question(r) if r.number == number) { return; }
+        room.close_round()
+    };
+    answer_log::log_to(log, records);
+}
+
+pub fn answer(code: &str, player_id: &str, token: &str, log: &Path) -> Result<(), AnswerError> {
+    let mut rooms = ROOMS.lock();
+    let room = rooms.get_mut(code).ok_or(AnswerError::NotFound)?;
+    let players = room.players.values().filter(|p| p.connections > 0).count();
+    let Phase::Question(round) = &mut room.phase else { return Err(AnswerError::NoQuestion) };
+    if !round.choices.iter().any(|c| c.token == token) { return Err(AnswerError::UnknownChoice); }
+    if round.answers.contains_key(player_id) { return Err(AnswerError::AlreadyAnswered); }
+    round.answers.insert(player_id.to_string(), (token.to_string(), round.started.elapsed()));
+    let progress = Event::Progress { round: round.number, answered: round.answers.len(), players };
+    room.last_active = Instant::now();
+    room.broadcast(progress);
+    let records = room.close_if_all_answered();
+    drop(rooms);
+    answer_log::log_to(log, records);
+    Ok(())
+}
+
+/// Choice image for the open or just-revealed round.
+pub fn choice(code: &str, token: &str) -> Option<GeneratedChoice> {
+    let rooms = ROOMS.lock();
+    match &rooms.get(code)?.phase {
+        Phase::Question(r) | Phase::Reveal(r, _) => r.choices.iter().find(|c| c.token == token).cloned(),
+        Phase::Lobby | Phase::Finished => None,
+    }
+}
+
+pub fn sweep_expired(now: Instant) {
+    let ttl = room_ttl();
+    ROOMS.lock().retain(|_, r| now.duration_since(r.last_active) <= ttl);
+}
+
+impl Room {
+    fn check_join(&self, member: &Member, host_key: Option<&str>) -> Result<(), JoinError> {
+        match member {
+            Member::Host => if host_key != Some(self.host_key.as_str()) { return Err(JoinError::BadHostKey) },
+            Member::Player { player_id, nickname } => {
+                if self.players.contains_key(player_id) { return Ok(()); }
+                if matches!(self.phase, Phase::Finished) { return Err(JoinError::Finished); }
+                if self.players.values().any(|p| p.nickname == *nickname) { return Err(JoinError::NicknameTaken); }
+                if self.players.len() >= MAX_PLAYERS { return Err(JoinError::Full); }
+            }
+        }
+        Ok(())
+    }
+
+    // no receivers is fine: nobody is connected right now
+    fn broadcast(&self, event: Event) {
+        let _ = self.tx.send(event);
+    }
+
+    fn lobby_event(&self) -> Event {
+        let mut players: Vec<String> = self.players.values().filter(|p| p.connections > 0).map(|p| p.nickname.clone()).collect();
+        players.sort();
+        Event::Lobby { players }
+    }
+
+    fn question_event(&self, code: &str, round: &Round) -> Event {
+        Event::Question {
+            round: round.number,
+            rounds: self.rounds,
+            question: round.question.clone(),
+            choices: round.choices.iter().map(|c| RoomChoice { token: c.token.clone(), image_url: format!("/api/rooms/{}/image/{}", code, c.token) }).collect(),
+            time_limit_ms: self.time_limit.map(|l| l.as_millis() as u64),
+        }
+    }
+
+    fn close_if_all_answered(&mut self) -> Vec<AnswerRecord> {
+        let Phase::Question(round) = &self.phase else { return Vec::new() };
+        let waiting = self.players.iter().any(|(id, p)| p.connections > 0 && !round.answers.contains_key(id));
+        if waiting || round.answers.is_empty() { return Vec::new(); }
+        self.close_round()
+    }
+
+    // Returns the round's answer records. Callers write them to the answer log after releasing
+    // ROOMS, so a slow disk never holds up the other rooms.
+    fn close_round(&mut self) -> Vec<AnswerRecord> {
+        let Phase::Question(round) = std::mem::replace(&mut self.phase, Phase::Finished) else { return Vec::new() };
+        let records = self.answer_records(&round);
+        let results = score_round(&mut self.players, &round, self.time_limit);
+        let sp = species::get(&round.answer_category);
+        let event = Event::RoundResult {
+            round: round.number,
+            rounds: self.rounds,
+            answer: round.answer_category.clone(),
+            answer_label: sp.as_ref().map(|sp| sp.name(self.lang).to_string()).unwrap_or_else(|| round.answer_category.clone()),
+            explanation: sp.as_ref().and_then(|sp| sp.explanation(self.lang)).map(|s| s.to_string()),
+            results,
+            standings: self.standings(),
+        };
+        self.broadcast(event.clone());
+        self.phase = Phase::Reveal(round, event);
+        records
+    }
+
+    // one record per player in the round's results, including those who did not answer
+    fn answer_records(&self, round: &Round) -> Vec<AnswerRecord> {
+        self.players.iter().filter(|(id, p)| p.connections > 0 || round.answers.contains_key(*id)).map(|(id, p)| {
+            let answer = round.answers.get(id);
+            let picked = answer.and_then(|(token, _)| round.choices.iter().find(|c| &c.token == token));
+            AnswerRecord {
+                session: Some(self.code.clone()),
+                player_id: Some(id.clone()),
+                nickname: Some(p.nickname.clone()),
+                response_ms: answer.map(|(_, r)| r.as_millis() as u64),
+                ..AnswerRecord::choice("room", format!("{}#{}", self.code, round.number), &round.answer_category, &round.choices, picked)
+            }
+        }).collect()
+    }
+
+    fn standings(&self) -> Vec<Standing> {
+        standings(self.players.values())
+    }
+}
+
+// update scores for a closed round and describe it per player
+fn score_round(players: &mut HashMap<String, Player>, round: &Round, time_limit: Option<Duration>) -> Vec<PlayerResult> {
+    let mut results: Vec<PlayerResult> = players.iter_mut().filter_map(|(id, p)| {
+        let answer = round.answers.get(id);
+        // players who were not there for the round are left out of its results
+        if answer.is_none() && p.connections == 0 { return None; }
+        let correct = answer.is_some_and(|(token, _)| round.choices.iter().any(|c| &c.token == token && c.category == round.answer_category));
+        let speed_bonus = match (answer, time_limit) {
+            (Some((_, response)), Some(limit)) if correct => session::speed_bonus(*response, limit),
+            _ => 0,
+        };
+        if correct { p.score += 1; }
+        p.speed_bonus += speed_bonus;
+        Some(PlayerResult { nickname: p.nickname.clone(), correct, response_ms: answer.map(|(_, r)| r.as_millis() as u64), speed_bonus })
+    }).collect();
+    results.sort_by(|a, b| b.correct.cmp(&a.correct).then(a.response_ms.unwrap_or(u64::MAX).cmp(&b.response_ms.unwrap_or(u64::MAX))));
+    results
+}
+
+// most correct answers first, then the larger speed bonus; equal players share a rank
+fn standings<'a>(players: impl Iterator<Item = &'a Player>) -> Vec<Standing> {
+    let mut list: Vec<&Player> = players.collect();
+    list.sort_by(|a, b| b.score.cmp(&a.score).then(b.speed_bonus.cmp(&a.speed_bonus)).then(a.nickname.cmp(&b.nickname)));
+    let mut out: Vec<Standing> = Vec::with_capacity(list.len());
+    for (i, p) in list.into_iter().enumerate() {
+        let rank = match out.last() {
+            Some(prev) if prev.score == p.score && prev.speed_bonus == p.speed_bonus => prev.rank,
+            _ => i + 1,
+        };
+        out.push(Standing { rank, nickname: p.nickname.clone(), score: p.score, speed_bonus: p.speed_bonus });
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gen_choice(token: &str, category: &str) -> GeneratedChoice {
+        GeneratedChoice { token: token.to_string(), category: category.to_string(), file: None, procedural_key: Some(category.to_string()), degrade: None }
+    }
+
+    fn player(nickname: &str, connections: usize) -> Player {
+        Player { nickname: nickname.to_string(), score: 0, speed_bonus: 0, connections }
+    }
+
+    #[test]
+    fn test_codes_use_readable_alphabet() {
+        let mut rng = rand::thread_rng();
+        for _ in 0..100 {
+            let code = new_code(&mut rng);
+            assert_eq!(code.len(), CODE_LEN);
+            assert!(code.bytes().all(|b| CODE_ALPHABET.contains(&b)));
+        }
+        assert_eq!(normalize_code(" ab3k9z "), "AB3K9Z");
+    }
+
+    #[test]
+    fn test_score_round_and_standings() {
+        let mut players: HashMap<String, Player> = [("a", player("alice", 1)), ("b", player("bob", 1)), ("c", player("carol", 1)), ("gone", player("dave", 0))]
+            .into_iter().map(|(id, p)| (id.to_string(), p)).collect();
+        let mut round = Round { number: 1, question: "q".to_string(), answer_category: "tanuki".to_string(), choices: vec![gen_choice("t", "tanuki"), gen_choice("h", "hakubishin")], started: Instant::now(), answers: HashMap::new() };
+        round.answers.insert("a".to_string(), ("t".to_string(), Duration::from_secs(1)));
+        round.answers.insert("b".to_string(), ("t".to_string(), Duration::from_secs(5)));
+        round.answers.insert("c".to_string(), ("h".to_string(), Duration::from_secs(2)));
+        let results = score_round(&mut players, &round, Some(Duration::from_secs(10)));
+        // dave left before the round and is not listed; nobody answered for him
+        let names: Vec<&str> = results.iter().map(|r| r.nickname.as_str()).collect();
+        assert_eq!(names, vec!["alice", "bob", "carol"]);
+        assert_eq!((results[0].speed_bonus, results[1].speed_bonus, results[2].speed_bonus), (9, 5, 0));
+        let table = standings(players.values());
+        assert_eq!(table[0], Standing { rank: 1, nickname: "alice".to_string(), score: 1, speed_bonus: 9 });
+        assert_eq!((table[2].nickname.as_str(), table[2].rank), ("carol", 3));
+        assert_eq!(table[3].rank, 3);
+    }
+
+    #[test]
+    fn test_room_flow() {
+        // keep the closed round out of data/answer_log.jsonl
+        let log = std::env::temp_dir().join(format!("tanuki-room-answers-{}.jsonl", Uuid::new_v4()));
+        let (code, host_key) = create(1, Difficulty::Normal, None, "en").unwrap();
+        assert_eq!(check_join(&code, &Member::Host, Some("wrong")), Err(JoinError::BadHostKey));
+        let alice = Member::Player { player_id: "p1".to_string(), nickname: "alice".to_string() };
+        let mut host = join(&code, &Member::Host, Some(&host_key)).ok().unwrap();
+        let _alice = join(&code, &alice, None).ok().unwrap();
+        assert_eq!(check_join(&code, &Member::Player { player_id: "p2".to_string(), nickname: "alice".to_string() }, None), Err(JoinError::NicknameTaken));
+        assert!(matches!(next(&code, &log), Ok(Next::NeedQuestion { round: 1, .. })));
+        assert_eq!(start_round(&code, 1, "q".to_string(), "tanuki".to_string(), vec![gen_choice("t", "tanuki"), gen_choice("h", "hakubishin")]), None);
+        assert_eq!(answer(&code, "p1", "x", &log), Err(AnswerError::UnknownChoice));
+        // alice is the only player, so her answer closes the round
+        assert_eq!(answer(&code, "p1", "t", &log), Ok(()));
+        assert_eq!(answer(&code, "p1", "t", &log), Err(AnswerError::NoQuestion));
+        let logged: AnswerRecord = serde_json::from_str(std::fs::read_to_string(&log).unwrap().trim()).unwrap();
+        assert_eq!((logged.question_id, logged.nickname, logged.correct), (format!("{}#1", code), Some("alice".to_string()), true));
+        assert!(choice(&code, "t").is_some());
+        // someone joining during the reveal sees the round's result
+        let bob = join(&code, &Member::Player { player_id: "p2".to_string(), nickname: "bob".to_string() }, None).ok().unwrap();
+        assert!(matches!(bob.initial.get(1), Some(Event::RoundResult { round: 1, .. })));
+        assert!(matches!(next(&code, &log), Ok(Next::Finished)));
+        let mut saw_final = false;
+        while let Ok(event) = host.rx.try_recv() {
+            if let Event::Final { standings } = event {
+                assert_eq!(standings[0].score, 1);
+                saw_final = true;
+            }
+        }
+        assert!(saw_final);
+        leave(&code, &alice, &log);
+        let _ = std::fs::remove_file(&log);
+    }
+}
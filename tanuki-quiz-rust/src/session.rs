@@ -0,0 +1,337 @@
+// Multi-round quiz sessions.
+//
+// A session is started with POST /api/session and ties a fixed number of generated
+// quizzes together. Each /api/generate_quiz?session_id=... call issues the next round,
+// and every scored /api/submit_generated answer for one of those rounds is recorded here.
+// A session created with a time limit makes every round timed: the server measures each
+// response from when the round was issued, late answers score nothing, and correct answers
+// earn a speed bonus of up to MAX_SPEED_BONUS points for the time left on the clock.
+// Rounds are played one at a time: asking for the next round gives up any earlier round that
+// is still unanswered, and it counts as wrong, so a skipped or lost round never stalls a session.
+// Sessions expire like QUIZ_STORE entries but with their own (longer) idle lifetime.
+
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::env;
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+pub const DEFAULT_ROUNDS: usize = 10;
+pub const MAX_ROUNDS: usize = 50;
+pub const MAX_TIME_LIMIT_SECS: u64 = 120;
+pub const MAX_SPEED_BONUS: u32 = 10;
+
+pub struct Session {
+    pub rounds: usize,
+    // per-round limit for timed sessions
+    pub time_limit: Option<Duration>,
+    pub issued: Vec<IssuedRound>,
+    pub answers: Vec<RoundResult>,
+    pub started_at: Instant,
+    pub finished_at: Option<Instant>,
+    pub last_active: Instant,
+    // set once the result has been posted to the leaderboard
+    pub submitted: bool,
+}
+
+pub struct IssuedRound {
+    pub quiz_id: String,
+    pub species: String,
+}
+
+pub struct RoundResult {
+    pub quiz_id: String,
+    pub species: String,
+    pub correct: bool,
+    // server-measured, None when the round ran out or was given up before an answer arrived
+    pub response_ms: Option<u64>,
+    pub speed_bonus: u32,
+}
+
+/// How a round ended, as measured by the server.
+#[derive(Clone, Copy)]
+pub enum Outcome {
+    Answered { correct: bool, response: Duration },
+    TimedOut,
+}
+
+#[derive(Serialize)]
+pub struct SpeciesAccuracy {
+    pub answered: usize,
+    pub correct: usize,
+    pub accuracy: f64,
+}
+
+#[derive(Serialize)]
+pub struct SessionSummary {
+    pub session_id: String,
+    pub rounds: usize,
+    pub answered: usize,
+    pub score: usize,
+    pub finished: bool,
+    pub time_taken_ms: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub time_limit_ms: Option<u64>,
+    pub timed_out: usize,
+    pub speed_bonus: u32,
+    pub per_species: BTreeMap<String, SpeciesAccuracy>,
+}
+
+// short progress info attached to QuizResult for session rounds
+#[derive(Serialize, Deserialize)]
+pub struct SessionProgress {
+    pub session_id: String,
+    pub answered: usize,
+    pub rounds: usize,
+    pub score: usize,
+    pub speed_bonus: u32,
+    pub finished: bool,
+}
+
+pub enum RoundError {
+    NotFound,
+    Complete,
+}
+
+pub enum ClaimError {
+    NotFound,
+    NotFinished,
+    AlreadySubmitted,
+}
+
+static SESSION_STORE: Lazy<Mutex<HashMap<String, Session>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Bonus for a correct answer given after `response` out of `limit`, in whole points.
+pub fn speed_bonus(response: Duration, limit: Duration) -> u32 {
+    let limit_ms = limit.as_millis();
+    if limit_ms == 0 { return 0; }
+    let left_ms = limit.saturating_sub(response).as_millis();
+    // rounded up, so any correct answer inside the limit earns at least a point
+    (left_ms * MAX_SPEED_BONUS as u128).div_ceil(limit_ms) as u32
+}
+
+/// Idle lifetime of a session, SESSION_TTL_SECS (default 30 minutes).
+pub fn session_ttl() -> Duration {
+    let secs = env::var("SESSION_TTL_SECS").ok().and_then(|s| s.parse().ok()).unwrap_or(30 * 60);
+    Duration::from_secs(secs)
+}
+
+impl Session {
+    pub fn new(rounds: usize, time_limit: Option<Duration>) -> Session {
+        let now = Instant::now();
+        Session { rounds, time_limit, issued: Vec::new(), answers: Vec::new(), started_at: now, finished_at: None, last_active: now, submitted: false }
+    }
+
+    pub fn score(&self) -> usize {
+        self.answers.iter().filter(|r| r.correct).count()
+    }
+
+    pub fn speed_bonus(&self) -> u32 {
+        self.answers.iter().map(|r| r.speed_bonus).sum()
+    }
+
+    pub fn finished(&self) -> bool {
+        self.answers.len() >= self.rounds
+    }
+
+    pub fn summary(&self, session_id: &str) -> SessionSummary {
+        let mut per_species: BTreeMap<String, SpeciesAccuracy> = BTreeMap::new();
+        for r in &self.answers {
+            let e = per_species.entry(r.species.clone()).or_insert(SpeciesAccuracy { answered: 0, correct: 0, accuracy: 0.0 });
+            e.answered += 1;
+            if r.correct { e.correct += 1; }
+        }
+        for e in per_species.values_mut() {
+            e.accuracy = e.correct as f64 / e.answered as f64;
+        }
+        let end = self.finished_at.unwrap_or_else(Instant::now);
+        SessionSummary {
+            session_id: session_id.to_string(),
+            rounds: self.rounds,
+            answered: self.answers.len(),
+            score: self.score(),
+            finished: self.finished(),
+            time_taken_ms: end.duration_since(self.started_at).as_millis() as u64,
+            time_limit_ms: self.time_limit.map(|l| l.as_millis() as u64),
+            timed_out: self.answers.iter().filter(|r| r.response_ms.is_none()).count(),
+            speed_bonus: self.speed_bonus(),
+            per_species,
+        }
+    }
+
+    pub fn progress(&self, session_id: &str) -> SessionProgress {
+        SessionProgress { session_id: session_id.to_string(), answered: self.answers.len(), rounds: self.rounds, score: self.score(), speed_bonus: self.speed_bonus(), finished: self.finished() }
+    }
+
+    // record the answer for a round; repeated answers for the same quiz are ignored
+    fn record(&mut self, quiz_id: &str, species: &str, outcome: Outcome) {
+        if !self.issued.iter().any(|r| r.quiz_id == quiz_id) || self.answers.iter().any(|r| r.quiz_id == quiz_id) { return; }
+        let result = match outcome {
+            Outcome::Answered { correct, response } => {
+                let speed_bonus = match self.time_limit {
+                    Some(limit) if correct => speed_bonus(response, limit),
+                    _ => 0,
+                };
+                RoundResult { quiz_id: quiz_id.to_string(), species: species.to_string(), correct, response_ms: Some(response.as_millis() as u64), speed_bonus }
+            }
+            Outcome::TimedOut => RoundResult { quiz_id: quiz_id.to_string(), species: species.to_string(), correct: false, response_ms: None, speed_bonus: 0 },
+        };
+        self.answers.push(result);
+        self.last_active = Instant::now();
+        if self.finished() && self.finished_at.is_none() { self.finished_at = Some(self.last_active); }
+    }
+
+    // issued rounds that have no result yet count as wrong
+    fn give_up_pending(&mut self) {
+        let pending: Vec<(String, String)> = self.issued.iter()
+            .filter(|i| !self.answers.iter().any(|r| r.quiz_id == i.quiz_id))
+            .map(|i| (i.quiz_id.clone(), i.species.clone()))
+            .collect();
+        for (quiz_id, species) in pending { self.record(&quiz_id, &species, Outcome::TimedOut); }
+    }
+}
+
+pub fn create_session(rounds: usize, time_limit: Option<Duration>) -> String {
+    let id = Uuid::new_v4().to_string();
+    SESSION_STORE.lock().insert(id.clone(), Session::new(rounds, time_limit));
+    id
+}
+
+/// Reserve the next round of a session for `quiz_id`, whose answer is `species`. Earlier rounds
+/// still unanswered are given up first. Returns the 1-based round number and the session's time limit.
+pub fn start_round(session_id: &str, quiz_id: &str, species: &str) -> Result<(usize, Option<Duration>), RoundError> {
+    let mut store = SESSION_STORE.lock();
+    let session = store.get_mut(session_id).ok_or(RoundError::NotFound)?;
+    session.give_up_pending();
+    if session.issued.len() >= session.rounds { return Err(RoundError::Complete); }
+    session.issued.push(IssuedRound { quiz_id: quiz_id.to_string(), species: species.to_string() });
+    session.last_active = Instant::now();
+    Ok((session.issued.len(), session.time_limit))
+}
+
+pub fn record_answer(session_id: &str, quiz_id: &str, species: &str, outcome: Outcome) -> Option<SessionProgress> {
+    let mut store = SESSION_STORE.lock();
+    let session = store.get_mut(session_id)?;
+    session.record(quiz_id, species, outcome);
+    Some(session.progress(session_id))
+}
+
+pub fn summary(session_id: &str) -> Option<SessionSummary> {
+    SESSION_STORE.lock().get(session_id).map(|s| s.summary(session_id))
+}
+
+/// Mark a finished session as submitted to the leaderboard and return its server-side result.
+pub fn claim_for_leaderboard(session_id: &str) -> Result<SessionSummary, ClaimError> {
+    let mut store = SESSION_STORE.lock();
+    let session = store.get_mut(session_id).ok_or(ClaimError::NotFound)?;
+    if !session.finished() { return Err(ClaimError::NotFinished); }
+    if session.submitted { return Err(ClaimError::AlreadySubmitted); }
+    session.submitted = true;
+    Ok(session.summary(session_id))
+}
+
+// undo a claim when the leaderboard write failed so the player can retry
+pub fn release_claim(session_id: &str) {
+    if let Some(s) = SESSION_STORE.lock().get_mut(session_id) { s.submitted = false; }
+}
+
+pub fn sweep_expired(now: Instant) {
+    let ttl = session_ttl();
+    SESSION_STORE.lock().retain(|_, s| now.duration_since(s.last_active) <= ttl);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn issued(quiz_id: &str) -> IssuedRound {
+        IssuedRound { quiz_id: quiz_id.to_string(), species: "tanuki".to_string() }
+    }
+
+    fn answered(correct: bool, ms: u64) -> Outcome {
+        Outcome::Answered { correct, response: Duration::from_millis(ms) }
+    }
+
+    #[test]
+    fn test_summary_counts_per_species() {
+        let mut s = Session::new(3, None);
+        for q in ["a", "b", "c"] { s.issued.push(issued(q)); }
+        s.record("a", "tanuki", answered(true, 0));
+        s.record("b", "tanuki", answered(false, 0));
+        assert!(!s.finished());
+        s.record("c", "hakubishin", answered(true, 0));
+        let sum = s.summary("sid");
+        assert!(sum.finished);
+        assert_eq!(sum.score, 2);
+        assert_eq!(sum.per_species["tanuki"].answered, 2);
+        assert_eq!(sum.per_species["tanuki"].accuracy, 0.5);
+        assert_eq!(sum.per_species["hakubishin"].correct, 1);
+        // untimed sessions never award a bonus
+        assert_eq!(sum.speed_bonus, 0);
+    }
+
+    #[test]
+    fn test_timed_rounds_award_speed_bonus() {
+        let limit = Duration::from_secs(10);
+        assert_eq!(speed_bonus(Duration::ZERO, limit), MAX_SPEED_BONUS);
+        assert_eq!(speed_bonus(Duration::from_secs(5), limit), MAX_SPEED_BONUS / 2);
+        assert_eq!(speed_bonus(Duration::from_millis(9999), limit), 1);
+        assert_eq!(speed_bonus(Duration::from_secs(12), limit), 0);
+        let mut s = Session::new(3, Some(limit));
+        for q in ["a", "b", "c"] { s.issued.push(issued(q)); }
+        s.record("a", "tanuki", answered(true, 2000));
+        s.record("b", "tanuki", answered(false, 1000));
+        s.record("c", "anaguma", Outcome::TimedOut);
+        let sum = s.summary("sid");
+        assert!(sum.finished);
+        assert_eq!((sum.score, sum.speed_bonus, sum.timed_out), (1, 8, 1));
+        assert_eq!(sum.time_limit_ms, Some(10_000));
+    }
+
+    #[test]
+    fn test_record_ignores_unknown_and_repeated_rounds() {
+        let mut s = Session::new(2, None);
+        s.issued.push(issued("a"));
+        s.record("a", "tanuki", answered(true, 0));
+        s.record("a", "tanuki", answered(false, 0));
+        s.record("zzz", "anaguma", answered(true, 0));
+        assert_eq!(s.answers.len(), 1);
+        assert_eq!(s.score(), 1);
+    }
+
+    #[test]
+    fn test_start_round_stops_at_round_limit() {
+        let id = create_session(1, None);
+        assert!(matches!(start_round(&id, "q1", "tanuki"), Ok((1, None))));
+        assert!(matches!(start_round(&id, "q2", "tanuki"), Err(RoundError::Complete)));
+        assert!(matches!(start_round("missing", "q3", "tanuki"), Err(RoundError::NotFound)));
+    }
+
+    #[test]
+    fn test_next_round_gives_up_unanswered_ones() {
+        let id = create_session(2, None);
+        start_round(&id, "q1", "tanuki").ok();
+        // q1 was never answered; it counts as wrong and q2 is still the second round
+        assert!(matches!(start_round(&id, "q2", "anaguma"), Ok((2, None))));
+        assert!(!summary(&id).unwrap().finished);
+        // asking past the last round gives up q2 too, so the session can be claimed
+        assert!(matches!(start_round(&id, "q3", "tanuki"), Err(RoundError::Complete)));
+        let sum = summary(&id).unwrap();
+        assert_eq!((sum.finished, sum.answered, sum.score), (true, 2, 0));
+        assert_eq!(record_answer(&id, "q2", "anaguma", answered(true, 0)).map(|p| p.score), Some(0));
+    }
+
+    #[test]
+    fn test_claim_requires_finished_session_once() {
+        let id = create_session(1, None);
+        assert!(matches!(claim_for_leaderboard(&id), Err(ClaimError::NotFinished)));
+        start_round(&id, "q1", "tanuki").ok();
+        record_answer(&id, "q1", "tanuki", answered(true, 0));
+        assert_eq!(claim_for_leaderboard(&id).ok().map(|s| s.score), Some(1));
+        assert!(matches!(claim_for_leaderboard(&id), Err(ClaimError::AlreadySubmitted)));
+        release_claim(&id);
+        assert!(claim_for_leaderboard(&id).is_ok());
+    }
+}
@@ -0,0 +1,189 @@
+// Species registry.
+//
+// Every species the quiz knows about is described once in data/species.json
+// (override with SPECIES_CONFIG_PATH): its key (also the asset filename prefix),
+// display names, scientific name, image search terms, the identification tips shown
+// after an answer and the colours used for the
+// procedural /images/<key>N.png fallback. If the file cannot be loaded the copy
+// bundled into the binary is used so the quiz keeps working.
+
+use image::Rgba;
+use once_cell::sync::Lazy;
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::env;
+use std::path::PathBuf;
+
+const BUNDLED: &str = include_str!("../data/species.json");
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Species {
+    pub key: String,
+    // display names by language code; "ja" is required
+    pub names: BTreeMap<String, String>,
+    pub scientific_name: String,
+    #[serde(default)]
+    pub search_terms: Vec<String>,
+    // short identification tips by language code, shown in quiz results
+    #[serde(default)]
+    pub explanation: BTreeMap<String, String>,
+    // page with more information about the species
+    #[serde(default)]
+    pub info_url: Option<String>,
+    pub render: RenderParams,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct RenderParams {
+    // "#RRGGBB" colours
+    pub background: String,
+    pub face: String,
+    // colour of the mask stripe drawn across the face, if any
+    #[serde(default)]
+    pub mask_stripe: Option<String>,
+}
+
+static REGISTRY: Lazy<RwLock<Vec<Species>>> = Lazy::new(|| RwLock::new(parse(BUNDLED).expect("bundled species.json must be valid")));
+
+pub fn config_path() -> PathBuf {
+    env::var("SPECIES_CONFIG_PATH").map(PathBuf::from).unwrap_or_else(|_| PathBuf::from("data").join("species.json"))
+}
+
+pub fn parse(s: &str) -> Result<Vec<Species>, Vec<String>> {
+    let list: Vec<Species> = serde_json::from_str(s).map_err(|e| vec![format!("invalid species json: {}", e)])?;
+    validate(&list)?;
+    Ok(list)
+}
+
+fn validate(list: &[Species]) -> Result<(), Vec<String>> {
+    let mut errors = Vec::new();
+    if list.len() < 2 { errors.push("at least two species are required for a quiz".to_string()); }
+    let mut seen = HashSet::new();
+    for sp in list {
+        if sp.key.is_empty() || !sp.key.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_') {
+            errors.push(format!("species '{}': key must be lowercase ascii letters, digits or '_'", sp.key));
+        }
+        if !seen.insert(sp.key.clone()) { errors.push(format!("species '{}': duplicate key", sp.key)); }
+        if sp.names.get("ja").map(|n| n.trim().is_empty()).unwrap_or(true) { errors.push(format!("species '{}': names.ja is required", sp.key)); }
+        if let Some(url) = &sp.info_url {
+            if !url.starts_with("https://") && !url.starts_with("http://") { errors.push(format!("species '{}': info_url must be an http(s) URL", sp.key)); }
+        }
+        for c in [Some(&sp.render.background), Some(&sp.render.face), sp.render.mask_stripe.as_ref()].into_iter().flatten() {
+            if parse_color(c).is_none() { errors.push(format!("species '{}': invalid colour '{}'", sp.key, c)); }
+        }
+    }
+    // keys are used as filename prefixes, so one must not be a prefix of another
+    for a in list {
+        for b in list {
+            if a.key != b.key && b.key.starts_with(&a.key) { errors.push(format!("species '{}' is a prefix of '{}'", a.key, b.key)); }
+        }
+    }
+    if errors.is_empty() { Ok(()) } else { Err(errors) }
+}
+
+/// Load the configured registry. On error the current (initially bundled) registry stays active.
+pub fn reload() -> Result<usize, Vec<String>> {
+    let p = config_path();
+    let s = std::fs::read_to_string(&p).map_err(|e| vec![format!("cannot read {}: {}", p.display(), e)])?;
+    let list = parse(&s)?;
+    let n = list.len();
+    *REGISTRY.write() = list;
+    Ok(n)
+}
+
+pub fn all() -> Vec<Species> {
+    REGISTRY.read().clone()
+}
+
+pub fn keys() -> Vec<String> {
+    REGISTRY.read().iter().map(|s| s.key.clone()).collect()
+}
+
+pub fn get(key: &str) -> Option<Species> {
+    REGISTRY.read().iter().find(|s| s.key == key).cloned()
+}
+
+/// Species whose display name in any language matches, e.g. a static catalog answer like "たぬき".
+pub fn by_name(name: &str) -> Option<Species> {
+    REGISTRY.read().iter().find(|s| s.names.values().any(|n| n == name)).cloned()
+}
+
+/// Species for an image key or filename such as "tanuki3" or "hakubishin1.jpg".
+pub fn for_image_key(name: &str) -> Option<Species> {
+    let lower = name.to_lowercase();
+    REGISTRY.read().iter().find(|s| lower.starts_with(&s.key)).cloned()
+}
+
+impl Species {
+    pub fn name(&self, lang: &str) -> &str {
+        self.names.get(lang).or_else(|| self.names.get("ja")).map(|s| s.as_str()).unwrap_or(&self.key)
+    }
+
+    pub fn explanation(&self, lang: &str) -> Option<&str> {
+        self.explanation.get(lang).or_else(|| self.explanation.get("ja")).map(|s| s.as_str())
+    }
+}
+
+pub fn parse_color(s: &str) -> Option<Rgba<u8>> {
+    let hex = s.strip_prefix('#')?;
+    if hex.len() != 6 { return None; }
+    let v = u32::from_str_radix(hex, 16).ok()?;
+    Some(Rgba([(v >> 16) as u8, (v >> 8) as u8, v as u8, 0xFF]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bundled_registry_is_valid() {
+        let list = parse(BUNDLED).unwrap();
+        let keys: Vec<&str> = list.iter().map(|s| s.key.as_str()).collect();
+        assert_eq!(keys, vec!["tanuki", "anaguma", "hakubishin"]);
+    }
+
+    #[test]
+    fn test_for_image_key_and_names() {
+        let sp = for_image_key("Hakubishin3.jpg").unwrap();
+        assert_eq!(sp.key, "hakubishin");
+        assert_eq!(sp.name("ja"), "ハクビシン");
+        assert_eq!(sp.name("fr"), "ハクビシン");
+        assert!(for_image_key("kitsune1.jpg").is_none());
+        assert_eq!(get("tanuki").unwrap().scientific_name, "Nyctereutes procyonoides");
+        assert_eq!(by_name("アナグマ").unwrap().key, "anaguma");
+        assert!(by_name("キツネ").is_none());
+    }
+
+    #[test]
+    fn test_bundled_species_have_explanations() {
+        for sp in parse(BUNDLED).unwrap() {
+            for lang in ["ja", "en"] {
+                assert!(sp.names.contains_key(lang), "{} has no {} name", sp.key, lang);
+                assert!(sp.explanation.contains_key(lang), "{} has no {} explanation", sp.key, lang);
+            }
+            assert!(sp.info_url.is_some(), "{} has no info_url", sp.key);
+        }
+    }
+
+    #[test]
+    fn test_validate_rejects_bad_entries() {
+        let json = r##"[
+            {"key": "kitsune", "names": {"ja": "キツネ"}, "scientific_name": "Vulpes vulpes", "render": {"background": "#FF8800", "face": "#FFFFFF"}},
+            {"key": "kitsune", "names": {"en": "fox"}, "scientific_name": "", "render": {"background": "orange", "face": "#FFFFFF"}},
+            {"key": "kitsune_red", "names": {"ja": "アカギツネ"}, "scientific_name": "", "render": {"background": "#FF0000", "face": "#FFFFFF"}}
+        ]"##;
+        let errs = parse(json).unwrap_err();
+        assert!(errs.iter().any(|e| e.contains("duplicate")));
+        assert!(errs.iter().any(|e| e.contains("names.ja")));
+        assert!(errs.iter().any(|e| e.contains("invalid colour")));
+        assert!(errs.iter().any(|e| e.contains("prefix")));
+    }
+
+    #[test]
+    fn test_parse_color() {
+        assert_eq!(parse_color("#8B4513"), Some(Rgba([0x8B, 0x45, 0x13, 0xFF])));
+        assert!(parse_color("8B4513").is_none());
+        assert!(parse_color("#12345").is_none());
+    }
+}
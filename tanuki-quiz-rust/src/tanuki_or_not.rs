@@ -0,0 +1,182 @@
+// "Tanuki or not?" yes/no mode.
+//
+// GET /api/tanuki_or_not shows a single image and asks whether it is a tanuki. The image is
+// a tanuki with probability TANUKI_RATIO (default 0.5) and otherwise one of the look-alikes
+// from the species registry. Answers are scored and counted here, separately from the
+// pick-one mode, so we can see how often each look-alike is mistaken for a tanuki.
+// Counts live in memory and are written to data/tanuki_or_not_stats.json (override with
+// TANUKI_OR_NOT_STATS_PATH) by the periodic cleanup task, so an answer never waits on the disk.
+
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use rand::seq::SliceRandom;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::env;
+use std::path::{Path, PathBuf};
+
+// species key the question is about
+pub const TARGET: &str = "tanuki";
+
+#[derive(Serialize, Deserialize, Clone, Default, Debug, PartialEq)]
+pub struct SpeciesCounts {
+    pub answered: u64,
+    // how many times players said "yes, this is a tanuki"
+    pub said_yes: u64,
+    pub correct: u64,
+}
+
+#[derive(Serialize)]
+pub struct SpeciesStats {
+    pub answered: u64,
+    pub said_yes: u64,
+    pub said_no: u64,
+    pub correct: u64,
+    pub accuracy: f64,
+    // share of answers that called this species a tanuki; for look-alikes this is the confusion rate
+    pub yes_rate: f64,
+}
+
+#[derive(Serialize)]
+pub struct StatsReport {
+    pub tanuki_ratio: f64,
+    pub answered: u64,
+    pub correct: u64,
+    pub per_species: BTreeMap<String, SpeciesStats>,
+}
+
+#[derive(Default)]
+struct Store {
+    counts: BTreeMap<String, SpeciesCounts>,
+    // changed since the last flush
+    dirty: bool,
+}
+
+static STATS: Lazy<Mutex<Store>> = Lazy::new(|| Mutex::new(Store::default()));
+
+/// Share of questions showing a tanuki, TANUKI_RATIO clamped to 0.0-1.0 (default 0.5).
+pub fn tanuki_ratio() -> f64 {
+    env::var("TANUKI_RATIO").ok().and_then(|s| s.parse::<f64>().ok()).filter(|r| r.is_finite()).unwrap_or(0.5).clamp(0.0, 1.0)
+}
+
+fn stats_path() -> PathBuf {
+    env::var("TANUKI_OR_NOT_STATS_PATH").map(PathBuf::from).unwrap_or_else(|_| PathBuf::from("data").join("tanuki_or_not_stats.json"))
+}
+
+/// Load the counts file. Returns the number of species with counts.
+pub fn load() -> Result<usize, String> {
+    let p = stats_path();
+    let counts = match std::fs::read_to_string(&p) {
+        Ok(s) => serde_json::from_str(&s).map_err(|e| format!("cannot parse {}: {}", p.display(), e))?,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => BTreeMap::new(),
+        Err(e) => return Err(format!("cannot read {}: {}", p.display(), e)),
+    };
+    let mut store = STATS.lock();
+    store.counts = counts;
+    store.dirty = false;
+    Ok(store.counts.len())
+}
+
+/// Write the counts file if anything changed. Write then rename, so a crash never leaves half a file.
+pub fn flush() -> Result<(), String> {
+    let snapshot = {
+        let mut store = STATS.lock();
+        if !store.dirty { return Ok(()); }
+        store.dirty = false;
+        store.counts.clone()
+    };
+    write(&stats_path(), &snapshot).inspect_err(|_| STATS.lock().dirty = true)
+}
+
+fn write(p: &Path, counts: &BTreeMap<String, SpeciesCounts>) -> Result<(), String> {
+    if let Some(dir) = p.parent() { std::fs::create_dir_all(dir).map_err(|e| format!("mkdir error: {}", e))?; }
+    let s = serde_json::to_string_pretty(counts).map_err(|e| e.to_string())?;
+    let tmp = p.with_extension("json.tmp");
+    std::fs::write(&tmp, s).map_err(|e| format!("write error: {}", e))?;
+    std::fs::rename(&tmp, p).map_err(|e| format!("rename error: {}", e))
+}
+
+/// Species to show: the target with probability `ratio`, otherwise a random other species.
+/// Falls back to whatever is available when the registry has no target or no look-alikes.
+pub fn pick_species<R: Rng + ?Sized>(keys: &[String], ratio: f64, rng: &mut R) -> Option<String> {
+    let has_target = keys.iter().any(|k| k == TARGET);
+    let others: Vec<&String> = keys.iter().filter(|k| *k != TARGET).collect();
+    if has_target && (others.is_empty() || rng.gen_bool(ratio)) { return Some(TARGET.to_string()); }
+    others.choose(rng).map(|k| k.to_string())
+}
+
+/// A "yes" answer is correct exactly when the image shows the target species.
+pub fn is_correct(shown: &str, said_yes: bool) -> bool {
+    said_yes == (shown == TARGET)
+}
+
+fn count(counts: &mut BTreeMap<String, SpeciesCounts>, shown: &str, said_yes: bool) {
+    let c = counts.entry(shown.to_string()).or_default();
+    c.answered += 1;
+    if said_yes { c.said_yes += 1; }
+    if is_correct(shown, said_yes) { c.correct += 1; }
+}
+
+pub fn record(shown: &str, said_yes: bool) {
+    let mut store = STATS.lock();
+    count(&mut store.counts, shown, said_yes);
+    store.dirty = true;
+}
+
+fn report_from(counts: BTreeMap<String, SpeciesCounts>, tanuki_ratio: f64) -> StatsReport {
+    let rate = |n: u64, d: u64| if d == 0 { 0.0 } else { n as f64 / d as f64 };
+    let per_species: BTreeMap<String, SpeciesStats> = counts.into_iter().map(|(k, c)| {
+        let stats = SpeciesStats { answered: c.answered, said_yes: c.said_yes, said_no: c.answered - c.said_yes, correct: c.correct, accuracy: rate(c.correct, c.answered), yes_rate: rate(c.said_yes, c.answered) };
+        (k, stats)
+    }).collect();
+    StatsReport {
+        tanuki_ratio,
+        answered: per_species.values().map(|s| s.answered).sum(),
+        correct: per_species.values().map(|s| s.correct).sum(),
+        per_species,
+    }
+}
+
+pub fn report() -> StatsReport {
+    let counts = STATS.lock().counts.clone();
+    report_from(counts, tanuki_ratio())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_pick_species_follows_ratio() {
+        let mut rng = rand::thread_rng();
+        let all = keys(&["tanuki", "anaguma", "hakubishin"]);
+        assert!((0..50).all(|_| pick_species(&all, 1.0, &mut rng).as_deref() == Some("tanuki")));
+        assert!((0..50).all(|_| pick_species(&all, 0.0, &mut rng).as_deref() != Some("tanuki")));
+        let tanukis = (0..2000).filter(|_| pick_species(&all, 0.3, &mut rng).as_deref() == Some("tanuki")).count();
+        assert!((450..750).contains(&tanukis), "got {} tanuki out of 2000", tanukis);
+        assert_eq!(pick_species(&keys(&["tanuki"]), 0.0, &mut rng).as_deref(), Some("tanuki"));
+        assert_eq!(pick_species(&keys(&["anaguma"]), 1.0, &mut rng).as_deref(), Some("anaguma"));
+        assert_eq!(pick_species(&[], 0.5, &mut rng), None);
+    }
+
+    #[test]
+    fn test_counts_and_report() {
+        let mut counts = BTreeMap::new();
+        count(&mut counts, "tanuki", true);
+        count(&mut counts, "tanuki", false);
+        count(&mut counts, "hakubishin", true);
+        count(&mut counts, "hakubishin", true);
+        count(&mut counts, "hakubishin", false);
+        let r = report_from(counts, 0.5);
+        assert_eq!((r.answered, r.correct), (5, 2));
+        assert_eq!(r.per_species["tanuki"].accuracy, 0.5);
+        let civet = &r.per_species["hakubishin"];
+        assert_eq!((civet.said_yes, civet.said_no, civet.correct), (2, 1, 1));
+        assert!((civet.yes_rate - 2.0 / 3.0).abs() < 1e-9);
+    }
+}